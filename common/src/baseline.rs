@@ -0,0 +1,318 @@
+//! Baselines of the frame time percentiles of a scenario, stored per machine and backend, to catch
+//! regressions when something like a backend's dependencies changes.
+//!
+//! A run with `--baseline save` stores its percentiles at
+//! `<dir>/<machine>/<backend>-<scenario>.json`. A run with `--baseline check` compares its
+//! percentiles to the stored ones and fails if the p50 or p99 frame time got slower by more than
+//! the threshold.
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{BenchmarkResults, RunMetadata, Summary};
+
+pub const DEFAULT_BASELINE_DIR: &str = "baselines";
+/// Slowdowns up to this fraction are treated as noise
+pub const DEFAULT_THRESHOLD: f64 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BaselineMode {
+    /// Store the results as the new baseline, replacing the old one
+    Save,
+    /// Compare the results to the baseline
+    Check,
+}
+
+/// Where and how a run is compared to its baseline
+#[derive(Clone, Debug, PartialEq)]
+pub struct BaselineSettings {
+    pub mode: BaselineMode,
+    pub dir: PathBuf,
+    pub machine: String,
+    /// Fraction by which a percentile may be slower than the baseline
+    pub threshold: f64,
+}
+
+impl BaselineSettings {
+    /// The file of the baseline of a run on this machine
+    pub fn path(&self, backend: &str, scenario: &str) -> PathBuf {
+        self.dir.join(file_name(&self.machine)).join(format!(
+            "{}-{}.json",
+            file_name(backend),
+            file_name(scenario)
+        ))
+    }
+}
+
+/// The frame time percentiles of a scenario. With repetitions, every percentile is the mean over
+/// them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Baseline {
+    /// Metadata of the first repetition
+    pub metadata: RunMetadata,
+    pub repetitions: u32,
+    pub cpu_frame_time: Percentiles,
+    /// `None` if not every run has GPU timings
+    pub gpu_frame_time: Option<Percentiles>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Percentiles {
+    pub p50_ms: f64,
+    pub p99_ms: f64,
+}
+
+/// A percentile of a run next to the one of the baseline
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Change {
+    /// Like `CPU p50`
+    pub metric: &'static str,
+    pub baseline_ms: f64,
+    pub current_ms: f64,
+    pub regressed: bool,
+}
+
+impl Change {
+    /// Fraction by which the run is slower than the baseline, negative if it is faster
+    pub fn slowdown(&self) -> f64 {
+        self.current_ms / self.baseline_ms - 1.0
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {:.3}ms -> {:.3}ms ({:+.1}%)",
+            self.metric,
+            self.baseline_ms,
+            self.current_ms,
+            self.slowdown() * 100.0
+        )?;
+        if self.regressed {
+            write!(f, " REGRESSED")?;
+        }
+        Ok(())
+    }
+}
+
+impl Baseline {
+    /// `None` without any runs
+    pub fn of(runs: &[BenchmarkResults]) -> Option<Self> {
+        let first = runs.first()?;
+        let percentiles = |summaries: Vec<Summary>| {
+            let count = summaries.len() as f64;
+            Percentiles {
+                p50_ms: summaries.iter().map(|summary| summary.p50_ms).sum::<f64>() / count,
+                p99_ms: summaries.iter().map(|summary| summary.p99_ms).sum::<f64>() / count,
+            }
+        };
+
+        Some(Self {
+            metadata: first.metadata.clone(),
+            repetitions: runs.len() as u32,
+            cpu_frame_time: percentiles(runs.iter().map(|run| run.cpu_frame_time).collect()),
+            gpu_frame_time: runs
+                .iter()
+                .map(|run| run.gpu_frame_time)
+                .collect::<Option<_>>()
+                .map(percentiles),
+        })
+    }
+
+    pub fn write_json(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()
+    }
+
+    pub fn read_json(path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = io::BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Compares the percentiles of `current` to these. GPU percentiles are only compared if both
+    /// have them.
+    pub fn check(&self, current: &Self, threshold: f64) -> Vec<Change> {
+        let mut changes = Vec::new();
+        let mut compare = |metric, baseline_ms: f64, current_ms: f64| {
+            changes.push(Change {
+                metric,
+                baseline_ms,
+                current_ms,
+                regressed: current_ms > baseline_ms * (1.0 + threshold),
+            })
+        };
+
+        let (baseline_cpu, current_cpu) = (self.cpu_frame_time, current.cpu_frame_time);
+        compare("CPU p50", baseline_cpu.p50_ms, current_cpu.p50_ms);
+        compare("CPU p99", baseline_cpu.p99_ms, current_cpu.p99_ms);
+        if let (Some(baseline_gpu), Some(current_gpu)) =
+            (self.gpu_frame_time, current.gpu_frame_time)
+        {
+            compare("GPU p50", baseline_gpu.p50_ms, current_gpu.p50_ms);
+            compare("GPU p99", baseline_gpu.p99_ms, current_gpu.p99_ms);
+        }
+
+        changes
+    }
+
+    /// Settings of the run that differ from the baseline's, which makes the comparison less
+    /// meaningful
+    pub fn mismatches(&self, current: &Self) -> Vec<&'static str> {
+        let (baseline, current) = (&self.metadata, &current.metadata);
+        [
+            ("adapter", baseline.adapter != current.adapter),
+            ("driver", baseline.driver != current.driver),
+            ("frames", baseline.frames != current.frames),
+            ("MSAA", baseline.msaa_samples != current.msaa_samples),
+            (
+                "present mode",
+                baseline.present_mode != current.present_mode,
+            ),
+            (
+                "max frame latency",
+                baseline.max_frame_latency != current.max_frame_latency,
+            ),
+            (
+                "fixed step",
+                baseline.fixed_step_secs != current.fixed_step_secs,
+            ),
+            (
+                "vertex attributes",
+                baseline.vertex_attributes != current.vertex_attributes,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, differs)| differs.then_some(name))
+        .collect()
+    }
+}
+
+/// The host name, which tells machines apart by default
+pub fn machine_name() -> String {
+    std::env::var("COMPUTERNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .map(|name| name.trim().to_owned())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_owned())
+}
+
+/// Replaces everything but letters, digits, `-`, `_` and `.`, as scene names can be model paths
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::{VertexAttribute, VertexAttributes};
+
+    fn results(p50_ms: f64, p99_ms: f64, gpu: bool) -> BenchmarkResults {
+        let summary = Summary {
+            p50_ms,
+            p99_ms,
+            ..Summary::default()
+        };
+        BenchmarkResults {
+            metadata: RunMetadata {
+                backend: "wgpu".to_owned(),
+                adapter: "adapter".to_owned(),
+                driver: "driver".to_owned(),
+                scene: "triangles".to_owned(),
+                started_at: 0,
+                duration_secs: 1.0,
+                frames: 100,
+                msaa_samples: 1,
+                present_mode: None,
+                max_frame_latency: None,
+                warmup_frames: 0,
+                repetition: 0,
+                seed: None,
+                fixed_step_secs: None,
+                vertex_attributes: vec![VertexAttributes::POSITION_ONLY],
+            },
+            samples: Vec::new(),
+            cpu_frame_time: summary,
+            gpu_frame_time: gpu.then_some(summary),
+            cpu_phases: BTreeMap::new(),
+            buffers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn averages_repetitions() {
+        let baseline = Baseline::of(&[results(2.0, 4.0, true), results(4.0, 8.0, false)]).unwrap();
+        assert_eq!(baseline.repetitions, 2);
+        assert_eq!(
+            baseline.cpu_frame_time,
+            Percentiles {
+                p50_ms: 3.0,
+                p99_ms: 6.0
+            }
+        );
+        assert_eq!(baseline.gpu_frame_time, None);
+        assert!(Baseline::of(&[]).is_none());
+    }
+
+    #[test]
+    fn detects_regressions_beyond_the_threshold() {
+        let baseline = Baseline::of(&[results(2.0, 4.0, true)]).unwrap();
+        let current = Baseline::of(&[results(2.1, 5.0, true)]).unwrap();
+
+        let changes = baseline.check(&current, 0.1);
+        let regressed: Vec<_> = changes
+            .iter()
+            .filter(|change| change.regressed)
+            .map(|change| change.metric)
+            .collect();
+        assert_eq!(changes.len(), 4);
+        assert_eq!(regressed, ["CPU p99", "GPU p99"]);
+        assert!((changes[0].slowdown() - 0.05).abs() < 1e-9);
+
+        assert!(baseline
+            .check(&current, 0.3)
+            .iter()
+            .all(|change| !change.regressed));
+        assert!(baseline.mismatches(&current).is_empty());
+    }
+
+    #[test]
+    fn reports_different_vertex_attributes() {
+        let baseline = Baseline::of(&[results(2.0, 4.0, false)]).unwrap();
+        let mut current = baseline.clone();
+        current.metadata.vertex_attributes =
+            vec![VertexAttributes::POSITION_ONLY.with(VertexAttribute::Normal)];
+
+        assert_eq!(baseline.mismatches(&current), ["vertex attributes"]);
+    }
+
+    #[test]
+    fn paths_are_valid_file_names() {
+        let settings = BaselineSettings {
+            mode: BaselineMode::Check,
+            dir: DEFAULT_BASELINE_DIR.into(),
+            machine: "ci runner".to_owned(),
+            threshold: DEFAULT_THRESHOLD,
+        };
+        assert_eq!(
+            settings.path("wgpu", "/models/sponza.glb"),
+            Path::new("baselines/ci_runner/wgpu-_models_sponza.glb.json")
+        );
+    }
+}
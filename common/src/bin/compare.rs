@@ -0,0 +1,129 @@
+//! Compares exported results of two or more runs in a Markdown or HTML report, see [`USAGE`].
+
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use common::{BenchmarkResults, Report, ReportRun, Timing};
+
+const DEFAULT_OUTPUT: &str = "results/comparison.html";
+
+const USAGE: &str = "\
+Usage: compare [options] <baseline.json> <results.json>...
+
+Compares the frame times of exported runs to the first one.
+
+Options:
+  --timing <timing>  cpu (default) or gpu
+  --output <path>    Where to write the report, Markdown for .md and HTML otherwise
+                     [default: results/comparison.html]
+                     The plots of Markdown reports are written next to them as SVG files
+  --help             Print this help";
+
+fn main() {
+    let mut timing = Timing::Cpu;
+    let mut output = PathBuf::from(DEFAULT_OUTPUT);
+    let mut inputs = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" => {
+                println!("{USAGE}");
+                return;
+            }
+            "--timing" => {
+                timing = match args.next().as_deref() {
+                    Some("cpu") => Timing::Cpu,
+                    Some("gpu") => Timing::Gpu,
+                    _ => usage_error("--timing needs cpu or gpu"),
+                }
+            }
+            "--output" => match args.next() {
+                Some(path) => output = path.into(),
+                None => usage_error("--output needs a value"),
+            },
+            _ if arg.starts_with("--") => usage_error(format!("unknown argument `{arg}`")),
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+
+    let runs = inputs
+        .iter()
+        .map(|path| match BenchmarkResults::read_json(path) {
+            Ok(results) => ReportRun {
+                label: label(path, &inputs),
+                results,
+            },
+            Err(err) => fail(format!("Failed to read {}: {err}", path.display())),
+        })
+        .collect();
+
+    let report = Report::new(runs, timing).unwrap_or_else(|err| fail(err));
+
+    if let Some(dir) = output.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        if let Err(err) = std::fs::create_dir_all(dir) {
+            fail(format!("Failed to create {}: {err}", dir.display()));
+        }
+    }
+
+    if output
+        .extension()
+        .is_some_and(|extension| extension == "md")
+    {
+        let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+        for plot in report.plots() {
+            write(
+                &output.with_file_name(format!("{stem}-{}.svg", plot.name)),
+                &plot.svg,
+            );
+        }
+        write(&output, &report.to_markdown(&stem));
+    } else {
+        write(&output, &report.to_html());
+    }
+
+    for (run, comparison) in inputs[1..].iter().zip(report.comparisons()) {
+        println!(
+            "{}: {:.3}× ({:.3} – {:.3}), p = {:.3}",
+            run.display(),
+            comparison.speedup.ratio,
+            comparison.speedup.ci95_low,
+            comparison.speedup.ci95_high,
+            comparison.mann_whitney.p_value
+        );
+    }
+}
+
+/// The file name without extension, or the whole path if another input has the same file name
+fn label(path: &Path, inputs: &[PathBuf]) -> String {
+    let stem = |path: &Path| path.file_stem().unwrap_or_default().to_owned();
+    let same_stem = inputs
+        .iter()
+        .filter(|input| stem(input) == stem(path))
+        .count();
+
+    if same_stem > 1 {
+        path.display().to_string()
+    } else {
+        stem(path).to_string_lossy().into_owned()
+    }
+}
+
+fn write(path: &Path, contents: &str) {
+    match std::fs::write(path, contents) {
+        Ok(()) => println!("Wrote {}", path.display()),
+        Err(err) => fail(format!("Failed to write {}: {err}", path.display())),
+    }
+}
+
+fn usage_error(message: impl Display) -> ! {
+    eprintln!("{message}\n\n{USAGE}");
+    std::process::exit(2);
+}
+
+fn fail(message: impl Display) -> ! {
+    eprintln!("{message}");
+    std::process::exit(1);
+}
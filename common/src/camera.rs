@@ -1,7 +1,86 @@
+use cgmath::{Matrix4, Point3, Vector3};
+
 // Pitch=0: horizontal
 // Yaw=0: looking to positive x, Yaw=PI: looking to negative x
+#[derive(Clone, Copy, Debug)]
 pub struct Camera {
     pub xyz: (f32, f32, f32),
     pub pitch: f32,
     pub yaw: f32,
-}
\ No newline at end of file
+}
+
+impl Camera {
+    /// The view-projection matrix in column-major order, with depth from 0 to 1 like in wgpu and
+    /// Vulkan. With `reverse_z`, the near plane is mapped to depth 1 and the far plane to 0.
+    /// All backends use this, so that they render exactly the same frames.
+    pub fn view_proj(&self, aspect_ratio: f32, reverse_z: bool) -> [[f32; 4]; 4] {
+        let view = Matrix4::look_to_rh(Point3::from(self.xyz), self.direction(), Vector3::unit_y());
+        let proj = cgmath::perspective(cgmath::Deg(45.0), aspect_ratio, 0.1, 100.0);
+
+        let mut view_proj = OPENGL_TO_WGPU_MATRIX * proj * view;
+        if reverse_z {
+            view_proj = REVERSE_Z_MATRIX * view_proj;
+        }
+
+        view_proj.into()
+    }
+
+    fn direction(&self) -> Vector3<f32> {
+        let (pitch, yaw) = (self.pitch, self.yaw);
+        Vector3::new(
+            yaw.cos() * pitch.cos(),
+            pitch.sin(),
+            pitch.cos() * yaw.sin(),
+        )
+    }
+}
+
+/// Maps depth from OpenGL's -1..1 to 0..1. The Vulkan backend flips the y axis in its shaders.
+#[rustfmt::skip]
+const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.5,
+    0.0, 0.0, 0.0, 1.0,
+);
+
+/// Maps depth `d` to `1 - d`, leaving x, y and w as they are
+#[rustfmt::skip]
+const REVERSE_Z_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, -1.0, 0.0,
+    0.0, 0.0, 1.0, 1.0,
+);
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{InnerSpace, Vector4};
+
+    use super::*;
+
+    #[test]
+    fn reverse_z_maps_depth_d_to_one_minus_d() {
+        let camera = Camera {
+            xyz: (1.0, 2.0, 3.0),
+            pitch: 0.3,
+            yaw: 2.0,
+        };
+        let depth = |distance: f32, reverse_z| {
+            let view_proj = Matrix4::from(camera.view_proj(1.5, reverse_z));
+            let point = Point3::from(camera.xyz) + camera.direction().normalize() * distance;
+            let clip = view_proj * Vector4::new(point.x, point.y, point.z, 1.0);
+            clip.z / clip.w
+        };
+
+        let mut previous = f32::NEG_INFINITY;
+        for distance in [0.1, 1.0, 10.0, 100.0] {
+            assert!(
+                depth(distance, false) > previous,
+                "depth grows with distance"
+            );
+            assert!((depth(distance, true) - (1.0 - depth(distance, false))).abs() < 1e-5);
+            previous = depth(distance, false);
+        }
+    }
+}
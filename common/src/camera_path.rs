@@ -0,0 +1,417 @@
+//! Camera paths through an arbitrary number of timed keyframes. Every pair of consecutive
+//! keyframes is connected by a cubic Bezier segment, either a straight line or a Catmull-Rom
+//! spline segment.
+
+use std::{
+    f32::consts::{PI, TAU},
+    fmt,
+};
+
+use bezier_nd::Bezier;
+use geo_nd::{FArray, Vector};
+use serde::{Deserialize, Serialize};
+
+use crate::Camera;
+
+/// Position, pitch and yaw in radians, so that all of them are interpolated at once
+type Point = [f32; 5];
+
+/// A camera pose at a point in time. Angles are in degrees, as in [`Camera`] but easier to
+/// write by hand.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Keyframe {
+    /// In the unit of the time passed to [`CameraPath::camera_at`]
+    pub at: f32,
+    pub position: [f32; 3],
+    #[serde(default)]
+    pub pitch: f32,
+    pub yaw: f32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    /// Straight lines, the camera changes direction abruptly at every keyframe
+    Linear,
+    /// A smooth curve through all keyframes. The tangent at a keyframe points from its previous
+    /// to its next keyframe, the first and last keyframe use the direction of their segment
+    /// unless the path loops.
+    #[default]
+    CatmullRom,
+}
+
+/// What happens after the last keyframe
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Repeat {
+    /// The camera stays at the last keyframe
+    #[default]
+    Once,
+    /// The path starts over at the first keyframe. The last keyframe should be the same pose as
+    /// the first one, the camera moves smoothly across them.
+    Loop,
+    /// The path is run backwards to the first keyframe and then forwards again
+    PingPong,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "CameraPathDef", into = "CameraPathDef")]
+pub struct CameraPath {
+    keyframes: Vec<Keyframe>,
+    interpolation: Interpolation,
+    repeat: Repeat,
+    /// The keyframes as points. Yaws are unwrapped, so that the difference between consecutive
+    /// keyframes is at most half a turn and the camera always turns the short way.
+    points: Vec<Point>,
+    /// `segments[i]` goes from keyframe `i` to keyframe `i + 1`
+    segments: Vec<Bezier<f32, FArray<f32, 5>, 5>>,
+}
+
+/// How camera paths are written in scenario files
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraPathDef {
+    keyframes: Vec<Keyframe>,
+    #[serde(default)]
+    interpolation: Interpolation,
+    #[serde(default)]
+    repeat: Repeat,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CameraPathError {
+    NoKeyframes,
+    /// Keyframes have to be sorted by time, without two at the same time
+    Unsorted,
+}
+
+impl fmt::Display for CameraPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoKeyframes => write!(f, "camera paths need at least one keyframe"),
+            Self::Unsorted => write!(f, "keyframes have to be in strictly increasing time"),
+        }
+    }
+}
+
+impl std::error::Error for CameraPathError {}
+
+/// The points and segments follow from the rest
+impl PartialEq for CameraPath {
+    fn eq(&self, other: &Self) -> bool {
+        self.keyframes == other.keyframes
+            && self.interpolation == other.interpolation
+            && self.repeat == other.repeat
+    }
+}
+
+impl TryFrom<CameraPathDef> for CameraPath {
+    type Error = CameraPathError;
+
+    fn try_from(def: CameraPathDef) -> Result<Self, Self::Error> {
+        Self::new(def.keyframes, def.interpolation, def.repeat)
+    }
+}
+
+impl From<CameraPath> for CameraPathDef {
+    fn from(path: CameraPath) -> Self {
+        Self {
+            keyframes: path.keyframes,
+            interpolation: path.interpolation,
+            repeat: path.repeat,
+        }
+    }
+}
+
+impl CameraPath {
+    pub fn new(
+        keyframes: Vec<Keyframe>,
+        interpolation: Interpolation,
+        repeat: Repeat,
+    ) -> Result<Self, CameraPathError> {
+        if keyframes.is_empty() {
+            return Err(CameraPathError::NoKeyframes);
+        }
+        if !keyframes.windows(2).all(|pair| pair[0].at < pair[1].at) {
+            return Err(CameraPathError::Unsorted);
+        }
+
+        let mut points: Vec<Point> = keyframes.iter().map(Keyframe::point).collect();
+        for i in 1..points.len() {
+            let turn = (points[i][4] - points[i - 1][4] + PI).rem_euclid(TAU) - PI;
+            points[i][4] = points[i - 1][4] + turn;
+        }
+
+        let segments = (0..keyframes.len() - 1)
+            .map(|i| {
+                let (from, to) = (points[i], points[i + 1]);
+                match interpolation {
+                    Interpolation::Linear => Bezier::line(&from.into(), &to.into()),
+                    Interpolation::CatmullRom => {
+                        // The Hermite segment with the Catmull-Rom tangents as a cubic Bezier
+                        let third = (keyframes[i + 1].at - keyframes[i].at) / 3.0;
+                        let c0 = add(from, tangent(&keyframes, &points, repeat, i), third);
+                        let c1 = add(to, tangent(&keyframes, &points, repeat, i + 1), -third);
+                        Bezier::cubic(&from.into(), &c0.into(), &c1.into(), &to.into())
+                    }
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            keyframes,
+            interpolation,
+            repeat,
+            points,
+            segments,
+        })
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    /// Time from the first to the last keyframe
+    pub fn duration(&self) -> f32 {
+        self.keyframes[self.keyframes.len() - 1].at - self.keyframes[0].at
+    }
+
+    /// The camera at `time`. Before the first keyframe it stays there, after the last one the
+    /// path is repeated as configured.
+    pub fn camera_at(&self, time: f32) -> Camera {
+        let time = self.repeated_time(time);
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.at <= time);
+
+        let point = match next {
+            0 => self.points[0],
+            next if next == self.keyframes.len() => self.points[next - 1],
+            next => {
+                let (from, to) = (self.keyframes[next - 1].at, self.keyframes[next].at);
+                let t = (time - from) / (to - from);
+                self.segments[next - 1].point_at(t).into_array()
+            }
+        };
+
+        Camera {
+            xyz: (point[0], point[1], point[2]),
+            pitch: point[3],
+            yaw: point[4].rem_euclid(TAU),
+        }
+    }
+
+    /// Maps times after the last keyframe back onto the path
+    fn repeated_time(&self, time: f32) -> f32 {
+        let start = self.keyframes[0].at;
+        let duration = self.duration();
+        if time <= start || duration == 0.0 {
+            return time;
+        }
+
+        match self.repeat {
+            Repeat::Once => time,
+            Repeat::Loop => start + (time - start).rem_euclid(duration),
+            Repeat::PingPong => {
+                let phase = (time - start).rem_euclid(2.0 * duration);
+                start + duration - (phase - duration).abs()
+            }
+        }
+    }
+}
+
+impl Keyframe {
+    pub fn from_camera(at: f32, camera: Camera) -> Self {
+        Self {
+            at,
+            position: camera.xyz.into(),
+            pitch: camera.pitch.to_degrees(),
+            yaw: camera.yaw.to_degrees(),
+        }
+    }
+
+    pub fn camera(&self) -> Camera {
+        Camera {
+            xyz: self.position.into(),
+            pitch: self.pitch.to_radians(),
+            yaw: self.yaw.to_radians(),
+        }
+    }
+
+    fn point(&self) -> Point {
+        let [x, y, z] = self.position;
+        [x, y, z, self.pitch.to_radians(), self.yaw.to_radians()]
+    }
+}
+
+/// The velocity at keyframe `i`, per unit of time. When looping, the first and last keyframe are
+/// the same pose, so their neighbors are the keyframes on the other side of the seam.
+fn tangent(keyframes: &[Keyframe], points: &[Point], repeat: Repeat, i: usize) -> Point {
+    let last = keyframes.len() - 1;
+    let wraps = repeat == Repeat::Loop;
+    // Moves points from the end of the path to its start, which also undoes full turns of yaw
+    let seam = add(points[0], points[last], -1.0);
+    let duration = keyframes[last].at - keyframes[0].at;
+
+    let (before_at, before) = match i {
+        0 if wraps => (
+            keyframes[last - 1].at - duration,
+            add(points[last - 1], seam, 1.0),
+        ),
+        0 => (keyframes[0].at, points[0]),
+        i => (keyframes[i - 1].at, points[i - 1]),
+    };
+    let (after_at, after) = match i {
+        i if i == last && wraps => (keyframes[1].at + duration, add(points[1], seam, -1.0)),
+        i if i == last => (keyframes[last].at, points[last]),
+        i => (keyframes[i + 1].at, points[i + 1]),
+    };
+
+    add(after, before, -1.0).map(|difference| difference / (after_at - before_at))
+}
+
+/// `a + b * factor`
+fn add(a: Point, b: Point, factor: f32) -> Point {
+    std::array::from_fn(|i| a[i] + b[i] * factor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframe(at: f32, position: [f32; 3], yaw: f32) -> Keyframe {
+        Keyframe {
+            at,
+            position,
+            pitch: 0.0,
+            yaw,
+        }
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{a} != {b}");
+    }
+
+    #[test]
+    fn catmull_rom_passes_through_keyframes() {
+        let keyframes = vec![
+            keyframe(0.0, [0.0, 0.0, 0.0], 0.0),
+            keyframe(1.0, [1.0, 2.0, 0.0], 90.0),
+            keyframe(3.0, [4.0, 0.0, 1.0], 45.0),
+        ];
+        let path =
+            CameraPath::new(keyframes.clone(), Interpolation::CatmullRom, Repeat::Once).unwrap();
+
+        for keyframe in &keyframes {
+            let camera = path.camera_at(keyframe.at);
+            assert_eq!(camera.xyz, keyframe.camera().xyz);
+            assert_close(camera.yaw, keyframe.camera().yaw);
+        }
+
+        // The tangent at the middle keyframe is horizontal, lines would be at 1.8 and 1.9 here
+        assert!(path.camera_at(0.9).xyz.1 > 1.9);
+        assert!(path.camera_at(1.1).xyz.1 > 1.95);
+    }
+
+    #[test]
+    fn two_keyframes_are_a_straight_line() {
+        let keyframes = vec![
+            keyframe(0.0, [0.0, 0.0, 10.0], 270.0),
+            keyframe(1.0, [10.0, 0.0, 0.0], 180.0),
+        ];
+
+        for interpolation in [Interpolation::Linear, Interpolation::CatmullRom] {
+            let path = CameraPath::new(keyframes.clone(), interpolation, Repeat::Once).unwrap();
+            let camera = path.camera_at(0.25);
+            assert_close(camera.xyz.0, 2.5);
+            assert_close(camera.xyz.2, 7.5);
+            assert_close(camera.yaw, 247.5f32.to_radians());
+        }
+    }
+
+    #[test]
+    fn yaw_turns_the_short_way() {
+        let path = CameraPath::new(
+            vec![
+                keyframe(0.0, [0.0; 3], 350.0),
+                keyframe(1.0, [0.0; 3], 10.0),
+            ],
+            Interpolation::Linear,
+            Repeat::Once,
+        )
+        .unwrap();
+
+        assert_close(path.camera_at(0.25).yaw, 355f32.to_radians());
+        assert_close(path.camera_at(0.75).yaw, 5f32.to_radians());
+    }
+
+    #[test]
+    fn repeats_after_the_last_keyframe() {
+        let keyframes = vec![
+            keyframe(1.0, [0.0; 3], 0.0),
+            keyframe(3.0, [2.0, 0.0, 0.0], 0.0),
+        ];
+        let x_at = |repeat, time| {
+            CameraPath::new(keyframes.clone(), Interpolation::Linear, repeat)
+                .unwrap()
+                .camera_at(time)
+                .xyz
+                .0
+        };
+
+        assert_close(x_at(Repeat::Once, 0.0), 0.0);
+        assert_close(x_at(Repeat::Once, 4.0), 2.0);
+        assert_close(x_at(Repeat::Loop, 4.0), 1.0);
+        assert_close(x_at(Repeat::Loop, 7.5), 0.5);
+        assert_close(x_at(Repeat::PingPong, 4.0), 1.0);
+        assert_close(x_at(Repeat::PingPong, 5.5), 0.5);
+        assert_close(x_at(Repeat::PingPong, 6.0), 1.0);
+    }
+
+    #[test]
+    fn loops_move_smoothly_across_the_seam() {
+        let keyframes = vec![
+            keyframe(0.0, [0.0, 0.0, 0.0], 0.0),
+            keyframe(1.0, [2.0, 1.0, 0.0], 120.0),
+            keyframe(3.0, [2.0, 0.0, 3.0], 240.0),
+            keyframe(4.0, [0.0, 0.0, 0.0], 360.0),
+        ];
+        let path = CameraPath::new(keyframes, Interpolation::CatmullRom, Repeat::Loop).unwrap();
+
+        // Yaw is wrapped around a full turn, so differences are taken the short way
+        let pose = |time| {
+            let camera = path.camera_at(time);
+            [camera.xyz.0, camera.xyz.1, camera.xyz.2, camera.yaw]
+        };
+        let velocity = |from: f32, to: f32| {
+            let (a, b) = (pose(from), pose(to));
+            let mut difference: [f32; 4] = std::array::from_fn(|i| b[i] - a[i]);
+            difference[3] = (difference[3] + PI).rem_euclid(TAU) - PI;
+            difference.map(|difference| difference / (to - from))
+        };
+
+        let step = 1e-3;
+        let before_seam = velocity(4.0 - step, 4.0);
+        let after_seam = velocity(4.0, 4.0 + step);
+        for (before, after) in before_seam.into_iter().zip(after_seam) {
+            assert!((before - after).abs() < 0.02, "{before} != {after}");
+        }
+        // The tangent points from the keyframe before the seam to the one after it
+        assert!((after_seam[2] + 1.5).abs() < 0.02, "{}", after_seam[2]);
+    }
+
+    #[test]
+    fn rejects_invalid_keyframes() {
+        let new = |keyframes| CameraPath::new(keyframes, Interpolation::default(), Repeat::Once);
+
+        assert_eq!(new(Vec::new()), Err(CameraPathError::NoKeyframes));
+        assert_eq!(
+            new(vec![
+                keyframe(1.0, [0.0; 3], 0.0),
+                keyframe(1.0, [1.0; 3], 0.0),
+            ]),
+            Err(CameraPathError::Unsorted)
+        );
+    }
+}
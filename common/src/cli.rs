@@ -0,0 +1,609 @@
+//! The command line interface shared by the benchmark binaries of all backends, so that runs can
+//! be reproduced from a shell script.
+
+use std::{fmt, mem::discriminant, path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    machine_name, BaselineMode, BaselineSettings, ClockMode, DepthCompare, DepthFormat,
+    DepthSettings, PresentMode, RendererSettings, SampleCount, DEFAULT_BASELINE_DIR,
+    DEFAULT_THRESHOLD, MAX_FRAME_LATENCY,
+};
+
+/// Length of windowed runs without `--duration` or `--frames`
+pub const DEFAULT_DURATION: Duration = Duration::from_secs(5);
+/// Length of headless runs without `--duration` or `--frames`
+pub const DEFAULT_HEADLESS_FRAMES: u64 = 1000;
+/// Size of the offscreen target of headless runs without `--size`
+pub const DEFAULT_HEADLESS_SIZE: (u32, u32) = (1280, 720);
+
+const USAGE: &str = "\
+Options:
+  --scenario <path>            Run a scenario file instead of the scene, length and warm-up options
+  --scene <name>               Render a built-in scene: triangles (default)
+  --triangles <count>          Number of random triangles of the triangles scene [default: 1]
+  --model <path>               Render an OBJ, glTF or GLB file instead of a built-in scene
+  --duration <seconds>         Length of the measured run [default: 5 in a window]
+  --frames <count>             Length of the measured run in frames [default: 1000 headless]
+  --warmup <frames>            Frames rendered before measuring starts [default: 0]
+  --repetitions <count>        Measured runs after the warm-up, more than one are also
+                               aggregated into a summary [default: 1]
+  --fixed-step <seconds>       Advance the camera by this much every frame instead of by the
+                               time it took, so that every backend renders the same frames
+  --present-mode <mode>        fifo (default), fifo-relaxed, mailbox or immediate
+  --max-frame-latency <count>  Frames that may be queued up, 1 to 3 [default: 2]
+  --msaa <samples>             1 (default), 2, 4 or 8
+  --depth <format>             depth32-float (default), depth24-plus, depth16-unorm or none
+  --reverse-z                  Map the near plane to depth 1 and the far plane to 0
+  --size <width>x<height>      Window or offscreen target size [default: 1280x720 headless]
+  --output <path>              Where to write the JSON results, a .json file. The CSV is written
+                               next to it [default: results/<backend>-<timestamp>.json]
+                               Repetitions get their index appended, their summary -summary
+  --seed <number>              Seed of all random numbers [default: random]
+  --baseline <mode>            save the results as the baseline of this machine, backend and
+                               scenario, or check them against it and fail on a regression
+  --baseline-dir <path>        Where baselines are stored [default: baselines]
+  --machine <name>             Name of this machine in the baselines [default: host name]
+  --threshold <percent>        How much slower the p50 or p99 frame time may be than the
+                               baseline [default: 10]
+  --headless                   Render into an offscreen target instead of a window
+  --fly                        Start in free-fly mode instead of running the benchmark
+  --help                       Print this help
+
+A run renders the scene for its length, exports the results and exits.
+
+Keys in a window:
+  F                            Toggle free-fly mode, which pauses the run and restarts it after
+  WASD, Q, E                   Fly forwards, sideways, down and up
+  Left mouse button, wheel     Drag to look around, scroll to change the speed
+  R                            Start or stop recording the flight as a replayable scenario file
+  Space, Backspace             Add or remove a random triangle
+  F12                          Save a screenshot";
+
+/// What is rendered
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SceneSource {
+    /// Random triangles, generated from the seed
+    Triangles(usize),
+    Model(PathBuf),
+}
+
+/// How long the measured part of a run is
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RunLength {
+    Duration(Duration),
+    Frames(u64),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cli {
+    /// Describes the scene, length and warm-up instead of the options
+    pub scenario: Option<PathBuf>,
+    pub scene: SceneSource,
+    /// `None` uses [`DEFAULT_DURATION`] in a window and [`DEFAULT_HEADLESS_FRAMES`] headless
+    pub length: Option<RunLength>,
+    pub warmup_frames: u64,
+    pub repetitions: u32,
+    pub clock: ClockMode,
+    pub settings: RendererSettings,
+    /// `None` uses the default window size or [`DEFAULT_HEADLESS_SIZE`]
+    pub size: Option<(u32, u32)>,
+    pub output: Option<PathBuf>,
+    /// `None` picks a random seed, which is recorded in the results
+    pub seed: Option<u64>,
+    pub baseline: Option<BaselineMode>,
+    pub baseline_dir: PathBuf,
+    /// `None` uses the host name
+    pub machine: Option<String>,
+    /// Fraction by which a frame time may be slower than the baseline
+    pub threshold: f64,
+    pub headless: bool,
+    /// Start in free-fly mode, only in a window
+    pub fly: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CliError {
+    /// `--help` was passed, which is not an error but ends parsing just the same
+    Help,
+    UnknownArgument(String),
+    MissingValue(&'static str),
+    InvalidValue {
+        option: &'static str,
+        value: String,
+        expected: &'static str,
+    },
+    Conflict(&'static str, &'static str),
+    /// The first option only has an effect together with the second
+    Requires(&'static str, &'static str),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Help => write!(f, "{USAGE}"),
+            Self::UnknownArgument(argument) => write!(f, "unknown argument `{argument}`"),
+            Self::MissingValue(option) => write!(f, "{option} needs a value"),
+            Self::InvalidValue {
+                option,
+                value,
+                expected,
+            } => write!(
+                f,
+                "invalid value `{value}` for {option}, expected {expected}"
+            ),
+            Self::Conflict(first, second) => {
+                write!(f, "{first} and {second} can not be used together")
+            }
+            Self::Requires(option, required) => write!(f, "{option} requires {required}"),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl Default for Cli {
+    fn default() -> Self {
+        Self {
+            scenario: None,
+            scene: SceneSource::Triangles(1),
+            length: None,
+            warmup_frames: 0,
+            repetitions: 1,
+            clock: ClockMode::RealTime,
+            settings: RendererSettings::default(),
+            size: None,
+            output: None,
+            seed: None,
+            baseline: None,
+            baseline_dir: PathBuf::from(DEFAULT_BASELINE_DIR),
+            machine: None,
+            threshold: DEFAULT_THRESHOLD,
+            headless: false,
+            fly: false,
+        }
+    }
+}
+
+impl Cli {
+    /// Parses the arguments of the process. Prints the usage and exits on `--help` or invalid
+    /// arguments.
+    pub fn from_env() -> Self {
+        match Self::parse(std::env::args().skip(1)) {
+            Ok(cli) => cli,
+            Err(CliError::Help) => {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            Err(err) => {
+                eprintln!("{err}\n\n{USAGE}");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    /// Parses arguments without the program name. Values follow their option either as the next
+    /// argument or after a `=`.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, CliError> {
+        let mut cli = Self::default();
+        let mut builtin_scene = false;
+        let mut triangles = None;
+        let mut model = None;
+        let mut depth_format = Some(DepthFormat::Depth32Float);
+        let mut reverse_z = false;
+        let mut given = Vec::new();
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--help" => return Err(CliError::Help),
+                "--headless" => {
+                    cli.headless = true;
+                    continue;
+                }
+                "--fly" => {
+                    cli.fly = true;
+                    continue;
+                }
+                "--reverse-z" => {
+                    reverse_z = true;
+                    continue;
+                }
+                _ => {}
+            }
+
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_owned())),
+                None => (arg.as_str(), None),
+            };
+            let Some(&option) = OPTIONS.iter().find(|&&option| option == name) else {
+                return Err(CliError::UnknownArgument(arg));
+            };
+            given.push(option);
+
+            let value = inline_value
+                .or_else(|| args.next())
+                .ok_or(CliError::MissingValue(option))?;
+            let invalid = |expected| CliError::InvalidValue {
+                option,
+                value: value.clone(),
+                expected,
+            };
+
+            match option {
+                "--scenario" => cli.scenario = Some(PathBuf::from(&value)),
+                "--scene" => match value.as_str() {
+                    "triangles" => builtin_scene = true,
+                    _ => return Err(invalid("triangles")),
+                },
+                "--triangles" => {
+                    triangles = Some(value.parse().map_err(|_| invalid("a number"))?);
+                }
+                "--model" => model = Some(PathBuf::from(&value)),
+                "--duration" => {
+                    let duration = positive_seconds(&value).ok_or_else(|| invalid("seconds"))?;
+                    set_length(&mut cli.length, RunLength::Duration(duration))?;
+                }
+                "--frames" => {
+                    let frames = value
+                        .parse()
+                        .ok()
+                        .filter(|&frames| frames > 0)
+                        .ok_or_else(|| invalid("a positive number"))?;
+                    set_length(&mut cli.length, RunLength::Frames(frames))?;
+                }
+                "--fixed-step" => {
+                    let step = positive_seconds(&value).ok_or_else(|| invalid("seconds"))?;
+                    cli.clock = ClockMode::FixedStep(step);
+                }
+                "--warmup" => {
+                    cli.warmup_frames = value.parse().map_err(|_| invalid("a number"))?;
+                }
+                "--repetitions" => {
+                    cli.repetitions = value
+                        .parse()
+                        .ok()
+                        .filter(|&repetitions| repetitions > 0)
+                        .ok_or_else(|| invalid("a positive number"))?;
+                }
+                "--present-mode" => {
+                    cli.settings.present_mode = match value.as_str() {
+                        "fifo" => PresentMode::Fifo,
+                        "fifo-relaxed" => PresentMode::FifoRelaxed,
+                        "mailbox" => PresentMode::Mailbox,
+                        "immediate" => PresentMode::Immediate,
+                        _ => return Err(invalid("fifo, fifo-relaxed, mailbox or immediate")),
+                    };
+                }
+                "--max-frame-latency" => {
+                    cli.settings.max_frame_latency = value
+                        .parse()
+                        .ok()
+                        .filter(|latency| (1..=MAX_FRAME_LATENCY).contains(latency))
+                        .ok_or_else(|| invalid("1, 2 or 3"))?;
+                }
+                "--msaa" => {
+                    cli.settings.msaa = value
+                        .parse()
+                        .ok()
+                        .and_then(SampleCount::new)
+                        .ok_or_else(|| invalid("1, 2, 4 or 8"))?;
+                }
+                "--depth" => {
+                    depth_format = match value.as_str() {
+                        "depth32-float" => Some(DepthFormat::Depth32Float),
+                        "depth24-plus" => Some(DepthFormat::Depth24Plus),
+                        "depth16-unorm" => Some(DepthFormat::Depth16Unorm),
+                        "none" => None,
+                        _ => {
+                            return Err(invalid(
+                                "depth32-float, depth24-plus, depth16-unorm or none",
+                            ))
+                        }
+                    };
+                }
+                "--size" => {
+                    cli.size = Some(
+                        value
+                            .split_once('x')
+                            .and_then(|(width, height)| {
+                                Some((width.parse().ok()?, height.parse().ok()?))
+                            })
+                            .filter(|&(width, height)| width > 0 && height > 0)
+                            .ok_or_else(|| invalid("<width>x<height>"))?,
+                    );
+                }
+                "--output" => {
+                    // The CSV is written next to it with the other extension
+                    let path = PathBuf::from(&value);
+                    if path.extension().is_none_or(|extension| extension != "json") {
+                        return Err(invalid("a path ending in .json"));
+                    }
+                    cli.output = Some(path);
+                }
+                "--seed" => cli.seed = Some(value.parse().map_err(|_| invalid("a number"))?),
+                "--baseline" => {
+                    cli.baseline = Some(match value.as_str() {
+                        "save" => BaselineMode::Save,
+                        "check" => BaselineMode::Check,
+                        _ => return Err(invalid("save or check")),
+                    });
+                }
+                "--baseline-dir" => cli.baseline_dir = PathBuf::from(&value),
+                "--machine" => cli.machine = Some(value.clone()),
+                "--threshold" => {
+                    let percent = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|percent| *percent >= 0.0 && percent.is_finite())
+                        .ok_or_else(|| invalid("a percentage"))?;
+                    cli.threshold = percent / 100.0;
+                }
+                _ => unreachable!("{option} is not handled"),
+            }
+        }
+
+        if cli.headless && cli.fly {
+            return Err(CliError::Conflict("--headless", "--fly"));
+        }
+        cli.settings.depth = match depth_format {
+            // Nearer fragments have a greater depth with reverse-Z
+            Some(format) if reverse_z => DepthSettings::new(format, DepthCompare::Greater, true),
+            Some(format) => DepthSettings::new(format, DepthCompare::Less, false),
+            None if reverse_z => return Err(CliError::Requires("--reverse-z", "a depth buffer")),
+            None => None,
+        };
+        if cli.baseline.is_none() {
+            if let Some(&option) = given
+                .iter()
+                .find(|option| BASELINE_OPTIONS.contains(option))
+            {
+                return Err(CliError::Requires(option, "--baseline"));
+            }
+        }
+        if cli.scenario.is_some() {
+            if let Some(&option) = given
+                .iter()
+                .find(|option| SCENARIO_OPTIONS.contains(option))
+            {
+                return Err(CliError::Conflict("--scenario", option));
+            }
+        }
+
+        cli.scene = match (builtin_scene, model) {
+            (true, Some(_)) => return Err(CliError::Conflict("--scene", "--model")),
+            (_, Some(_)) if triangles.is_some() => {
+                return Err(CliError::Conflict("--triangles", "--model"))
+            }
+            (_, Some(path)) => SceneSource::Model(path),
+            (_, None) => SceneSource::Triangles(triangles.unwrap_or(1)),
+        };
+
+        Ok(cli)
+    }
+
+    /// `None` without `--baseline`
+    pub fn baseline_settings(&self) -> Option<BaselineSettings> {
+        Some(BaselineSettings {
+            mode: self.baseline?,
+            dir: self.baseline_dir.clone(),
+            machine: self.machine.clone().unwrap_or_else(machine_name),
+            threshold: self.threshold,
+        })
+    }
+
+    pub fn length(&self) -> RunLength {
+        self.length.unwrap_or(if self.headless {
+            RunLength::Frames(DEFAULT_HEADLESS_FRAMES)
+        } else {
+            RunLength::Duration(DEFAULT_DURATION)
+        })
+    }
+}
+
+/// The options that take a value
+const OPTIONS: &[&str] = &[
+    "--scenario",
+    "--scene",
+    "--triangles",
+    "--model",
+    "--duration",
+    "--frames",
+    "--warmup",
+    "--repetitions",
+    "--fixed-step",
+    "--present-mode",
+    "--max-frame-latency",
+    "--msaa",
+    "--depth",
+    "--size",
+    "--output",
+    "--seed",
+    "--baseline",
+    "--baseline-dir",
+    "--machine",
+    "--threshold",
+];
+
+/// The options that a scenario file replaces
+const SCENARIO_OPTIONS: &[&str] = &[
+    "--scene",
+    "--triangles",
+    "--model",
+    "--duration",
+    "--frames",
+    "--warmup",
+    "--repetitions",
+    "--fixed-step",
+];
+
+/// The options that only configure `--baseline`
+const BASELINE_OPTIONS: &[&str] = &["--baseline-dir", "--machine", "--threshold"];
+
+fn positive_seconds(value: &str) -> Option<Duration> {
+    Duration::try_from_secs_f64(value.parse().ok()?)
+        .ok()
+        .filter(|duration| !duration.is_zero())
+}
+
+fn set_length(length: &mut Option<RunLength>, new: RunLength) -> Result<(), CliError> {
+    let conflicts = length.is_some_and(|length| discriminant(&length) != discriminant(&new));
+    if conflicts {
+        return Err(CliError::Conflict("--duration", "--frames"));
+    }
+
+    *length = Some(new);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Cli, CliError> {
+        Cli::parse(args.split_whitespace().map(str::to_owned))
+    }
+
+    #[test]
+    fn defaults_depend_on_headless() {
+        let cli = parse("").unwrap();
+        assert_eq!(cli, Cli::default());
+        assert_eq!(cli.length(), RunLength::Duration(DEFAULT_DURATION));
+
+        let cli = parse("--headless").unwrap();
+        assert!(cli.headless);
+        assert_eq!(cli.length(), RunLength::Frames(DEFAULT_HEADLESS_FRAMES));
+    }
+
+    #[test]
+    fn parses_all_options() {
+        let cli = parse(
+            "--model scene.glb --frames=500 --warmup 50 --repetitions 3 --present-mode mailbox \
+             --max-frame-latency 1 --msaa 4 --size 800x600 --output out/run.json --seed 7 \
+             --headless",
+        )
+        .unwrap();
+
+        assert_eq!(cli.scene, SceneSource::Model("scene.glb".into()));
+        assert_eq!(cli.length(), RunLength::Frames(500));
+        assert_eq!(cli.warmup_frames, 50);
+        assert_eq!(cli.repetitions, 3);
+        assert_eq!(cli.settings.present_mode, PresentMode::Mailbox);
+        assert_eq!(cli.settings.max_frame_latency, 1);
+        assert_eq!(cli.settings.msaa, SampleCount::new(4).unwrap());
+        assert_eq!(cli.size, Some((800, 600)));
+        assert_eq!(cli.settings.depth, Some(DepthSettings::default()));
+        assert_eq!(cli.output, Some("out/run.json".into()));
+        assert_eq!(cli.seed, Some(7));
+        assert!(cli.headless);
+
+        let cli =
+            parse("--scene triangles --triangles 20 --duration 2.5 --fixed-step 0.01").unwrap();
+        assert_eq!(cli.clock, ClockMode::FixedStep(Duration::from_millis(10)));
+        assert_eq!(cli.scene, SceneSource::Triangles(20));
+        assert_eq!(
+            cli.length(),
+            RunLength::Duration(Duration::from_millis(2500))
+        );
+
+        let cli = parse("--depth depth24-plus --reverse-z").unwrap();
+        assert_eq!(
+            cli.settings.depth,
+            Some(DepthSettings {
+                format: DepthFormat::Depth24Plus,
+                compare: DepthCompare::Greater,
+                reverse_z: true,
+            })
+        );
+        assert_eq!(parse("--depth none").unwrap().settings.depth, None);
+
+        let cli = parse("--scenario scenarios/default.toml --msaa 4").unwrap();
+        assert_eq!(cli.scenario, Some("scenarios/default.toml".into()));
+
+        let cli =
+            parse("--baseline check --baseline-dir ci --machine runner --threshold 5").unwrap();
+        assert_eq!(
+            cli.baseline_settings(),
+            Some(BaselineSettings {
+                mode: BaselineMode::Check,
+                dir: "ci".into(),
+                machine: "runner".to_owned(),
+                threshold: 0.05,
+            })
+        );
+        assert_eq!(parse("").unwrap().baseline_settings(), None);
+    }
+
+    #[test]
+    fn reports_invalid_arguments() {
+        assert_eq!(
+            parse("--fast"),
+            Err(CliError::UnknownArgument("--fast".to_owned()))
+        );
+        assert_eq!(parse("--msaa"), Err(CliError::MissingValue("--msaa")));
+        assert!(matches!(
+            parse("--msaa 3"),
+            Err(CliError::InvalidValue {
+                option: "--msaa",
+                ..
+            })
+        ));
+        for latency in ["0", "4"] {
+            assert!(matches!(
+                parse(&format!("--max-frame-latency {latency}")),
+                Err(CliError::InvalidValue {
+                    option: "--max-frame-latency",
+                    ..
+                })
+            ));
+        }
+        assert!(matches!(
+            parse("--size 800"),
+            Err(CliError::InvalidValue {
+                option: "--size",
+                ..
+            })
+        ));
+        assert_eq!(
+            parse("--duration 5 --frames 100"),
+            Err(CliError::Conflict("--duration", "--frames"))
+        );
+        assert_eq!(
+            parse("--scene triangles --model a.obj"),
+            Err(CliError::Conflict("--scene", "--model"))
+        );
+        assert_eq!(
+            parse("--scenario a.toml --frames 10"),
+            Err(CliError::Conflict("--scenario", "--frames"))
+        );
+        assert_eq!(
+            parse("--fly --headless"),
+            Err(CliError::Conflict("--headless", "--fly"))
+        );
+        assert!(matches!(
+            parse("--output results.csv"),
+            Err(CliError::InvalidValue {
+                option: "--output",
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse("--threshold -5"),
+            Err(CliError::InvalidValue {
+                option: "--threshold",
+                ..
+            })
+        ));
+        assert_eq!(
+            parse("--depth none --reverse-z"),
+            Err(CliError::Requires("--reverse-z", "a depth buffer"))
+        );
+        assert_eq!(
+            parse("--machine runner"),
+            Err(CliError::Requires("--machine", "--baseline"))
+        );
+        assert_eq!(parse("--help"), Err(CliError::Help));
+    }
+}
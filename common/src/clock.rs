@@ -0,0 +1,134 @@
+//! The clock that drives the animation of a run. In real time, a slow backend renders fewer frames
+//! of the camera path than a fast one, so they render different poses. With a fixed step, frame N
+//! always shows the same pose and every backend renders the same frames.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::RunLength;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClockMode {
+    /// The animation follows the wall-clock time
+    #[default]
+    RealTime,
+    /// Every frame advances the animation by the same time, however long it took to render. Runs
+    /// of a duration render that duration divided by the step frames.
+    FixedStep(#[serde(with = "seconds")] Duration),
+}
+
+/// Counts the frames of a run and how much time has passed in it
+#[derive(Clone, Debug)]
+pub struct Clock {
+    mode: ClockMode,
+    started: Instant,
+    frames: u64,
+}
+
+impl Clock {
+    pub fn start(mode: ClockMode) -> Self {
+        Self {
+            mode,
+            started: Instant::now(),
+            frames: 0,
+        }
+    }
+
+    /// Called after every frame
+    pub fn tick(&mut self) {
+        self.frames += 1;
+    }
+
+    /// Frames since the start
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Wall-clock time since the start, regardless of the mode
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// How far a run of `length` has progressed at the current frame, from 0 to 1. Runs of a frame
+    /// count progress by frame in both modes.
+    pub fn progress(&self, length: RunLength) -> f32 {
+        let frames = self.frames as f64;
+        let progress = match (length, self.mode) {
+            (RunLength::Frames(total), _) => frames / total as f64,
+            (RunLength::Duration(duration), ClockMode::RealTime) => {
+                self.elapsed().as_secs_f64() / duration.as_secs_f64()
+            }
+            // By frame instead of by time, so that rounding errors can't add or drop a frame
+            (RunLength::Duration(duration), ClockMode::FixedStep(step)) => {
+                frames
+                    / (duration.as_secs_f64() / step.as_secs_f64())
+                        .round()
+                        .max(1.0)
+            }
+        };
+        progress as f32
+    }
+}
+
+/// Durations as a positive number of seconds
+mod seconds {
+    use std::time::Duration;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        Duration::try_from_secs_f64(f64::deserialize(deserializer)?)
+            .ok()
+            .filter(|duration| !duration.is_zero())
+            .ok_or_else(|| D::Error::custom("expected a positive number of seconds"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_step_progresses_by_frame() {
+        let mut clock = Clock::start(ClockMode::FixedStep(Duration::from_secs_f64(1.0 / 60.0)));
+        let length = RunLength::Duration(Duration::from_secs(10));
+
+        for _ in 0..300 {
+            clock.tick();
+        }
+        assert_eq!(clock.progress(length), 0.5);
+
+        for _ in 300..599 {
+            clock.tick();
+        }
+        assert!(clock.progress(length) < 1.0);
+        clock.tick();
+        assert_eq!(clock.progress(length), 1.0);
+    }
+
+    #[test]
+    fn frame_counts_ignore_the_mode() {
+        for mode in [
+            ClockMode::RealTime,
+            ClockMode::FixedStep(Duration::from_secs(1)),
+        ] {
+            let mut clock = Clock::start(mode);
+            clock.tick();
+            assert_eq!(clock.progress(RunLength::Frames(4)), 0.25);
+        }
+    }
+
+    #[test]
+    fn real_time_follows_the_wall_clock() {
+        let clock = Clock::start(ClockMode::RealTime);
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(clock.progress(RunLength::Duration(Duration::from_millis(20))) >= 1.0);
+        assert_eq!(clock.progress(RunLength::Frames(1)), 0.0);
+    }
+}
@@ -0,0 +1,263 @@
+//! Flying through the scene by hand, and recording the flight as a camera path that can be replayed
+//! on every backend.
+//!
+//! WASD moves, Q and E move down and up, dragging with the left mouse button looks around and the
+//! mouse wheel changes the speed.
+
+use std::{
+    collections::HashSet,
+    f32::consts::FRAC_PI_2,
+    time::{Duration, Instant},
+};
+
+use winit::{
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta},
+    keyboard::{KeyCode, PhysicalKey},
+};
+
+use crate::{windowing::Event, Camera, CameraPath, Interpolation, Keyframe, Repeat};
+
+/// Units per second
+const DEFAULT_SPEED: f32 = 5.0;
+/// Speed factor per line scrolled
+const SPEED_STEP: f32 = 1.2;
+/// Radians per pixel the cursor is moved
+const LOOK_SENSITIVITY: f32 = 0.005;
+/// Looking straight up or down would make the view direction parallel to the up vector
+const MAX_PITCH: f32 = FRAC_PI_2 * 0.99;
+/// Pixels of a scrolled line, for touchpads that scroll by pixels
+const PIXELS_PER_LINE: f64 = 20.0;
+
+/// Time between the keyframes of a recording
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct FreeFlyCamera {
+    camera: Camera,
+    speed: f32,
+    held_keys: HashSet<KeyCode>,
+    looking: bool,
+    /// Last known cursor position, `None` until the cursor moved
+    cursor: Option<(f64, f64)>,
+    last_update: Instant,
+}
+
+impl FreeFlyCamera {
+    pub fn new(camera: Camera) -> Self {
+        Self {
+            camera,
+            speed: DEFAULT_SPEED,
+            held_keys: HashSet::new(),
+            looking: false,
+            cursor: None,
+            last_update: Instant::now(),
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        match event {
+            Event::KeyboardInput(KeyEvent {
+                physical_key: PhysicalKey::Code(key),
+                state,
+                ..
+            }) => match state {
+                ElementState::Pressed => _ = self.held_keys.insert(*key),
+                ElementState::Released => _ = self.held_keys.remove(key),
+            },
+            Event::MouseInput {
+                state,
+                button: MouseButton::Left,
+            } => self.looking = state.is_pressed(),
+            Event::CursorMoved(position) => {
+                if let (true, Some(last)) = (self.looking, self.cursor) {
+                    self.look((position.0 - last.0) as f32, (position.1 - last.1) as f32);
+                }
+                self.cursor = Some(*position);
+            }
+            Event::MouseWheel(delta) => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, lines) => *lines,
+                    MouseScrollDelta::PixelDelta(position) => (position.y / PIXELS_PER_LINE) as f32,
+                };
+                self.speed *= SPEED_STEP.powf(lines);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn camera(&self) -> Camera {
+        self.camera
+    }
+
+    /// Moves the camera by the time since the last update and returns it
+    pub fn update(&mut self) -> Camera {
+        let now = Instant::now();
+        self.advance(now - self.last_update);
+        self.last_update = now;
+        self.camera
+    }
+
+    /// Turns the camera by a cursor movement in pixels
+    fn look(&mut self, dx: f32, dy: f32) {
+        self.camera.yaw += dx * LOOK_SENSITIVITY;
+        self.camera.pitch =
+            (self.camera.pitch - dy * LOOK_SENSITIVITY).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Moves the camera along the view direction and sideways by the keys that are held
+    fn advance(&mut self, elapsed: Duration) {
+        let axis = |positive, negative| {
+            self.held_keys.contains(&positive) as i8 as f32
+                - self.held_keys.contains(&negative) as i8 as f32
+        };
+        let forward = axis(KeyCode::KeyW, KeyCode::KeyS);
+        let right = axis(KeyCode::KeyD, KeyCode::KeyA);
+        let up = axis(KeyCode::KeyE, KeyCode::KeyQ);
+
+        let Camera { pitch, yaw, .. } = self.camera;
+        // The same view direction as in the renderers, right is perpendicular to it on the ground
+        let direction = (
+            yaw.cos() * pitch.cos(),
+            pitch.sin(),
+            pitch.cos() * yaw.sin(),
+        );
+        let sideways = (-yaw.sin(), 0.0, yaw.cos());
+
+        let distance = self.speed * elapsed.as_secs_f32();
+        let (x, y, z) = &mut self.camera.xyz;
+        *x += (forward * direction.0 + right * sideways.0) * distance;
+        *y += (forward * direction.1 + up) * distance;
+        *z += (forward * direction.2 + right * sideways.2) * distance;
+    }
+}
+
+/// Samples the camera while flying, to replay the flight as a [`CameraPath`]
+pub struct PathRecorder {
+    started: Instant,
+    /// Seconds since the start of the recording
+    samples: Vec<(f32, Camera)>,
+}
+
+impl PathRecorder {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            samples: Vec::new(),
+        }
+    }
+
+    /// Records the camera of a frame, if the last sample is long enough ago
+    pub fn sample(&mut self, camera: Camera) {
+        self.sample_at(self.started.elapsed(), camera);
+    }
+
+    /// Ends the recording at the pose of `camera`. Returns how long it took and the path with its
+    /// keyframes at fractions of that, or `None` if nothing was recorded.
+    pub fn finish(self, camera: Camera) -> Option<(Duration, CameraPath)> {
+        let elapsed = self.started.elapsed();
+        self.finish_at(elapsed, camera)
+    }
+
+    fn sample_at(&mut self, time: Duration, camera: Camera) {
+        let time = time.as_secs_f32();
+        let due = self
+            .samples
+            .last()
+            .is_none_or(|&(last, _)| time - last >= SAMPLE_INTERVAL.as_secs_f32());
+        if due {
+            self.samples.push((time, camera));
+        }
+    }
+
+    fn finish_at(mut self, time: Duration, camera: Camera) -> Option<(Duration, CameraPath)> {
+        if self
+            .samples
+            .last()
+            .is_some_and(|&(last, _)| time.as_secs_f32() > last)
+        {
+            self.samples.push((time.as_secs_f32(), camera));
+        }
+
+        let &(start, _) = self.samples.first()?;
+        let &(end, _) = self.samples.last()?;
+        if end <= start {
+            return None;
+        }
+
+        let keyframes = self
+            .samples
+            .iter()
+            .map(|&(time, camera)| Keyframe::from_camera((time - start) / (end - start), camera))
+            .collect();
+        let path = CameraPath::new(keyframes, Interpolation::CatmullRom, Repeat::Once).ok()?;
+
+        Some((Duration::from_secs_f32(end - start), path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera(x: f32, yaw: f32) -> Camera {
+        Camera {
+            xyz: (x, 0.0, 0.0),
+            pitch: 0.0,
+            yaw,
+        }
+    }
+
+    #[test]
+    fn moves_along_the_view_direction() {
+        let mut fly = FreeFlyCamera::new(camera(0.0, 0.0));
+        fly.held_keys.insert(KeyCode::KeyW);
+        fly.advance(Duration::from_secs(1));
+        let (x, y, z) = fly.camera.xyz;
+        assert!((x - DEFAULT_SPEED).abs() < 1e-4 && y.abs() < 1e-4 && z.abs() < 1e-4);
+
+        // Looking along negative z, right is positive x
+        let mut fly = FreeFlyCamera::new(camera(0.0, 3.0 * FRAC_PI_2));
+        fly.held_keys.extend([KeyCode::KeyD, KeyCode::KeyE]);
+        fly.advance(Duration::from_secs(2));
+        let (x, y, z) = fly.camera.xyz;
+        assert!((x - 2.0 * DEFAULT_SPEED).abs() < 1e-4);
+        assert!((y - 2.0 * DEFAULT_SPEED).abs() < 1e-4);
+        assert!(z.abs() < 1e-4);
+    }
+
+    #[test]
+    fn pitch_is_clamped() {
+        let mut fly = FreeFlyCamera::new(camera(0.0, 0.0));
+        fly.look(100.0, -1e6);
+        assert_eq!(fly.camera.pitch, MAX_PITCH);
+        assert!((fly.camera.yaw - 100.0 * LOOK_SENSITIVITY).abs() < 1e-6);
+    }
+
+    #[test]
+    fn recordings_become_camera_paths() {
+        let mut recorder = PathRecorder::new();
+        for (millis, x) in [(0, 0.0), (50, 0.5), (100, 1.0), (200, 2.0)] {
+            recorder.sample_at(Duration::from_millis(1000 + millis), camera(x, 0.0));
+        }
+        let (duration, path) = recorder
+            .finish_at(Duration::from_millis(1250), camera(2.5, 0.0))
+            .unwrap();
+
+        assert_eq!(duration, Duration::from_secs_f32(0.25));
+        let times: Vec<_> = path
+            .keyframes()
+            .iter()
+            .map(|keyframe| keyframe.at)
+            .collect();
+        assert_eq!(
+            times.len(),
+            4,
+            "samples closer than the interval are skipped"
+        );
+        assert_eq!((times[0], times[3]), (0.0, 1.0));
+        assert!((path.camera_at(0.8).xyz.0 - 2.0).abs() < 1e-4);
+
+        assert!(PathRecorder::new()
+            .finish_at(Duration::ZERO, camera(0.0, 0.0))
+            .is_none());
+    }
+}
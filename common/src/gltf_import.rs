@@ -0,0 +1,347 @@
+//! An importer for glTF 2.0 scenes, both `.gltf` with external or embedded buffers and `.glb`.
+//!
+//! The node hierarchy of the scene is flattened: every primitive of every node becomes its own
+//! [`Mesh`] in world space. Meshes that are used by several nodes are thus duplicated, which is
+//! what the renderers would have to draw anyway. Normals, tangents, the first set of texture
+//! coordinates and vertex colors are imported, materials, textures and animations are ignored.
+
+use std::{fmt, path::Path};
+
+use gltf::mesh::Mode;
+
+use crate::{Mesh, Vertex};
+
+/// A column-major 4x4 matrix, as used by glTF
+type Matrix = [[f32; 4]; 4];
+
+const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+#[derive(Debug)]
+pub enum GltfError {
+    /// The file or one of its buffers could not be read or parsed
+    Gltf(gltf::Error),
+    /// The file does not contain any scenes
+    NoScene,
+    /// A primitive has no positions
+    MissingPositions { mesh: usize, primitive: usize },
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Gltf(err) => write!(f, "failed to load glTF file: {err}"),
+            Self::NoScene => write!(f, "glTF file does not contain any scenes"),
+            Self::MissingPositions { mesh, primitive } => {
+                write!(f, "primitive {primitive} of mesh {mesh} has no positions")
+            }
+        }
+    }
+}
+
+impl std::error::Error for GltfError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Gltf(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<gltf::Error> for GltfError {
+    fn from(err: gltf::Error) -> Self {
+        Self::Gltf(err)
+    }
+}
+
+/// Imports the default scene, or the first one if there is no default
+pub fn load_gltf(path: impl AsRef<Path>) -> Result<Vec<Mesh>, GltfError> {
+    let path = path.as_ref();
+    let gltf::Gltf { document, blob } = gltf::Gltf::open(path)?;
+    // Images are not needed, so only the buffers are loaded
+    let buffers = gltf::import_buffers(&document, path.parent(), blob)?;
+
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or(GltfError::NoScene)?;
+
+    let mut meshes = Vec::new();
+    for node in scene.nodes() {
+        import_node(&node, &IDENTITY, &buffers, &mut meshes)?;
+    }
+
+    Ok(meshes)
+}
+
+fn import_node(
+    node: &gltf::Node,
+    parent_transform: &Matrix,
+    buffers: &[gltf::buffer::Data],
+    meshes: &mut Vec<Mesh>,
+) -> Result<(), GltfError> {
+    let transform = multiply(parent_transform, &node.transform().matrix());
+
+    if let Some(mesh) = node.mesh() {
+        for primitive in mesh.primitives() {
+            if let Some(imported) = import_primitive(&mesh, &primitive, &transform, buffers)? {
+                meshes.push(imported);
+            }
+        }
+    }
+
+    for child in node.children() {
+        import_node(&child, &transform, buffers, meshes)?;
+    }
+
+    Ok(())
+}
+
+/// Returns `None` for primitives that are not made of triangles
+fn import_primitive(
+    mesh: &gltf::Mesh,
+    primitive: &gltf::Primitive,
+    transform: &Matrix,
+    buffers: &[gltf::buffer::Data],
+) -> Result<Option<Mesh>, GltfError> {
+    let mode = primitive.mode();
+    if !matches!(
+        mode,
+        Mode::Triangles | Mode::TriangleStrip | Mode::TriangleFan
+    ) {
+        return Ok(None);
+    }
+
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+
+    let vertices: Vec<Vertex> = reader
+        .read_positions()
+        .ok_or(GltfError::MissingPositions {
+            mesh: mesh.index(),
+            primitive: primitive.index(),
+        })?
+        .map(|position| Vertex {
+            xyz: transform_point(transform, position),
+        })
+        .collect();
+
+    let normal_matrix = normal_matrix(transform);
+    let normals = reader.read_normals().map(|normals| {
+        normals
+            .map(|normal| transform_normal(&normal_matrix, normal))
+            .collect()
+    });
+
+    // Tangents follow the surface like positions do, and the bitangent flips with mirroring
+    let handedness = determinant(transform).signum();
+    let tangents = reader.read_tangents().map(|tangents| {
+        tangents
+            .map(|[x, y, z, w]| {
+                let [x, y, z] = normalize(transform_direction(transform, [x, y, z]));
+                [x, y, z, w * handedness]
+            })
+            .collect()
+    });
+
+    let tex_coords = reader
+        .read_tex_coords(0)
+        .map(|tex_coords| tex_coords.into_f32().collect());
+
+    let colors = reader
+        .read_colors(0)
+        .map(|colors| colors.into_rgba_f32().collect());
+
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..vertices.len() as u32).collect(),
+    };
+    let mut indices = triangle_list(mode, &indices);
+
+    // Mirroring transforms turn the triangles inside out
+    if determinant(transform) < 0.0 {
+        for triangle in indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+    }
+
+    Ok(Some(Mesh {
+        indices,
+        vertices,
+        normals,
+        tangents,
+        tex_coords,
+        colors,
+    }))
+}
+
+/// Converts strips and fans into lists, keeping the winding order of the triangles
+fn triangle_list(mode: Mode, indices: &[u32]) -> Vec<u32> {
+    match mode {
+        Mode::TriangleStrip => (0..indices.len().saturating_sub(2))
+            .flat_map(|i| {
+                if i % 2 == 0 {
+                    [indices[i], indices[i + 1], indices[i + 2]]
+                } else {
+                    [indices[i + 1], indices[i], indices[i + 2]]
+                }
+            })
+            .collect(),
+        Mode::TriangleFan => (1..indices.len().saturating_sub(1))
+            .flat_map(|i| [indices[0], indices[i], indices[i + 1]])
+            .collect(),
+        _ => indices[..indices.len() - indices.len() % 3].to_vec(),
+    }
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut result = [[0.0; 4]; 4];
+    for (column, result_column) in result.iter_mut().enumerate() {
+        for (row, value) in result_column.iter_mut().enumerate() {
+            *value = (0..4).map(|i| a[i][row] * b[column][i]).sum();
+        }
+    }
+    result
+}
+
+fn transform_point(m: &Matrix, [x, y, z]: [f32; 3]) -> [f32; 3] {
+    [0, 1, 2].map(|row| m[0][row] * x + m[1][row] * y + m[2][row] * z + m[3][row])
+}
+
+/// Like [`transform_point`], but without the translation
+fn transform_direction(m: &Matrix, [x, y, z]: [f32; 3]) -> [f32; 3] {
+    [0, 1, 2].map(|row| m[0][row] * x + m[1][row] * y + m[2][row] * z)
+}
+
+/// Determinant of the upper 3x3 part
+fn determinant(m: &Matrix) -> f32 {
+    m[0][0] * (m[1][1] * m[2][2] - m[2][1] * m[1][2])
+        - m[1][0] * (m[0][1] * m[2][2] - m[2][1] * m[0][2])
+        + m[2][0] * (m[0][1] * m[1][2] - m[1][1] * m[0][2])
+}
+
+/// The inverse transpose of the upper 3x3 part up to a positive factor, as a column-major matrix
+fn normal_matrix(m: &Matrix) -> [[f32; 3]; 3] {
+    // Columns of the upper 3x3 part
+    let [c0, c1, c2] = [0, 1, 2].map(|column| [m[column][0], m[column][1], m[column][2]]);
+
+    // The rows of the inverse are the cross products of the columns divided by the determinant,
+    // so they are the columns of the inverse transpose
+    let sign = determinant(m).signum();
+    [cross(c1, c2), cross(c2, c0), cross(c0, c1)].map(|column| column.map(|value| value * sign))
+}
+
+fn transform_normal(normal_matrix: &[[f32; 3]; 3], [x, y, z]: [f32; 3]) -> [f32; 3] {
+    let m = normal_matrix;
+    normalize([0, 1, 2].map(|row| m[0][row] * x + m[1][row] * y + m[2][row] * z))
+}
+
+fn normalize(vector: [f32; 3]) -> [f32; 3] {
+    let length = vector.iter().map(|value| value * value).sum::<f32>().sqrt();
+    if length > 0.0 {
+        vector.map(|value| value / length)
+    } else {
+        vector
+    }
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VertexAttributes;
+
+    /// A triangle with 16-bit indices that is used by a mirrored child node and a root node
+    const SCENE: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0, 2] }],
+        "nodes": [
+            { "translation": [0, 0, 5], "children": [1] },
+            { "mesh": 0, "scale": [-1, 1, 1] },
+            { "mesh": 0 }
+        ],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1 }] }],
+        "accessors": [
+            {
+                "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                "min": [0, 0, 0], "max": [1, 1, 0]
+            },
+            { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }
+        ],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 6 }
+        ],
+        "buffers": [{
+            "byteLength": 44,
+            "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIAAAA="
+        }]
+    }"#;
+
+    #[test]
+    fn flattens_node_transforms() {
+        let path = std::env::temp_dir().join("common-gltf-import-test.gltf");
+        std::fs::write(&path, SCENE).unwrap();
+        let meshes = load_gltf(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(meshes.len(), 2);
+
+        let mirrored = &meshes[0];
+        let positions: Vec<_> = mirrored.vertices.iter().map(|vertex| vertex.xyz).collect();
+        assert_eq!(
+            positions,
+            [[0.0, 0.0, 5.0], [-1.0, 0.0, 5.0], [0.0, 1.0, 5.0]]
+        );
+        // The winding order is flipped to undo the mirroring
+        assert_eq!(mirrored.indices, [0, 2, 1]);
+        assert_eq!(mirrored.attributes(), VertexAttributes::POSITION_ONLY);
+
+        let untransformed = &meshes[1];
+        assert_eq!(untransformed.vertices[1].xyz, [1.0, 0.0, 0.0]);
+        assert_eq!(untransformed.indices, [0, 1, 2]);
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_to_scaled_surfaces() {
+        // Scaling x by 2 turns the plane x + y = 1 into x / 2 + y = 1, with the normal (1, 2)
+        let mut transform = IDENTITY;
+        transform[0][0] = 2.0;
+
+        let normal = transform_normal(
+            &normal_matrix(&transform),
+            [0.5f32.sqrt(), 0.5f32.sqrt(), 0.0],
+        );
+
+        let expected = [1.0 / 5.0f32.sqrt(), 2.0 / 5.0f32.sqrt(), 0.0];
+        for (actual, expected) in normal.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < 1e-6,
+                "{normal:?} != {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn strips_and_fans_become_lists() {
+        assert_eq!(
+            triangle_list(Mode::TriangleStrip, &[0, 1, 2, 3]),
+            [0, 1, 2, 2, 1, 3]
+        );
+        assert_eq!(
+            triangle_list(Mode::TriangleFan, &[0, 1, 2, 3]),
+            [0, 1, 2, 0, 2, 3]
+        );
+    }
+}
@@ -0,0 +1,277 @@
+//! Golden-image tests that check whether all backends draw the same thing.
+//!
+//! A deterministic scene is rendered offscreen and compared against a PNG stored in the
+//! repository. All backends share the same golden images, so a backend that passes draws the
+//! same image as every other backend that passes. Run the tests with `UPDATE_GOLDEN=1` to
+//! (re)write the golden images from the current output.
+//!
+//! The GPU backends skip their tests if no adapter or device is found. Machines without a GPU,
+//! like CI runners, can run them on Mesa's software rasterizers: lavapipe provides a Vulkan
+//! device for both backends (e.g. the `mesa-vulkan-drivers` package, select it with
+//! `VK_ICD_FILENAMES` if there are several drivers), and `WGPU_BACKEND=gl` makes the wgpu backend
+//! use llvmpipe through OpenGL instead.
+
+use std::{
+    f32::consts::PI,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{Camera, Image, Mesh, Renderer, RendererSettings};
+
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../golden");
+/// Where the actual and diff images of failed comparisons are written to
+const FAILURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../golden/failures");
+const UPDATE_ENV_VAR: &str = "UPDATE_GOLDEN";
+
+pub const GOLDEN_SIZE: (u32, u32) = (256, 256);
+
+/// A scene that renders the same on every run
+pub struct GoldenScene {
+    pub name: &'static str,
+    pub seed: u64,
+    pub triangles: usize,
+    pub camera: Camera,
+}
+
+impl GoldenScene {
+    /// Random triangles, viewed from the direction the camera path of the app starts in
+    pub fn triangles() -> Self {
+        Self {
+            name: "triangles",
+            seed: 0,
+            triangles: 8,
+            camera: Camera {
+                xyz: (0.0, 0.0, 3.0),
+                pitch: 0.0,
+                yaw: PI * 3.0 / 2.0,
+            },
+        }
+    }
+
+    /// Renders a single frame of the scene and reads it back. The renderer must not have any
+    /// meshes loaded yet.
+    pub fn render(&self, renderer: &mut impl Renderer) -> Image {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        for _ in 0..self.triangles {
+            renderer.load_mesh(Mesh::random_triangle(&mut rng));
+        }
+
+        renderer.request_capture();
+        renderer.render(self.camera);
+        renderer
+            .capture()
+            .expect("renderer did not capture the rendered frame")
+    }
+
+    fn golden_path(&self) -> PathBuf {
+        Path::new(GOLDEN_DIR).join(format!("{}.png", self.name))
+    }
+}
+
+/// How much an image may deviate from its golden image. Edges of triangles are allowed to differ
+/// slightly, as rasterization rules are not exactly the same on all drivers.
+#[derive(Clone, Copy, Debug)]
+pub struct Tolerance {
+    /// Pixels with a larger difference in any channel count as mismatched
+    pub max_channel_delta: u8,
+    /// Fraction of pixels that may be mismatched
+    pub max_mismatched_fraction: f64,
+    /// Minimum peak signal-to-noise ratio in dB
+    pub min_psnr: f64,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            max_channel_delta: 8,
+            max_mismatched_fraction: 0.005,
+            min_psnr: 35.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    pub max_channel_delta: u8,
+    pub mismatched_pixels: usize,
+    pub total_pixels: usize,
+    /// Infinite if the images are identical
+    pub psnr: f64,
+    /// Mismatched pixels in red on top of the dimmed expected image
+    pub diff: Image,
+}
+
+impl Comparison {
+    /// Both images must have the same size
+    pub fn new(actual: &Image, expected: &Image, tolerance: &Tolerance) -> Self {
+        assert_eq!(
+            (actual.width, actual.height),
+            (expected.width, expected.height),
+            "compared images differ in size"
+        );
+
+        let mut max_channel_delta = 0;
+        let mut mismatched_pixels = 0;
+        let mut squared_error_sum = 0.0;
+        let mut diff = Vec::with_capacity(expected.data.len());
+
+        let pixel_len = Image::BYTES_PER_PIXEL as usize;
+        for (actual, expected) in actual
+            .data
+            .chunks_exact(pixel_len)
+            .zip(expected.data.chunks_exact(pixel_len))
+        {
+            let mut pixel_delta = 0;
+            for (&a, &e) in actual.iter().zip(expected) {
+                let delta = a.abs_diff(e);
+                pixel_delta = pixel_delta.max(delta);
+                squared_error_sum += f64::from(delta).powi(2);
+            }
+            max_channel_delta = max_channel_delta.max(pixel_delta);
+
+            if pixel_delta > tolerance.max_channel_delta {
+                mismatched_pixels += 1;
+                diff.extend_from_slice(&[255, 0, 0, 255]);
+            } else {
+                let luma = (0.299 * f32::from(expected[0])
+                    + 0.587 * f32::from(expected[1])
+                    + 0.114 * f32::from(expected[2]))
+                    / 4.0;
+                diff.extend_from_slice(&[luma as u8, luma as u8, luma as u8, 255]);
+            }
+        }
+
+        let mean_squared_error = squared_error_sum / expected.data.len() as f64;
+        let psnr = 10.0 * (255.0f64.powi(2) / mean_squared_error).log10();
+
+        Self {
+            max_channel_delta,
+            mismatched_pixels,
+            total_pixels: expected.data.len() / pixel_len,
+            psnr,
+            diff: Image::new(expected.width, expected.height, diff),
+        }
+    }
+
+    pub fn mismatched_fraction(&self) -> f64 {
+        self.mismatched_pixels as f64 / self.total_pixels as f64
+    }
+
+    pub fn passes(&self, tolerance: &Tolerance) -> bool {
+        self.mismatched_fraction() <= tolerance.max_mismatched_fraction
+            && self.psnr >= tolerance.min_psnr
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} pixels mismatched ({:.3}%) | max channel delta {} | PSNR {:.2}dB",
+            self.mismatched_pixels,
+            self.total_pixels,
+            self.mismatched_fraction() * 100.0,
+            self.max_channel_delta,
+            self.psnr
+        )
+    }
+}
+
+/// Renders the scene with `R` and panics if the result does not match the golden image.
+/// On failure, the actual image and a diff image are written next to the golden images.
+pub fn assert_matches_golden<R: Renderer>(scene: &GoldenScene, tolerance: &Tolerance) {
+    let mut renderer = R::new_headless(GOLDEN_SIZE, &RendererSettings::default());
+    let actual = scene.render(&mut renderer);
+    let golden_path = scene.golden_path();
+
+    if std::env::var_os(UPDATE_ENV_VAR).is_some() {
+        std::fs::create_dir_all(GOLDEN_DIR).unwrap();
+        actual.write_png(&golden_path).unwrap();
+        println!("Updated {}", golden_path.display());
+        return;
+    }
+
+    let expected = Image::read_png(&golden_path).unwrap_or_else(|err| {
+        panic!(
+            "failed to read golden image {}: {err}\nRun with {UPDATE_ENV_VAR}=1 to create it",
+            golden_path.display()
+        )
+    });
+
+    let backend = renderer.info().backend;
+    let failure_path =
+        |kind: &str| Path::new(FAILURES_DIR).join(format!("{}-{backend}-{kind}.png", scene.name));
+
+    if (actual.width, actual.height) != (expected.width, expected.height) {
+        std::fs::create_dir_all(FAILURES_DIR).unwrap();
+        actual.write_png(failure_path("actual")).unwrap();
+        panic!(
+            "{} is {}x{}, but the golden image is {}x{}",
+            scene.name, actual.width, actual.height, expected.width, expected.height
+        );
+    }
+
+    let comparison = Comparison::new(&actual, &expected, tolerance);
+    println!("{}: {comparison}", scene.name);
+
+    if !comparison.passes(tolerance) {
+        std::fs::create_dir_all(FAILURES_DIR).unwrap();
+        actual.write_png(failure_path("actual")).unwrap();
+        comparison.diff.write_png(failure_path("diff")).unwrap();
+        panic!(
+            "{} does not match its golden image: {comparison}\nWrote the actual and diff images to {}",
+            scene.name,
+            Path::new(FAILURES_DIR).display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, rgba: [u8; 4]) -> Image {
+        Image::new(width, height, rgba.repeat((width * height) as usize))
+    }
+
+    #[test]
+    fn identical_images_match() {
+        let image = solid(4, 4, [10, 20, 30, 255]);
+        let comparison = Comparison::new(&image, &image, &Tolerance::default());
+
+        assert_eq!(comparison.mismatched_pixels, 0);
+        assert_eq!(comparison.max_channel_delta, 0);
+        assert!(comparison.psnr.is_infinite());
+        assert!(comparison.passes(&Tolerance::default()));
+    }
+
+    #[test]
+    fn mismatched_pixels_are_counted_and_marked() {
+        let expected = solid(10, 10, [0, 0, 0, 255]);
+        let mut actual = expected.clone();
+        actual.data[..4].copy_from_slice(&[200, 0, 0, 255]);
+
+        let comparison = Comparison::new(&actual, &expected, &Tolerance::default());
+
+        assert_eq!(comparison.mismatched_pixels, 1);
+        assert_eq!(comparison.max_channel_delta, 200);
+        assert_eq!(&comparison.diff.data[..4], &[255, 0, 0, 255]);
+        assert_eq!(&comparison.diff.data[4..8], &[0, 0, 0, 255]);
+        assert!(!comparison.passes(&Tolerance::default()));
+    }
+
+    #[test]
+    fn small_deltas_are_tolerated() {
+        let expected = solid(10, 10, [100, 100, 100, 255]);
+        let actual = solid(10, 10, [102, 99, 100, 255]);
+
+        let comparison = Comparison::new(&actual, &expected, &Tolerance::default());
+
+        assert_eq!(comparison.mismatched_pixels, 0);
+        assert!(comparison.psnr > 40.0);
+        assert!(comparison.passes(&Tolerance::default()));
+    }
+}
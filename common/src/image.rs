@@ -0,0 +1,93 @@
+//! CPU-side images, used to read back frames from a renderer.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::Path,
+};
+
+/// An 8-bit RGBA image whose rows are tightly packed from top to bottom.
+/// The color values are stored as they were in the render target, which is sRGB-encoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    pub const BYTES_PER_PIXEL: u32 = 4;
+
+    pub fn new(width: u32, height: u32, data: Vec<u8>) -> Self {
+        assert_eq!(
+            data.len(),
+            (width * height * Self::BYTES_PER_PIXEL) as usize,
+            "image data does not match its size"
+        );
+
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Copies an image whose rows are `bytes_per_row` apart, e.g. because of the row alignment
+    /// required for texture to buffer copies
+    pub fn from_padded_rows(width: u32, height: u32, bytes_per_row: u32, data: &[u8]) -> Self {
+        let row_len = (width * Self::BYTES_PER_PIXEL) as usize;
+        assert!(bytes_per_row as usize >= row_len, "rows overlap");
+
+        let data = data
+            .chunks(bytes_per_row as usize)
+            .take(height as usize)
+            .flat_map(|row| &row[..row_len])
+            .copied()
+            .collect();
+
+        Self::new(width, height, data)
+    }
+
+    /// Converts BGRA data, the usual format of window surfaces, to RGBA and vice versa
+    pub fn swap_red_blue(&mut self) {
+        for pixel in self.data.chunks_exact_mut(Self::BYTES_PER_PIXEL as usize) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    /// Reads an 8-bit RGBA PNG, as written by [`Image::write_png`]
+    pub fn read_png(path: impl AsRef<Path>) -> Result<Self, png::DecodingError> {
+        let decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+        let mut reader = decoder.read_info()?;
+
+        let info = reader.info();
+        if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+            return Err(png::DecodingError::IoError(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "expected 8-bit RGBA, found {}-bit {:?}",
+                    info.bit_depth as u8, info.color_type
+                ),
+            )));
+        }
+
+        let mut data = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut data)?;
+        data.truncate(frame.buffer_size());
+
+        Ok(Self::new(frame.width, frame.height, data))
+    }
+
+    pub fn write_png(&self, path: impl AsRef<Path>) -> Result<(), png::EncodingError> {
+        let file = BufWriter::new(File::create(path)?);
+
+        let mut encoder = png::Encoder::new(file, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.data)?;
+        writer.finish()
+    }
+}
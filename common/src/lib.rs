@@ -1,93 +1,719 @@
 //! A library that provides basic code to get a window up and running and to manage the app state.
 //! This library defines a [`Renderer`] trait that has to be implemented for some rendering backend.
-//! Then one can call [`run_app`] with a specific [`Renderer`] implementor.
+//! Then one can call [`run`] with a specific [`Renderer`] implementor and the parsed [`Cli`].
 
+mod free_fly;
 mod windowing;
 
 pub mod camera;
-use std::{f32::consts::PI, time::Instant};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
 
-use bezier_nd::Bezier;
+use rand::{rngs::StdRng, SeedableRng};
 pub use camera::*;
+pub mod camera_path;
+pub use camera_path::*;
+pub mod clock;
+pub use clock::*;
 pub mod mesh;
 pub use mesh::*;
 pub mod has_window_and_display_handle;
 pub use has_window_and_display_handle::*;
 pub mod renderer;
 pub use renderer::*;
+pub mod stats;
+pub use stats::*;
+pub mod results;
+pub use results::*;
+pub mod baseline;
+pub use baseline::*;
+pub mod report;
+pub use report::*;
+pub mod plot;
+pub mod profiling;
+pub use profiling::*;
+pub mod image;
+pub use image::*;
+pub mod golden;
+pub mod obj;
+pub use obj::*;
+pub mod gltf_import;
+pub use gltf_import::*;
+pub mod model;
+pub use model::*;
+pub mod shader;
+pub use shader::*;
+pub mod range_allocator;
+pub use range_allocator::*;
+pub mod settings;
+pub use settings::*;
+pub mod cli;
+pub use cli::*;
+pub mod scenario;
+pub use scenario::*;
+use free_fly::{FreeFlyCamera, PathRecorder};
 use windowing::Event;
 use winit::{
     event::{ElementState, KeyEvent},
     keyboard::{KeyCode, PhysicalKey},
 };
-use geo_nd::{FArray, Vector};
+
+const RESULTS_DIR: &str = "results";
+const SCREENSHOTS_DIR: &str = "screenshots";
+const RECORDINGS_DIR: &str = "recordings";
+
+#[derive(Clone)]
+struct AppConfig {
+    scenario: Scenario,
+    /// The meshes of the scenario's model, loaded before the window is created
+    model: Vec<Mesh>,
+    settings: RendererSettings,
+    /// `None` writes the results to [`RESULTS_DIR`]
+    output: Option<PathBuf>,
+    seed: u64,
+    /// Start in free-fly mode
+    fly: bool,
+    baseline: Option<BaselineSettings>,
+}
 
 struct Application<R> {
-    init_time: Instant,
-    frames: u64,
+    /// Restarted with every run
+    clock: Clock,
+    started_at: SystemTime,
+    /// All frames rendered so far, which is how the renderer numbers its GPU timings
+    rendered_frames: u64,
+    /// Index of the first frame of the current run, as counted by the renderer
+    run_first_frame: u64,
+    frame_times: Histogram,
+    gpu_frame_times: Histogram,
+    phase_times: BTreeMap<&'static str, Histogram>,
+    samples: Vec<FrameSample>,
+    /// Handles of the random triangles, in the order they were loaded
+    random_triangles: Vec<MeshHandle>,
+    /// The attribute sets of all meshes loaded so far, as drawn by the renderer
+    vertex_attributes: BTreeSet<VertexAttributes>,
+    scenario: Scenario,
+    /// Index of the current repetition of the scenario
+    repetition: u32,
+    /// Frames that are still rendered before the first repetition starts
+    warmup_frames_left: u64,
+    /// Results of the finished repetitions without their samples, aggregated after the last one
+    finished_runs: Vec<BenchmarkResults>,
+    output: Option<PathBuf>,
+    seed: u64,
+    /// All random numbers of a run come from here, so that runs with the same seed are the same
+    rng: StdRng,
+    /// Set once the results of the last repetition were exported
+    finished: bool,
+    baseline: Option<BaselineSettings>,
+    /// Set once the results were saved as the baseline or passed the check against it
+    baseline_passed: bool,
+    /// `Some` in free-fly mode, which pauses the run
+    fly: Option<FreeFlyCamera>,
+    /// `Some` while the flight is recorded
+    recorder: Option<PathRecorder>,
+    /// Set until the frame after pressing F12 was rendered
+    screenshot_requested: bool,
     renderer: R,
 }
 
 impl<R: Renderer> windowing::Application for Application<R> {
+    type Config = AppConfig;
+
     fn new(
+        config: AppConfig,
         window: impl HasWindowAndDisplayHandle + Send + Sync + 'static,
         initial_window_size: (u32, u32),
     ) -> Self {
-        let renderer = R::new(window, initial_window_size);
-
-        Self {
-            init_time: Instant::now(),
-            frames: 0,
-            renderer,
-        }
+        let renderer = R::new(window, initial_window_size, &config.settings);
+        Self::with_renderer(renderer, config)
     }
 
     fn handle_event(&mut self, event: windowing::Event) {
+        if let Some(fly) = &mut self.fly {
+            fly.handle_event(&event);
+        }
+
         match event {
             Event::Render => {
-                const DURATION_SECS: f32 = 5.0;
-                let current_time = self.init_time.elapsed().as_secs_f32() / DURATION_SECS;
-
-                if current_time > 1.0 {
-                    let average_fps = self.frames as f32 / DURATION_SECS;
-                    println!("Average FPS over {DURATION_SECS}s: {average_fps}");
-                    self.init_time = Instant::now();
-                    self.frames = 0;
-                    return;
+                self.render();
+                if std::mem::take(&mut self.screenshot_requested) {
+                    self.save_screenshot();
                 }
-
-                let dx = FArray::<f32, 5>::from_array([0.0, 0.0, 10.0, 0.0, PI*3.0/2.0]);
-                let dy = FArray::<f32, 5>::from_array([10.0, 0.0, 0.0, 0.0, PI]);
-                let line = Bezier::line(&dx, &dy);
-                let x = line.point_at(current_time);
-
-
-                self.frames += 1;
-                self.renderer.render(Camera { xyz: (x[0], x[1], x[2]), pitch: x[3], yaw: x[4]});
             }
             Event::Resize { size, scale_factor } => self.renderer.resize(size, scale_factor),
             Event::KeyboardInput(KeyEvent {
                 physical_key: PhysicalKey::Code(KeyCode::Space),
                 state: ElementState::Pressed,
                 ..
-            }) => {
-                let random_float = || rand::random::<f32>() * 2.0 - 1.0;
+            }) => self.load_random_triangle(),
+            Event::KeyboardInput(KeyEvent {
+                physical_key: PhysicalKey::Code(KeyCode::Backspace),
+                state: ElementState::Pressed,
+                ..
+            }) => self.unload_random_triangle(),
+            Event::KeyboardInput(KeyEvent {
+                physical_key: PhysicalKey::Code(KeyCode::F12),
+                state: ElementState::Pressed,
+                repeat: false,
+                ..
+            }) => self.request_screenshot(),
+            Event::KeyboardInput(KeyEvent {
+                physical_key: PhysicalKey::Code(KeyCode::KeyF),
+                state: ElementState::Pressed,
+                repeat: false,
+                ..
+            }) => self.toggle_free_fly(),
+            Event::KeyboardInput(KeyEvent {
+                physical_key: PhysicalKey::Code(KeyCode::KeyR),
+                state: ElementState::Pressed,
+                repeat: false,
+                ..
+            }) => self.toggle_recording(),
+            _ => {}
+        }
+    }
 
-                let vertices = (0..3)
-                    .map(|_| Vertex {
-                        xyz: [random_float(), random_float(), random_float()],
-                    })
-                    .collect();
+    fn should_exit(&self) -> bool {
+        self.finished
+    }
+}
 
-                let indices = vec![0, 1, 2];
+impl<R: Renderer> Application<R> {
+    /// Loads the scene of `config` and starts the run
+    fn with_renderer(renderer: R, config: AppConfig) -> Self {
+        let mut app = Self {
+            clock: Clock::start(config.scenario.clock),
+            started_at: SystemTime::now(),
+            rendered_frames: 0,
+            run_first_frame: 0,
+            frame_times: Histogram::new(),
+            gpu_frame_times: Histogram::new(),
+            phase_times: BTreeMap::new(),
+            samples: Vec::new(),
+            random_triangles: Vec::new(),
+            vertex_attributes: BTreeSet::new(),
+            warmup_frames_left: config.scenario.warmup_frames,
+            repetition: 0,
+            finished_runs: Vec::new(),
+            output: config.output,
+            seed: config.seed,
+            rng: StdRng::seed_from_u64(config.seed),
+            finished: false,
+            baseline: config.baseline,
+            baseline_passed: false,
+            fly: config
+                .fly
+                .then(|| FreeFlyCamera::new(config.scenario.camera.camera_at(0.0))),
+            recorder: None,
+            screenshot_requested: false,
+            scenario: config.scenario,
+            renderer,
+        };
 
-                self.renderer.load_mesh(Mesh { indices, vertices });
+        // Attributes the renderer ignores are not uploaded, so that the results record the
+        // attributes the scene was actually drawn with
+        let drawn_attributes = app.renderer.info().vertex_attributes;
+        for mut mesh in config.model {
+            mesh.retain_attributes(drawn_attributes);
+            app.vertex_attributes.insert(mesh.attributes());
+            app.renderer.load_mesh(mesh);
+        }
+        if let SceneSource::Triangles(count) = app.scenario.scene {
+            for _ in 0..count {
+                app.load_random_triangle();
+            }
+        }
+
+        app.start_run();
+        app
+    }
+
+    /// Resets the clock and the statistics of the run, so that neither loading the scene nor
+    /// warming up is measured and repetitions don't include the export of the previous one
+    fn start_run(&mut self) {
+        self.clock = Clock::start(self.scenario.clock);
+        self.started_at = SystemTime::now();
+        self.run_first_frame = self.rendered_frames;
+        self.frame_times.clear();
+        self.gpu_frame_times.clear();
+        self.phase_times.clear();
+        self.samples.clear();
+    }
+
+    /// How far the run has progressed, from 0 to 1
+    fn progress(&self) -> f32 {
+        self.clock.progress(self.scenario.length)
+    }
+
+    fn render(&mut self) {
+        if let Some(fly) = &mut self.fly {
+            let camera = fly.update();
+            if let Some(recorder) = &mut self.recorder {
+                recorder.sample(camera);
+            }
+            self.render_unmeasured(camera);
+            return;
+        }
+
+        if self.finished {
+            return;
+        }
+
+        if self.warmup_frames_left > 0 {
+            self.render_warmup_frame();
+            return;
+        }
+
+        let progress = self.progress();
+        if progress >= 1.0 {
+            self.finish_run();
+            return;
+        }
+
+        // Discard phases that were recorded outside of a frame, e.g. while loading a mesh
+        profiling::take_phases();
+
+        let time_secs = self.clock.elapsed().as_secs_f64();
+        let frame_start = Instant::now();
+        self.renderer.render(self.scenario.camera.camera_at(progress));
+        let frame_time = frame_start.elapsed();
+        self.rendered_frames += 1;
+
+        let mut cpu_phases_ms = BTreeMap::new();
+        let phases = profiling::take_phases();
+        for phase in phases.into_iter().filter(|_| self.scenario.records(Metric::CpuPhases)) {
+            self.phase_times.entry(phase.name).or_default().record(phase.duration);
+            cpu_phases_ms.insert(phase.name.to_owned(), phase.duration.as_secs_f64() * 1e3);
+        }
+
+        self.frame_times.record(frame_time);
+        self.samples.push(FrameSample {
+            frame: self.clock.frames(),
+            time_secs,
+            cpu_ms: frame_time.as_secs_f64() * 1e3,
+            gpu_ms: None,
+            cpu_phases_ms,
+        });
+        self.clock.tick();
+
+        self.merge_gpu_timings();
+    }
+
+    /// Renders a frame from the start of the camera path without measuring it
+    fn render_warmup_frame(&mut self) {
+        self.render_unmeasured(self.scenario.camera.camera_at(0.0));
+        self.warmup_frames_left -= 1;
+        if self.warmup_frames_left == 0 {
+            self.start_run();
+        }
+    }
+
+    fn render_unmeasured(&mut self, camera: Camera) {
+        self.renderer.render(camera);
+        self.rendered_frames += 1;
+        profiling::take_phases();
+        // Drained so they don't pile up, they are dropped by `merge_gpu_timings` anyway
+        self.renderer.gpu_timings();
+    }
+
+    /// Reports and exports the statistics of the current repetition and starts the next one
+    fn finish_run(&mut self) {
+        let duration_secs = self.clock.elapsed().as_secs_f64();
+        // The last frames are still in flight, their GPU timings would be missing otherwise
+        self.renderer.finish_gpu_timings();
+        self.merge_gpu_timings();
+
+        let average_fps = self.clock.frames() as f64 / duration_secs;
+        println!("Average FPS over {duration_secs:.2}s: {average_fps}");
+        println!("CPU frame times: {}", self.frame_times.summary());
+        if self.gpu_frame_times.samples() > 0 {
+            println!("GPU frame times: {}", self.gpu_frame_times.summary());
+        }
+        for (name, phase_times) in &self.phase_times {
+            println!("  {name}: {}", phase_times.summary());
+        }
+        self.export_results(duration_secs);
+
+        self.repetition += 1;
+        if self.repetition == self.scenario.repetitions {
+            if self.scenario.repetitions > 1 {
+                self.export_aggregate();
+            }
+            if let Some(settings) = self.baseline.clone() {
+                self.baseline_passed = self.save_or_check_baseline(&settings);
+            }
+            self.finished = true;
+            return;
+        }
+
+        self.start_run();
+    }
+
+    /// Free-fly mode pauses the run, which starts over when leaving it
+    fn toggle_free_fly(&mut self) {
+        match self.fly.take() {
+            None => {
+                let camera = self.scenario.camera.camera_at(self.progress().min(1.0));
+                self.fly = Some(FreeFlyCamera::new(camera));
+                println!("Flying, the run is paused. R records a camera path, F restarts the run");
+            }
+            Some(fly) => {
+                if let Some(recorder) = self.recorder.take() {
+                    self.save_recording(recorder, fly.camera());
+                }
+                println!("Restarting the run");
+                self.start_run();
             }
-            _ => {}
         }
     }
+
+    fn toggle_recording(&mut self) {
+        let Some(fly) = &self.fly else {
+            println!("Camera paths can only be recorded while flying, press F to fly");
+            return;
+        };
+
+        match self.recorder.take() {
+            None => {
+                self.recorder = Some(PathRecorder::new());
+                println!("Recording the camera path, press R again to save it");
+            }
+            Some(recorder) => self.save_recording(recorder, fly.camera()),
+        }
+    }
+
+    /// Saves the recorded flight as a scenario with the scene and seed of the current one, which
+    /// replays it on any backend
+    fn save_recording(&mut self, recorder: PathRecorder, camera: Camera) {
+        let Some((duration, path)) = recorder.finish(camera) else {
+            eprintln!("Nothing was recorded, not saving a camera path");
+            return;
+        };
+
+        if let Err(err) = std::fs::create_dir_all(RECORDINGS_DIR) {
+            eprintln!("Failed to create recordings directory: {err}");
+            return;
+        }
+
+        let recorded_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut scenario = Scenario {
+            name: format!("recording-{recorded_at}"),
+            length: RunLength::Duration(duration),
+            seed: Some(self.seed),
+            camera: path,
+            ..self.scenario.clone()
+        };
+        // The recording is saved elsewhere, relative paths would not resolve anymore
+        if let SceneSource::Model(model) = &mut scenario.scene {
+            if let Ok(absolute) = model.canonicalize() {
+                *model = absolute;
+            }
+        }
+
+        let path = Path::new(RECORDINGS_DIR).join(format!("{}.toml", scenario.name));
+        match scenario.save(&path) {
+            Ok(()) => println!(
+                "Saved camera path to {0}, replay it with --scenario {0}",
+                path.display()
+            ),
+            Err(err) => eprintln!("Failed to save camera path: {err}"),
+        }
+    }
+
+    fn load_random_triangle(&mut self) {
+        let mesh = Mesh::random_triangle(&mut self.rng);
+        self.vertex_attributes.insert(mesh.attributes());
+        let handle = self.renderer.load_mesh(mesh);
+        self.random_triangles.push(handle);
+    }
+
+    /// Unloads the most recently loaded random triangle
+    fn unload_random_triangle(&mut self) {
+        if let Some(handle) = self.random_triangles.pop() {
+            self.renderer.unload_mesh(handle);
+        }
+    }
+
+    /// The screenshot is taken of the next frame, the only one the renderer keeps for it
+    fn request_screenshot(&mut self) {
+        self.renderer.request_capture();
+        self.screenshot_requested = true;
+    }
+
+    fn save_screenshot(&mut self) {
+        let Some(image) = self.renderer.capture() else {
+            eprintln!("No frame can be read back, not taking a screenshot");
+            return;
+        };
+
+        if let Err(err) = std::fs::create_dir_all(SCREENSHOTS_DIR) {
+            eprintln!("Failed to create screenshots directory: {err}");
+            return;
+        }
+
+        let taken_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = Path::new(SCREENSHOTS_DIR)
+            .join(format!("{}-{taken_at}.png", self.renderer.info().backend));
+        match image.write_png(&path) {
+            Ok(()) => println!("Saved screenshot to {}", path.display()),
+            Err(err) => eprintln!("Failed to save screenshot: {err}"),
+        }
+    }
+
+    /// Attaches GPU timings to the samples of their frames. Timings of warm-up frames and previous
+    /// repetitions are dropped.
+    fn merge_gpu_timings(&mut self) {
+        let timings = self.renderer.gpu_timings();
+        if !self.scenario.records(Metric::GpuFrameTime) {
+            return;
+        }
+
+        for timing in timings {
+            let Some(sample) = timing
+                .frame
+                .checked_sub(self.run_first_frame)
+                .and_then(|index| self.samples.get_mut(index as usize))
+            else {
+                continue;
+            };
+
+            let gpu_time = timing.total();
+            sample.gpu_ms = Some(gpu_time.as_secs_f64() * 1e3);
+            self.gpu_frame_times.record(gpu_time);
+        }
+    }
+
+    fn export_results(&mut self, duration_secs: f64) {
+        let info = self.renderer.info();
+        let started_at = self
+            .started_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let results = BenchmarkResults {
+            metadata: RunMetadata {
+                backend: info.backend,
+                adapter: info.adapter,
+                driver: info.driver,
+                scene: self.scenario.name.clone(),
+                started_at,
+                duration_secs,
+                frames: self.clock.frames(),
+                msaa_samples: info.msaa.get(),
+                present_mode: info.present_mode,
+                max_frame_latency: info.max_frame_latency,
+                warmup_frames: self.scenario.warmup_frames,
+                repetition: self.repetition,
+                seed: Some(self.seed),
+                fixed_step_secs: match self.scenario.clock {
+                    ClockMode::RealTime => None,
+                    ClockMode::FixedStep(step) => Some(step.as_secs_f64()),
+                },
+                vertex_attributes: self.vertex_attributes.iter().copied().collect(),
+            },
+            samples: std::mem::take(&mut self.samples),
+            cpu_frame_time: self.frame_times.summary(),
+            gpu_frame_time: (self.gpu_frame_times.samples() > 0)
+                .then(|| self.gpu_frame_times.summary()),
+            cpu_phases: self
+                .phase_times
+                .iter()
+                .map(|(name, phase_times)| (name.to_string(), phase_times.summary()))
+                .collect(),
+            buffers: self.renderer.buffer_stats(),
+        };
+
+        let json_path = self.results_path(&results.metadata, self.repetition);
+        let csv_path = json_path.with_extension("csv");
+
+        if let Some(dir) = json_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            if let Err(err) = std::fs::create_dir_all(dir) {
+                eprintln!("Failed to create {}: {err}", dir.display());
+                return;
+            }
+        }
+
+        for (path, result) in [
+            (&json_path, results.write_json(&json_path)),
+            (&csv_path, results.write_csv(&csv_path)),
+        ] {
+            match result {
+                Ok(()) => println!("Wrote {}", path.display()),
+                Err(err) => eprintln!("Failed to write {}: {err}", path.display()),
+            }
+        }
+
+        self.finished_runs.push(BenchmarkResults {
+            samples: Vec::new(),
+            ..results
+        });
+    }
+
+    /// Reports and exports the aggregate of all repetitions, next to the results of the first one
+    fn export_aggregate(&mut self) {
+        let Some(aggregate) = AggregatedResults::of(&self.finished_runs) else {
+            return;
+        };
+
+        println!("Over {} repetitions:", aggregate.repetitions);
+        println!("  FPS: {}", aggregate.fps);
+        println!("  CPU frame time (ms): {}", aggregate.cpu_frame_time_ms);
+        if let Some(gpu_frame_time_ms) = aggregate.gpu_frame_time_ms {
+            println!("  GPU frame time (ms): {gpu_frame_time_ms}");
+        }
+        for (name, phase_ms) in &aggregate.cpu_phases_ms {
+            println!("    {name} (ms): {phase_ms}");
+        }
+
+        let path = self.results_path(&aggregate.metadata, "summary");
+        match aggregate.write_json(&path) {
+            Ok(()) => println!("Wrote {}", path.display()),
+            Err(err) => eprintln!("Failed to write {}: {err}", path.display()),
+        }
+    }
+
+    /// Saves the percentiles of all repetitions as the baseline, or checks them against it.
+    /// Returns whether that succeeded and there was no regression.
+    fn save_or_check_baseline(&self, settings: &BaselineSettings) -> bool {
+        let Some(current) = Baseline::of(&self.finished_runs) else {
+            return false;
+        };
+        let path = settings.path(&current.metadata.backend, &self.scenario.name);
+
+        match settings.mode {
+            BaselineMode::Save => {
+                if let Some(dir) = path.parent() {
+                    if let Err(err) = std::fs::create_dir_all(dir) {
+                        eprintln!("Failed to create {}: {err}", dir.display());
+                        return false;
+                    }
+                }
+                match current.write_json(&path) {
+                    Ok(()) => println!("Saved baseline {}", path.display()),
+                    Err(err) => {
+                        eprintln!("Failed to write {}: {err}", path.display());
+                        return false;
+                    }
+                }
+                true
+            }
+            BaselineMode::Check => {
+                let baseline = match Baseline::read_json(&path) {
+                    Ok(baseline) => baseline,
+                    Err(err) => {
+                        eprintln!("Failed to read baseline {}: {err}", path.display());
+                        return false;
+                    }
+                };
+
+                println!(
+                    "Compared to baseline {} (threshold {:.1}%):",
+                    path.display(),
+                    settings.threshold * 100.0
+                );
+                let mismatches = baseline.mismatches(&current);
+                if !mismatches.is_empty() {
+                    println!("  Differs from the baseline in: {}", mismatches.join(", "));
+                }
+                let changes = baseline.check(&current, settings.threshold);
+                for change in &changes {
+                    println!("  {change}");
+                }
+                changes.iter().all(|change| !change.regressed)
+            }
+        }
+    }
+
+    /// The output path, or a path in [`RESULTS_DIR`] by the backend and start of the run. With
+    /// repetitions, `suffix` is appended to the file name.
+    fn results_path(&self, metadata: &RunMetadata, suffix: impl Display) -> PathBuf {
+        let mut path = self.output.clone().unwrap_or_else(|| {
+            Path::new(RESULTS_DIR)
+                .join(format!("{}-{}.json", metadata.backend, metadata.started_at))
+        });
+        if self.scenario.repetitions > 1 {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            path.set_file_name(format!("{stem}-{suffix}.json"));
+        }
+        path
+    }
 }
 
-pub fn run_app<R: Renderer>() {
-    windowing::run_window_app::<Application<R>>();
+/// Runs the scenario of `cli` and exports the results of every repetition. In a window, more
+/// random triangles are added with space and removed again with backspace. F toggles free-fly
+/// mode, in which R records a camera path. With `--baseline`, the process exits with 1 if the
+/// results could not be saved as the baseline or regressed from it.
+pub fn run<R: Renderer>(cli: Cli) {
+    let scenario = match &cli.scenario {
+        Some(path) => Scenario::load(path).unwrap_or_else(|err| {
+            eprintln!("Failed to load {}: {err}", path.display());
+            std::process::exit(1);
+        }),
+        None => Scenario::from_cli(&cli),
+    };
+    let model = match &scenario.scene {
+        SceneSource::Triangles(_) => Vec::new(),
+        SceneSource::Model(path) => load_model_or_exit(path),
+    };
+    let seed = cli.seed.or(scenario.seed).unwrap_or_else(rand::random);
+    let baseline = cli.baseline_settings();
+    let config = AppConfig {
+        scenario,
+        model,
+        settings: cli.settings,
+        output: cli.output,
+        seed,
+        fly: cli.fly,
+        baseline,
+    };
+
+    let app = if cli.headless {
+        let size = cli.size.unwrap_or(DEFAULT_HEADLESS_SIZE);
+        let renderer = R::new_headless(size, &config.settings);
+        let mut app = Application::with_renderer(renderer, config);
+        while !app.finished {
+            app.render();
+        }
+        Some(app)
+    } else {
+        windowing::run_window_app::<Application<R>>(config, cli.size)
+    };
+
+    // Also if the window was closed before the run finished
+    if app.is_some_and(|app| app.baseline.is_some() && !app.baseline_passed) {
+        std::process::exit(1);
+    }
+}
+
+/// Loads an OBJ or glTF file. Exits the process if it can't be loaded, as there is no point in
+/// benchmarking something else than what was asked for.
+fn load_model_or_exit(path: &Path) -> Vec<Mesh> {
+    match load_model(path) {
+        Ok(meshes) => {
+            println!(
+                "Loaded {}: {} meshes, {} vertices, {} triangles",
+                path.display(),
+                meshes.len(),
+                meshes.iter().map(|mesh| mesh.vertices.len()).sum::<usize>(),
+                meshes.iter().map(|mesh| mesh.indices.len() / 3).sum::<usize>()
+            );
+            meshes
+        }
+        Err(err) => {
+            eprintln!("Failed to load {}: {err}", path.display());
+            std::process::exit(1);
+        }
+    }
 }
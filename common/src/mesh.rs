@@ -1,9 +1,212 @@
-#[derive(Debug)]
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug)]
 pub struct Vertex {
     pub xyz: [f32; 3],
 }
 
+/// A per-vertex input of the shaders
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum VertexAttribute {
+    Position,
+    Normal,
+    /// The direction of increasing `u`, with the handedness of the bitangent in `w`
+    Tangent,
+    /// The first set of texture coordinates
+    Uv0,
+    /// A linear RGBA color
+    Color,
+}
+
+impl VertexAttribute {
+    pub const ALL: [Self; 5] = [
+        Self::Position,
+        Self::Normal,
+        Self::Tangent,
+        Self::Uv0,
+        Self::Color,
+    ];
+
+    /// Number of `f32` components
+    pub fn components(self) -> usize {
+        match self {
+            Self::Position | Self::Normal => 3,
+            Self::Tangent | Self::Color => 4,
+            Self::Uv0 => 2,
+        }
+    }
+
+    /// The `@location` of the attribute in the shaders
+    pub fn location(self) -> u32 {
+        self as u32
+    }
+
+    /// The name that is defined in shader variants that have the attribute
+    pub fn define(self) -> &'static str {
+        match self {
+            Self::Position => "POSITION",
+            Self::Normal => "NORMAL",
+            Self::Tangent => "TANGENT",
+            Self::Uv0 => "UV0",
+            Self::Color => "COLOR",
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// A set of vertex attributes, which decides the vertex buffer layout and shader variant a mesh
+/// is drawn with. Serialized as the list of its attributes.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+#[serde(into = "Vec<VertexAttribute>", from = "Vec<VertexAttribute>")]
+pub struct VertexAttributes(u8);
+
+impl VertexAttributes {
+    /// Every mesh has positions
+    pub const POSITION_ONLY: Self = Self(1 << VertexAttribute::Position as u8);
+    pub const ALL: Self = Self((1 << VertexAttribute::ALL.len()) - 1);
+
+    pub fn with(self, attribute: VertexAttribute) -> Self {
+        Self(self.0 | attribute.bit())
+    }
+
+    pub fn contains(self, attribute: VertexAttribute) -> bool {
+        self.0 & attribute.bit() != 0
+    }
+
+    /// In the order of their shader locations
+    pub fn iter(self) -> impl Iterator<Item = VertexAttribute> {
+        VertexAttribute::ALL
+            .into_iter()
+            .filter(move |&attribute| self.contains(attribute))
+    }
+}
+
+impl std::fmt::Debug for VertexAttributes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+/// Like `Position+Normal`, for reports
+impl std::fmt::Display for VertexAttributes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<_> = self
+            .iter()
+            .map(|attribute| format!("{attribute:?}"))
+            .collect();
+        write!(f, "{}", names.join("+"))
+    }
+}
+
+impl FromIterator<VertexAttribute> for VertexAttributes {
+    fn from_iter<T: IntoIterator<Item = VertexAttribute>>(iter: T) -> Self {
+        iter.into_iter()
+            .fold(Self::default(), |attributes, attribute| {
+                attributes.with(attribute)
+            })
+    }
+}
+
+impl From<Vec<VertexAttribute>> for VertexAttributes {
+    fn from(attributes: Vec<VertexAttribute>) -> Self {
+        attributes.into_iter().collect()
+    }
+}
+
+impl From<VertexAttributes> for Vec<VertexAttribute> {
+    fn from(attributes: VertexAttributes) -> Self {
+        attributes.iter().collect()
+    }
+}
+
+/// Optional attributes are either missing or have exactly one entry per vertex
+#[derive(Clone, Debug)]
 pub struct Mesh {
     pub indices: Vec<u32>,
     pub vertices: Vec<Vertex>,
-}
\ No newline at end of file
+    pub normals: Option<Vec<[f32; 3]>>,
+    /// Only meaningful together with normals
+    pub tangents: Option<Vec<[f32; 4]>>,
+    /// With the origin in the top left corner of the texture
+    pub tex_coords: Option<Vec<[f32; 2]>>,
+    pub colors: Option<Vec<[f32; 4]>>,
+}
+
+impl Mesh {
+    /// A mesh without any optional attributes
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>) -> Self {
+        Self {
+            indices,
+            vertices,
+            normals: None,
+            tangents: None,
+            tex_coords: None,
+            colors: None,
+        }
+    }
+
+    /// A triangle whose corners lie within the cube from -1 to 1
+    pub fn random_triangle(rng: &mut impl Rng) -> Self {
+        let mut random_float = || rng.gen::<f32>() * 2.0 - 1.0;
+
+        let vertices = (0..3)
+            .map(|_| Vertex {
+                xyz: [random_float(), random_float(), random_float()],
+            })
+            .collect();
+
+        Self::new(vertices, vec![0, 1, 2])
+    }
+
+    /// The attributes this mesh has data for
+    pub fn attributes(&self) -> VertexAttributes {
+        let optional = [
+            (VertexAttribute::Normal, self.normals.is_some()),
+            (VertexAttribute::Tangent, self.tangents.is_some()),
+            (VertexAttribute::Uv0, self.tex_coords.is_some()),
+            (VertexAttribute::Color, self.colors.is_some()),
+        ];
+
+        optional.into_iter().filter(|&(_, present)| present).fold(
+            VertexAttributes::POSITION_ONLY,
+            |attributes, (attribute, _)| attributes.with(attribute),
+        )
+    }
+
+    /// Drops the optional attributes that are not in `attributes`
+    pub fn retain_attributes(&mut self, attributes: VertexAttributes) {
+        if !attributes.contains(VertexAttribute::Normal) {
+            self.normals = None;
+        }
+        if !attributes.contains(VertexAttribute::Tangent) {
+            self.tangents = None;
+        }
+        if !attributes.contains(VertexAttribute::Uv0) {
+            self.tex_coords = None;
+        }
+        if !attributes.contains(VertexAttribute::Color) {
+            self.colors = None;
+        }
+    }
+
+    /// The data of one attribute as consecutive `f32`s, or `None` if the mesh doesn't have it
+    pub fn attribute_data(&self, attribute: VertexAttribute) -> Option<Vec<f32>> {
+        fn flatten<const N: usize>(values: &[[f32; N]]) -> Vec<f32> {
+            values.iter().flatten().copied().collect()
+        }
+
+        match attribute {
+            VertexAttribute::Position => {
+                Some(self.vertices.iter().flat_map(|vertex| vertex.xyz).collect())
+            }
+            VertexAttribute::Normal => self.normals.as_deref().map(flatten),
+            VertexAttribute::Tangent => self.tangents.as_deref().map(flatten),
+            VertexAttribute::Uv0 => self.tex_coords.as_deref().map(flatten),
+            VertexAttribute::Color => self.colors.as_deref().map(flatten),
+        }
+    }
+}
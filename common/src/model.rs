@@ -0,0 +1,55 @@
+//! Loads models from any of the supported file formats, chosen by the file extension.
+
+use std::{fmt, path::Path};
+
+use crate::{load_gltf, load_obj, GltfError, Mesh, ObjError};
+
+#[derive(Debug)]
+pub enum ModelError {
+    Obj(ObjError),
+    Gltf(GltfError),
+    UnsupportedFormat(Option<String>),
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Obj(err) => err.fmt(f),
+            Self::Gltf(err) => err.fmt(f),
+            Self::UnsupportedFormat(Some(extension)) => write!(
+                f,
+                "unsupported model format `{extension}`, expected obj, gltf or glb"
+            ),
+            Self::UnsupportedFormat(None) => write!(
+                f,
+                "model files need an obj, gltf or glb extension to detect their format"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Obj(err) => Some(err),
+            Self::Gltf(err) => Some(err),
+            Self::UnsupportedFormat(_) => None,
+        }
+    }
+}
+
+/// OBJ files become a single mesh, glTF scenes one mesh per primitive
+pub fn load_model(path: impl AsRef<Path>) -> Result<Vec<Mesh>, ModelError> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+
+    match extension.as_deref() {
+        Some("obj") => load_obj(path)
+            .map(|mesh| vec![mesh])
+            .map_err(ModelError::Obj),
+        Some("gltf" | "glb") => load_gltf(path).map_err(ModelError::Gltf),
+        _ => Err(ModelError::UnsupportedFormat(extension)),
+    }
+}
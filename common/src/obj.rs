@@ -0,0 +1,340 @@
+//! A loader for Wavefront OBJ files.
+//!
+//! All objects and groups are merged into a single [`Mesh`]. Polygons are triangulated as fans,
+//! which is correct for convex polygons, and vertices that are referenced with the same indices
+//! are only stored once. Normals and texture coordinates are only kept if every vertex has them.
+//! Statements that don't affect the geometry, like materials or smoothing groups, are ignored.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+use crate::{Mesh, Vertex};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(io::Error),
+    Parse {
+        line: usize,
+        kind: ObjErrorKind,
+    },
+    /// The file does not contain any faces
+    Empty,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ObjErrorKind {
+    InvalidNumber(String),
+    MissingCoordinates { expected: usize, found: usize },
+    TooFewFaceVertices(usize),
+    InvalidIndex(String),
+    IndexOutOfRange { index: i64, count: usize },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to read OBJ file: {err}"),
+            Self::Parse { line, kind } => write!(f, "line {line}: {kind}"),
+            Self::Empty => write!(f, "OBJ file does not contain any faces"),
+        }
+    }
+}
+
+impl fmt::Display for ObjErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidNumber(number) => write!(f, "invalid number `{number}`"),
+            Self::MissingCoordinates { expected, found } => {
+                write!(f, "expected {expected} coordinates, found {found}")
+            }
+            Self::TooFewFaceVertices(count) => {
+                write!(f, "faces need at least 3 vertices, found {count}")
+            }
+            Self::InvalidIndex(index) => write!(f, "invalid index `{index}`"),
+            Self::IndexOutOfRange { index, count } => {
+                write!(
+                    f,
+                    "index {index} is out of range, there are {count} elements"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ObjError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+pub fn load_obj(path: impl AsRef<Path>) -> Result<Mesh, ObjError> {
+    parse_obj(BufReader::new(File::open(path)?))
+}
+
+pub fn parse_obj(reader: impl BufRead) -> Result<Mesh, ObjError> {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut tex_coords: Vec<[f32; 2]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+
+    let mut mesh = Mesh::new(Vec::new(), Vec::new());
+    let mut vertex_tex_coords: Vec<Option<[f32; 2]>> = Vec::new();
+    let mut vertex_normals: Vec<Option<[f32; 3]>> = Vec::new();
+    // Maps the position, texture coordinate and normal indices of a face vertex to its index in
+    // the mesh
+    let mut vertex_indices: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+
+    for (line_index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let error = |kind| ObjError::Parse {
+            line: line_index + 1,
+            kind,
+        };
+
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        match keyword {
+            "v" => positions.push(parse_coordinates(tokens).map_err(error)?),
+            "vt" => {
+                let [u] = parse_coordinates(tokens.by_ref()).map_err(error)?;
+                let v = match tokens.next() {
+                    Some(v) => v
+                        .parse::<f32>()
+                        .map_err(|_| error(ObjErrorKind::InvalidNumber(v.to_owned())))?,
+                    None => 0.0,
+                };
+                // OBJ has the origin in the bottom left corner
+                tex_coords.push([u, 1.0 - v]);
+            }
+            "vn" => normals.push(parse_coordinates(tokens).map_err(error)?),
+            "f" => {
+                let mut face = Vec::new();
+                for token in tokens {
+                    let key =
+                        parse_face_vertex(token, positions.len(), tex_coords.len(), normals.len())
+                            .map_err(error)?;
+
+                    let index = *vertex_indices.entry(key).or_insert_with(|| {
+                        let (position, tex_coord, normal) = key;
+                        mesh.vertices.push(Vertex {
+                            xyz: positions[position],
+                        });
+                        vertex_tex_coords.push(tex_coord.map(|index| tex_coords[index]));
+                        vertex_normals.push(normal.map(|index| normals[index]));
+                        (mesh.vertices.len() - 1) as u32
+                    });
+                    face.push(index);
+                }
+
+                if face.len() < 3 {
+                    return Err(error(ObjErrorKind::TooFewFaceVertices(face.len())));
+                }
+
+                for i in 1..face.len() - 1 {
+                    mesh.indices.extend([face[0], face[i], face[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if mesh.indices.is_empty() {
+        return Err(ObjError::Empty);
+    }
+
+    mesh.tex_coords = vertex_tex_coords.into_iter().collect();
+    mesh.normals = vertex_normals.into_iter().collect();
+
+    Ok(mesh)
+}
+
+/// Parses the first `N` numbers and ignores optional ones after them, like `w` of positions
+fn parse_coordinates<'a, const N: usize>(
+    mut tokens: impl Iterator<Item = &'a str>,
+) -> Result<[f32; N], ObjErrorKind> {
+    let mut coordinates = [0.0; N];
+    for (found, coordinate) in coordinates.iter_mut().enumerate() {
+        let token = tokens
+            .next()
+            .ok_or(ObjErrorKind::MissingCoordinates { expected: N, found })?;
+        *coordinate = token
+            .parse()
+            .map_err(|_| ObjErrorKind::InvalidNumber(token.to_owned()))?;
+    }
+
+    Ok(coordinates)
+}
+
+/// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn` into zero-based indices
+fn parse_face_vertex(
+    token: &str,
+    num_positions: usize,
+    num_tex_coords: usize,
+    num_normals: usize,
+) -> Result<(usize, Option<usize>, Option<usize>), ObjErrorKind> {
+    let mut parts = token.split('/');
+
+    let position = parts
+        .next()
+        .ok_or_else(|| ObjErrorKind::InvalidIndex(token.to_owned()))
+        .and_then(|index| resolve_index(index, num_positions))?;
+
+    let mut optional = |count| match parts.next() {
+        None | Some("") => Ok(None),
+        Some(index) => resolve_index(index, count).map(Some),
+    };
+    let tex_coord = optional(num_tex_coords)?;
+    let normal = optional(num_normals)?;
+
+    if parts.next().is_some() {
+        return Err(ObjErrorKind::InvalidIndex(token.to_owned()));
+    }
+
+    Ok((position, tex_coord, normal))
+}
+
+/// OBJ indices start at 1, negative indices count backwards from the last element
+fn resolve_index(index: &str, count: usize) -> Result<usize, ObjErrorKind> {
+    let parsed: i64 = index
+        .parse()
+        .map_err(|_| ObjErrorKind::InvalidIndex(index.to_owned()))?;
+
+    let resolved = match parsed {
+        1.. => parsed - 1,
+        ..=-1 => count as i64 + parsed,
+        0 => return Err(ObjErrorKind::InvalidIndex(index.to_owned())),
+    };
+
+    if !(0..count as i64).contains(&resolved) {
+        return Err(ObjErrorKind::IndexOutOfRange {
+            index: parsed,
+            count,
+        });
+    }
+
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<Mesh, ObjError> {
+        parse_obj(source.as_bytes())
+    }
+
+    fn parse_error(source: &str) -> (usize, ObjErrorKind) {
+        match parse(source) {
+            Err(ObjError::Parse { line, kind }) => (line, kind),
+            other => panic!("expected a parse error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn triangulates_polygons_and_deduplicates_vertices() {
+        let mesh = parse(
+            "# a quad made of two groups
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            g first
+            f 1 2 3 4
+            g second
+            f 1 3 4",
+        )
+        .unwrap();
+
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.indices, [0, 1, 2, 0, 2, 3, 0, 2, 3]);
+        assert_eq!(mesh.vertices[2].xyz, [1.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn resolves_all_index_forms() {
+        let mesh = parse(
+            "v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            vt 0 0
+            vn 0 0 1
+            f -3/1/1 2//1 3/-1",
+        )
+        .unwrap();
+
+        assert_eq!(mesh.indices, [0, 1, 2]);
+        assert_eq!(mesh.vertices[0].xyz, [0.0, 0.0, 0.0]);
+        assert_eq!(mesh.vertices[2].xyz, [0.0, 1.0, 0.0]);
+        // Not all vertices have a normal or texture coordinate
+        assert!(mesh.normals.is_none());
+        assert!(mesh.tex_coords.is_none());
+    }
+
+    #[test]
+    fn keeps_attributes_of_all_vertices() {
+        let mesh = parse(
+            "v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            vt 0.25 1
+            vn 0 0 1
+            f 1/1/1 2/1/1 3/1/1",
+        )
+        .unwrap();
+
+        assert_eq!(mesh.normals, Some(vec![[0.0, 0.0, 1.0]; 3]));
+        assert_eq!(mesh.tex_coords, Some(vec![[0.25, 0.0]; 3]));
+    }
+
+    #[test]
+    fn reports_malformed_lines() {
+        assert_eq!(
+            parse_error("v 0 0 0\nv 1 zero 0"),
+            (2, ObjErrorKind::InvalidNumber("zero".to_owned()))
+        );
+        assert_eq!(
+            parse_error("v 0 0"),
+            (
+                1,
+                ObjErrorKind::MissingCoordinates {
+                    expected: 3,
+                    found: 2
+                }
+            )
+        );
+        assert_eq!(
+            parse_error("v 0 0 0\nv 1 0 0\nf 1 2"),
+            (3, ObjErrorKind::TooFewFaceVertices(2))
+        );
+        assert_eq!(
+            parse_error("v 0 0 0\nf 1 1 0"),
+            (2, ObjErrorKind::InvalidIndex("0".to_owned()))
+        );
+        assert_eq!(
+            parse_error("v 0 0 0\nf 1 1 4"),
+            (2, ObjErrorKind::IndexOutOfRange { index: 4, count: 1 })
+        );
+    }
+
+    #[test]
+    fn files_without_faces_are_rejected() {
+        assert!(matches!(parse("v 0 0 0"), Err(ObjError::Empty)));
+    }
+}
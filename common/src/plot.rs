@@ -0,0 +1,230 @@
+//! Line plots rendered to standalone SVG, for the comparison reports.
+
+use std::fmt::Write;
+
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 400.0;
+/// Space around the plot area for the title, axis labels and ticks
+const MARGIN_LEFT: f64 = 70.0;
+const MARGIN_RIGHT: f64 = 20.0;
+const MARGIN_TOP: f64 = 40.0;
+const MARGIN_BOTTOM: f64 = 50.0;
+/// Roughly how many ticks an axis has
+const TICKS: f64 = 5.0;
+
+/// Colors of the series, repeated after the last one
+const COLORS: [&str; 6] = [
+    "#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b",
+];
+
+pub struct Series {
+    pub label: String,
+    pub points: Vec<(f64, f64)>,
+}
+
+/// Both axes start at 0
+pub struct LinePlot {
+    pub title: String,
+    pub x_label: String,
+    pub y_label: String,
+    pub series: Vec<Series>,
+    /// Upper end of the y axis, higher values are drawn at the top. `None` fits all values.
+    pub y_max: Option<f64>,
+}
+
+impl LinePlot {
+    pub fn to_svg(&self) -> String {
+        let all_points = || self.series.iter().flat_map(|series| &series.points);
+        let x_max = all_points().map(|&(x, _)| x).fold(0.0, f64::max);
+        let y_max = self
+            .y_max
+            .unwrap_or_else(|| all_points().map(|&(_, y)| y).fold(0.0, f64::max));
+        let x_axis = Axis::new(x_max);
+        let y_axis = Axis::new(y_max);
+
+        let plot_width = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+        let plot_height = HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+        let to_x = |x: f64| MARGIN_LEFT + x / x_axis.max * plot_width;
+        let to_y = |y: f64| MARGIN_TOP + plot_height - y.min(y_axis.max) / y_axis.max * plot_height;
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" font-family="sans-serif" font-size="12">"#
+        );
+        let _ = writeln!(
+            svg,
+            r#"<rect width="{WIDTH}" height="{HEIGHT}" fill="white"/>"#
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="24" text-anchor="middle" font-size="16">{}</text>"#,
+            WIDTH / 2.0,
+            escape(&self.title)
+        );
+
+        for tick in x_axis.ticks() {
+            let x = to_x(tick);
+            let _ = writeln!(
+                svg,
+                r##"<line x1="{x:.1}" y1="{MARGIN_TOP}" x2="{x:.1}" y2="{:.1}" stroke="#e0e0e0"/>"##,
+                MARGIN_TOP + plot_height
+            );
+            let _ = writeln!(
+                svg,
+                r#"<text x="{x:.1}" y="{:.1}" text-anchor="middle">{}</text>"#,
+                MARGIN_TOP + plot_height + 16.0,
+                x_axis.label(tick)
+            );
+        }
+        for tick in y_axis.ticks() {
+            let y = to_y(tick);
+            let _ = writeln!(
+                svg,
+                r##"<line x1="{MARGIN_LEFT}" y1="{y:.1}" x2="{:.1}" y2="{y:.1}" stroke="#e0e0e0"/>"##,
+                MARGIN_LEFT + plot_width
+            );
+            let _ = writeln!(
+                svg,
+                r#"<text x="{:.1}" y="{:.1}" text-anchor="end">{}</text>"#,
+                MARGIN_LEFT - 6.0,
+                y + 4.0,
+                y_axis.label(tick)
+            );
+        }
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{MARGIN_LEFT}" y="{MARGIN_TOP}" width="{plot_width}" height="{plot_height}" fill="none" stroke="black"/>"#
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{:.1}" y="{:.1}" text-anchor="middle">{}</text>"#,
+            MARGIN_LEFT + plot_width / 2.0,
+            HEIGHT - 12.0,
+            escape(&self.x_label)
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text transform="translate(18 {:.1}) rotate(-90)" text-anchor="middle">{}</text>"#,
+            MARGIN_TOP + plot_height / 2.0,
+            escape(&self.y_label)
+        );
+
+        for (index, series) in self.series.iter().enumerate() {
+            let color = COLORS[index % COLORS.len()];
+            let mut points = String::new();
+            for &(x, y) in &series.points {
+                let _ = write!(points, "{:.1},{:.1} ", to_x(x), to_y(y));
+            }
+            let _ = writeln!(
+                svg,
+                r#"<polyline points="{}" fill="none" stroke="{color}" stroke-width="1"/>"#,
+                points.trim_end()
+            );
+
+            let legend_y = MARGIN_TOP + 16.0 + index as f64 * 16.0;
+            let legend_x = MARGIN_LEFT + 10.0;
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{legend_x}" y="{:.1}" width="12" height="3" fill="{color}"/>"#,
+                legend_y - 5.0
+            );
+            let _ = writeln!(
+                svg,
+                r#"<text x="{}" y="{legend_y:.1}">{}</text>"#,
+                legend_x + 18.0,
+                escape(&series.label)
+            );
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// An axis from 0 to a multiple of a round tick step
+struct Axis {
+    step: f64,
+    max: f64,
+}
+
+impl Axis {
+    fn new(max: f64) -> Self {
+        if !(max > 0.0 && max.is_finite()) {
+            return Self {
+                step: 1.0,
+                max: 1.0,
+            };
+        }
+
+        let rough_step = max / TICKS;
+        let magnitude = 10f64.powf(rough_step.log10().floor());
+        let step = [1.0, 2.0, 5.0, 10.0]
+            .into_iter()
+            .map(|factor| factor * magnitude)
+            .find(|&step| step >= rough_step)
+            .unwrap_or(10.0 * magnitude);
+
+        Self {
+            step,
+            max: (max / step).ceil() * step,
+        }
+    }
+
+    fn ticks(&self) -> impl Iterator<Item = f64> + '_ {
+        let count = (self.max / self.step).round() as u32;
+        (0..=count).map(|index| index as f64 * self.step)
+    }
+
+    /// Just the decimals the step needs
+    fn label(&self, tick: f64) -> String {
+        let decimals = (-self.step.log10().floor()).max(0.0) as usize;
+        format!("{tick:.decimals$}")
+    }
+}
+
+/// Escapes text for XML and HTML
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn axes_use_round_steps() {
+        let axis = Axis::new(17.3);
+        assert_eq!((axis.step, axis.max), (5.0, 20.0));
+        assert_eq!(axis.ticks().count(), 5);
+
+        let axis = Axis::new(0.42);
+        assert_eq!(axis.step, 0.1);
+        assert_eq!(axis.label(0.30000000000000004), "0.3");
+
+        assert_eq!(Axis::new(0.0).max, 1.0);
+    }
+
+    #[test]
+    fn renders_series_and_escapes_labels() {
+        let plot = LinePlot {
+            title: "a < b".to_owned(),
+            x_label: "Frame".to_owned(),
+            y_label: "ms".to_owned(),
+            series: vec![Series {
+                label: "wgpu & vulkan".to_owned(),
+                points: vec![(0.0, 1.0), (10.0, 100.0)],
+            }],
+            y_max: Some(5.0),
+        };
+        let svg = plot.to_svg();
+
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+        assert!(svg.contains("a &lt; b") && svg.contains("wgpu &amp; vulkan"));
+        // The point above the y axis is drawn at its top
+        assert!(svg.contains(&format!("{:.1},{MARGIN_TOP:.1}", WIDTH - MARGIN_RIGHT)));
+    }
+}
@@ -0,0 +1,56 @@
+//! Lightweight scoped timers for breaking down the CPU time of a frame into phases.
+//!
+//! Renderers tag their phases with [`phase`], the application collects them after every frame.
+//! Phases are recorded per thread, so they must be tagged on the thread that calls
+//! [`Renderer::render`](crate::Renderer::render).
+
+use std::{
+    cell::RefCell,
+    time::{Duration, Instant},
+};
+
+thread_local! {
+    static PHASES: RefCell<Vec<PhaseTiming>> = const { RefCell::new(Vec::new()) };
+}
+
+#[derive(Clone, Debug)]
+pub struct PhaseTiming {
+    pub name: &'static str,
+    pub duration: Duration,
+}
+
+/// Starts timing a phase, which ends when the returned guard is dropped.
+/// Phases with the same name are summed up within a frame.
+pub fn phase(name: &'static str) -> PhaseGuard {
+    PhaseGuard {
+        name,
+        start: Instant::now(),
+    }
+}
+
+#[must_use = "the phase ends as soon as the guard is dropped"]
+pub struct PhaseGuard {
+    name: &'static str,
+    start: Instant,
+}
+
+impl Drop for PhaseGuard {
+    fn drop(&mut self) {
+        let duration = self.start.elapsed();
+
+        PHASES.with_borrow_mut(|phases| {
+            match phases.iter_mut().find(|phase| phase.name == self.name) {
+                Some(phase) => phase.duration += duration,
+                None => phases.push(PhaseTiming {
+                    name: self.name,
+                    duration,
+                }),
+            }
+        });
+    }
+}
+
+/// Returns all phases recorded on this thread since the last call, in the order they first ended
+pub fn take_phases() -> Vec<PhaseTiming> {
+    PHASES.with_borrow_mut(std::mem::take)
+}
@@ -0,0 +1,200 @@
+//! Sub-allocation of ranges in a buffer, for backends that keep many meshes in a few large
+//! buffers and need to reuse the space of unloaded ones.
+
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+/// A first-fit allocator of ranges within `0..capacity`. The unit is up to the user, e.g.
+/// vertices or indices. Adjacent free ranges are merged, so the free list stays short.
+#[derive(Clone, Debug)]
+pub struct RangeAllocator {
+    capacity: u64,
+    /// Sorted and never adjacent or overlapping
+    free: Vec<Range<u64>>,
+}
+
+impl RangeAllocator {
+    pub fn new(capacity: u64) -> Self {
+        let mut allocator = Self {
+            capacity: 0,
+            free: Vec::new(),
+        };
+        allocator.grow(capacity);
+        allocator
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Returns `None` if there is no free range of at least `len`. Empty allocations always
+    /// succeed.
+    pub fn allocate(&mut self, len: u64) -> Option<Range<u64>> {
+        if len == 0 {
+            return Some(0..0);
+        }
+
+        let index = self
+            .free
+            .iter()
+            .position(|range| range.end - range.start >= len)?;
+
+        let free = &mut self.free[index];
+        let allocated = free.start..free.start + len;
+        free.start += len;
+        if free.is_empty() {
+            self.free.remove(index);
+        }
+
+        Some(allocated)
+    }
+
+    /// `range` must have been returned by [`Self::allocate`] and not been freed yet
+    pub fn free(&mut self, range: Range<u64>) {
+        if range.is_empty() {
+            return;
+        }
+        assert!(range.end <= self.capacity, "range is out of bounds");
+
+        let index = self.free.partition_point(|free| free.start < range.start);
+        assert!(
+            index == self.free.len() || range.end <= self.free[index].start,
+            "range overlaps a free range"
+        );
+        assert!(
+            index == 0 || self.free[index - 1].end <= range.start,
+            "range overlaps a free range"
+        );
+
+        let merges_previous = index > 0 && self.free[index - 1].end == range.start;
+        let merges_next = index < self.free.len() && self.free[index].start == range.end;
+
+        match (merges_previous, merges_next) {
+            (true, true) => {
+                self.free[index - 1].end = self.free[index].end;
+                self.free.remove(index);
+            }
+            (true, false) => self.free[index - 1].end = range.end,
+            (false, true) => self.free[index].start = range.start,
+            (false, false) => self.free.insert(index, range),
+        }
+    }
+
+    /// Adds the space from the old to the new capacity, which must not be smaller
+    pub fn grow(&mut self, capacity: u64) {
+        assert!(capacity >= self.capacity, "allocators can't shrink");
+        let added = self.capacity..capacity;
+        self.capacity = capacity;
+        self.free(added);
+    }
+
+    pub fn free_space(&self) -> u64 {
+        self.free.iter().map(|range| range.end - range.start).sum()
+    }
+
+    pub fn largest_free_range(&self) -> u64 {
+        self.free
+            .iter()
+            .map(|range| range.end - range.start)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// 0 if all free space is in one range, approaching 1 the more it is split into small ranges
+    pub fn fragmentation(&self) -> f64 {
+        match self.free_space() {
+            0 => 0.0,
+            free_space => 1.0 - self.largest_free_range() as f64 / free_space as f64,
+        }
+    }
+}
+
+/// How full and fragmented a buffer that meshes are allocated from is, in the unit of its
+/// allocator
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BufferStats {
+    /// Like `Position+Normal vertices`
+    pub buffer: String,
+    pub capacity: u64,
+    pub free_space: u64,
+    /// See [`RangeAllocator::fragmentation`]
+    pub fragmentation: f64,
+}
+
+impl BufferStats {
+    pub fn of(buffer: impl Into<String>, allocator: &RangeAllocator) -> Self {
+        Self {
+            buffer: buffer.into(),
+            capacity: allocator.capacity(),
+            free_space: allocator.free_space(),
+            fragmentation: allocator.fragmentation(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuses_freed_ranges_first_fit() {
+        let mut allocator = RangeAllocator::new(10);
+        let a = allocator.allocate(4).unwrap();
+        let b = allocator.allocate(4).unwrap();
+        assert_eq!((a.clone(), b), (0..4, 4..8));
+        assert_eq!(allocator.allocate(3), None);
+
+        allocator.free(a);
+        assert_eq!(allocator.allocate(2), Some(0..2));
+        assert_eq!(allocator.allocate(2), Some(2..4));
+        assert_eq!(allocator.allocate(2), Some(8..10));
+        assert_eq!(allocator.free_space(), 0);
+    }
+
+    #[test]
+    fn merges_adjacent_free_ranges() {
+        let mut allocator = RangeAllocator::new(9);
+        let ranges: Vec<_> = (0..3).map(|_| allocator.allocate(3).unwrap()).collect();
+
+        allocator.free(ranges[0].clone());
+        allocator.free(ranges[2].clone());
+        assert_eq!(allocator.largest_free_range(), 3);
+        assert!((allocator.fragmentation() - 0.5).abs() < 1e-9);
+
+        allocator.free(ranges[1].clone());
+        assert_eq!(allocator.largest_free_range(), 9);
+        assert_eq!(allocator.fragmentation(), 0.0);
+    }
+
+    #[test]
+    fn stats_describe_the_free_space() {
+        let mut allocator = RangeAllocator::new(8);
+        let ranges: Vec<_> = (0..4).map(|_| allocator.allocate(2).unwrap()).collect();
+        allocator.free(ranges[0].clone());
+        allocator.free(ranges[2].clone());
+
+        let stats = BufferStats::of("indices", &allocator);
+        assert_eq!((stats.capacity, stats.free_space), (8, 4));
+        assert!((stats.fragmentation - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn growing_extends_the_last_free_range() {
+        let mut allocator = RangeAllocator::new(4);
+        allocator.allocate(2).unwrap();
+        allocator.grow(8);
+
+        assert_eq!(allocator.capacity(), 8);
+        assert_eq!(allocator.allocate(6), Some(2..8));
+    }
+
+    #[test]
+    #[should_panic(expected = "overlaps")]
+    fn double_free_panics() {
+        let mut allocator = RangeAllocator::new(4);
+        let range = allocator.allocate(2).unwrap();
+        allocator.free(range.clone());
+        allocator.free(range);
+    }
+}
@@ -1,10 +1,90 @@
-use std::num::NonZeroU32;
+use std::{num::NonZeroU32, time::Duration};
 
-use crate::{Camera, HasWindowAndDisplayHandle, Mesh};
+use crate::{
+    BufferStats, Camera, HasWindowAndDisplayHandle, Image, Mesh, PresentMode, RendererSettings, SampleCount,
+    VertexAttributes,
+};
 
 pub trait Renderer {
-    fn new(window: impl HasWindowAndDisplayHandle + Send + Sync + 'static, initial_window_size: (u32, u32)) -> Self;
+    fn new(window: impl HasWindowAndDisplayHandle + Send + Sync + 'static, initial_window_size: (u32, u32), settings: &RendererSettings) -> Self;
+    /// Creates a renderer that draws into an offscreen target instead of a window surface
+    fn new_headless(size: (u32, u32), settings: &RendererSettings) -> Self;
     fn render(&mut self, camera: Camera);
     fn resize(&mut self, size: (NonZeroU32, NonZeroU32), scale_factor: f64);
-    fn load_mesh(&mut self, mesh: Mesh);
-}
\ No newline at end of file
+    /// Uploads a mesh, which is drawn by every following [`Renderer::render`] until it is
+    /// unloaded
+    fn load_mesh(&mut self, mesh: Mesh) -> MeshHandle;
+    /// Frees the memory of a loaded mesh, panics if the handle is not loaded
+    fn unload_mesh(&mut self, handle: MeshHandle);
+    /// Replaces a loaded mesh, which keeps its handle. The new mesh may have a different size and
+    /// different vertex attributes.
+    fn update_mesh(&mut self, handle: MeshHandle, mesh: Mesh);
+    fn info(&self) -> RendererInfo;
+
+    /// Keeps the frame of the next [`Renderer::render`] for [`Renderer::capture`]. Other frames
+    /// are not kept, as copying every frame would skew the measurements.
+    fn request_capture(&mut self);
+    /// Reads back the frame kept after [`Renderer::request_capture`] as an RGBA image. Returns
+    /// `None` if no frame was kept since the last capture or resize, or if the window surface
+    /// can't be read back.
+    fn capture(&mut self) -> Option<Image>;
+
+    /// Returns the GPU timings of all frames that finished since the last call.
+    ///
+    /// Timings usually arrive a few frames late and may be missing for some frames, e.g. when all
+    /// readback buffers are still in use. Renderers without GPU timing support return nothing.
+    fn gpu_timings(&mut self) -> Vec<GpuFrameTiming> {
+        Vec::new()
+    }
+
+    /// Waits until the GPU finished all submitted frames and their timings were read back, so that
+    /// the next [`Renderer::gpu_timings`] returns all of them
+    fn finish_gpu_timings(&mut self) {}
+
+    /// The state of the buffers meshes are allocated from, for renderers that keep them in a few
+    /// large buffers
+    fn buffer_stats(&self) -> Vec<BufferStats> {
+        Vec::new()
+    }
+}
+
+/// Identifies a mesh loaded into a [`Renderer`]. Renderers hand out increasing ids and never
+/// reuse them, so a stale handle can't refer to a different mesh.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MeshHandle(pub u64);
+
+/// Describes a renderer and the device it runs on, for benchmark results
+#[derive(Clone, Debug)]
+pub struct RendererInfo {
+    pub backend: String,
+    pub adapter: String,
+    pub driver: String,
+    /// The sample count that was negotiated from [`RendererSettings::msaa`]
+    pub msaa: SampleCount,
+    /// The present mode that was negotiated from [`RendererSettings::present_mode`], `None` when
+    /// rendering offscreen
+    pub present_mode: Option<PresentMode>,
+    /// `None` if the renderer does not limit how many frames are queued up
+    pub max_frame_latency: Option<u32>,
+    /// The vertex attributes meshes are drawn with, others are ignored by the renderer
+    pub vertex_attributes: VertexAttributes,
+}
+
+#[derive(Clone, Debug)]
+pub struct GpuFrameTiming {
+    /// Zero-based index of the [`Renderer::render`] call this frame was rendered by
+    pub frame: u64,
+    pub passes: Vec<GpuPassTiming>,
+}
+
+#[derive(Clone, Debug)]
+pub struct GpuPassTiming {
+    pub name: &'static str,
+    pub duration: Duration,
+}
+
+impl GpuFrameTiming {
+    pub fn total(&self) -> Duration {
+        self.passes.iter().map(|pass| pass.duration).sum()
+    }
+}
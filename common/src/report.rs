@@ -0,0 +1,542 @@
+//! Reports that compare the frame times of exported runs, as Markdown or as standalone HTML.
+//!
+//! The first run is the baseline. Every other run gets its speedup over the baseline with a
+//! bootstrapped confidence interval, and a Mann-Whitney U test of whether its frame times differ
+//! from the baseline's at all.
+
+use std::fmt::{self, Write};
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    plot::{escape, LinePlot, Series},
+    BenchmarkResults, FrameSample, MannWhitney, Speedup, Summary,
+};
+
+/// Bootstrap resamples of every speedup
+const RESAMPLES: usize = 1000;
+/// Differences with a lower p-value are reported as significant
+const SIGNIFICANCE_LEVEL: f64 = 0.05;
+
+/// Which frame times are compared
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Timing {
+    #[default]
+    Cpu,
+    /// Only of renderers that report GPU timings
+    Gpu,
+}
+
+impl Timing {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Cpu => "CPU",
+            Self::Gpu => "GPU",
+        }
+    }
+
+    fn frame_time_ms(self, sample: &FrameSample) -> Option<f64> {
+        match self {
+            Self::Cpu => Some(sample.cpu_ms),
+            Self::Gpu => sample.gpu_ms,
+        }
+    }
+
+    fn summary(self, results: &BenchmarkResults) -> Option<Summary> {
+        match self {
+            Self::Cpu => Some(results.cpu_frame_time),
+            Self::Gpu => results.gpu_frame_time,
+        }
+    }
+}
+
+/// Exported results with the name they have in the report
+pub struct ReportRun {
+    pub label: String,
+    pub results: BenchmarkResults,
+}
+
+/// A run compared to the baseline
+#[derive(Clone, Copy, Debug)]
+pub struct Comparison {
+    pub speedup: Speedup,
+    pub mann_whitney: MannWhitney,
+}
+
+impl Comparison {
+    pub fn is_significant(&self) -> bool {
+        self.mann_whitney.p_value < SIGNIFICANCE_LEVEL
+    }
+}
+
+/// An SVG plot of the report
+pub struct Plot {
+    /// Part of the file name when written next to a Markdown report
+    pub name: &'static str,
+    pub title: &'static str,
+    pub svg: String,
+}
+
+pub struct Report {
+    timing: Timing,
+    runs: Vec<ReportRun>,
+    summaries: Vec<Summary>,
+    /// In the order of the frames of every run
+    frame_times_ms: Vec<Vec<f64>>,
+    /// Of every run but the baseline
+    comparisons: Vec<Comparison>,
+}
+
+#[derive(Debug)]
+pub enum ReportError {
+    TooFewRuns,
+    MissingTimings { run: String, timing: Timing },
+}
+
+impl fmt::Display for ReportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooFewRuns => write!(f, "a comparison needs at least two runs"),
+            Self::MissingTimings { run, timing } => {
+                write!(f, "{run} has no {} frame times", timing.name())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReportError {}
+
+struct Table {
+    heading: String,
+    note: Option<String>,
+    columns: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+}
+
+impl Report {
+    /// Compares the runs to the first one
+    pub fn new(runs: Vec<ReportRun>, timing: Timing) -> Result<Self, ReportError> {
+        if runs.len() < 2 {
+            return Err(ReportError::TooFewRuns);
+        }
+
+        let mut summaries = Vec::new();
+        let mut frame_times_ms = Vec::new();
+        for run in &runs {
+            let frame_times: Vec<f64> = run
+                .results
+                .samples
+                .iter()
+                .filter_map(|sample| timing.frame_time_ms(sample))
+                .collect();
+            let summary = timing
+                .summary(&run.results)
+                .filter(|_| !frame_times.is_empty())
+                .ok_or_else(|| ReportError::MissingTimings {
+                    run: run.label.clone(),
+                    timing,
+                })?;
+
+            summaries.push(summary);
+            frame_times_ms.push(frame_times);
+        }
+
+        // Seeded, so that the same results always give the same report
+        let mut rng = StdRng::seed_from_u64(0);
+        let baseline = &frame_times_ms[0];
+        let comparisons = frame_times_ms[1..]
+            .iter()
+            .map(|frame_times| Comparison {
+                speedup: Speedup::bootstrap(baseline, frame_times, RESAMPLES, &mut rng)
+                    .expect("frame times are not empty"),
+                mann_whitney: MannWhitney::test(baseline, frame_times)
+                    .expect("frame times are not empty"),
+            })
+            .collect();
+
+        Ok(Self {
+            timing,
+            runs,
+            summaries,
+            frame_times_ms,
+            comparisons,
+        })
+    }
+
+    /// Of every run but the baseline, in the order of the runs
+    pub fn comparisons(&self) -> &[Comparison] {
+        &self.comparisons
+    }
+
+    /// Frame times over the frames of every run, and their percentiles. Frame times above the
+    /// highest 99.9th percentile are drawn at the top, so that single spikes don't flatten the rest.
+    pub fn plots(&self) -> Vec<Plot> {
+        let y_max = self
+            .summaries
+            .iter()
+            .map(|summary| summary.p999_ms)
+            .fold(0.0, f64::max);
+        let y_label = format!("{} frame time (ms)", self.timing.name());
+
+        let over_frames = self
+            .runs
+            .iter()
+            .zip(&self.frame_times_ms)
+            .map(|(run, frame_times)| Series {
+                label: run.label.clone(),
+                points: frame_times
+                    .iter()
+                    .enumerate()
+                    .map(|(frame, &ms)| (frame as f64, ms))
+                    .collect(),
+            })
+            .collect();
+
+        let percentiles = self
+            .runs
+            .iter()
+            .zip(&self.frame_times_ms)
+            .map(|(run, frame_times)| {
+                let mut sorted = frame_times.clone();
+                sorted.sort_by(f64::total_cmp);
+                let last = (sorted.len() - 1).max(1) as f64;
+                Series {
+                    label: run.label.clone(),
+                    points: sorted
+                        .iter()
+                        .enumerate()
+                        .map(|(index, &ms)| (index as f64 / last * 100.0, ms))
+                        .collect(),
+                }
+            })
+            .collect();
+
+        [
+            ("frame-times", "Frame times", "Frame", over_frames),
+            (
+                "percentiles",
+                "Frame time percentiles",
+                "Percentile",
+                percentiles,
+            ),
+        ]
+        .into_iter()
+        .map(|(name, title, x_label, series)| Plot {
+            name,
+            title,
+            svg: LinePlot {
+                title: title.to_owned(),
+                x_label: x_label.to_owned(),
+                y_label: y_label.clone(),
+                series,
+                y_max: Some(y_max),
+            }
+            .to_svg(),
+        })
+        .collect()
+    }
+
+    /// The report with links to the [`Report::plots`], which are expected next to it as
+    /// `<plot_stem>-<name>.svg`
+    pub fn to_markdown(&self, plot_stem: &str) -> String {
+        let cell = |text: &str| text.replace('|', "\\|");
+
+        let mut markdown = String::new();
+        let _ = writeln!(markdown, "# {}\n", self.title());
+        for table in self.tables() {
+            let _ = writeln!(markdown, "## {}\n", table.heading);
+            if let Some(note) = &table.note {
+                let _ = writeln!(markdown, "{note}\n");
+            }
+            let _ = writeln!(markdown, "| {} |", table.columns.join(" | "));
+            let _ = writeln!(markdown, "|{}", " --- |".repeat(table.columns.len()));
+            for row in &table.rows {
+                let row: Vec<String> = row.iter().map(|text| cell(text)).collect();
+                let _ = writeln!(markdown, "| {} |", row.join(" | "));
+            }
+            markdown.push('\n');
+        }
+
+        let _ = writeln!(markdown, "## Plots\n");
+        for plot in self.plots() {
+            let _ = writeln!(
+                markdown,
+                "![{}]({plot_stem}-{}.svg)\n",
+                plot.title, plot.name
+            );
+        }
+        markdown
+    }
+
+    /// A standalone page with the plots inlined
+    pub fn to_html(&self) -> String {
+        let title = escape(&self.title());
+
+        let mut html = String::new();
+        let _ = writeln!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>"
+        );
+        let _ = writeln!(
+            html,
+            "<style>\nbody {{ font-family: sans-serif; margin: 2em; }}\n\
+             table {{ border-collapse: collapse; }}\n\
+             th, td {{ border: 1px solid #ccc; padding: 4px 8px; text-align: right; }}\n\
+             th:first-child, td:first-child {{ text-align: left; }}\n</style>"
+        );
+        let _ = writeln!(html, "</head>\n<body>\n<h1>{title}</h1>");
+
+        for table in self.tables() {
+            let _ = writeln!(html, "<h2>{}</h2>", escape(&table.heading));
+            if let Some(note) = &table.note {
+                let _ = writeln!(html, "<p>{}</p>", escape(note));
+            }
+            let _ = write!(html, "<table>\n<tr>");
+            for column in table.columns {
+                let _ = write!(html, "<th>{}</th>", escape(column));
+            }
+            let _ = writeln!(html, "</tr>");
+            for row in &table.rows {
+                let _ = write!(html, "<tr>");
+                for text in row {
+                    let _ = write!(html, "<td>{}</td>", escape(text));
+                }
+                let _ = writeln!(html, "</tr>");
+            }
+            let _ = writeln!(html, "</table>");
+        }
+
+        let _ = writeln!(html, "<h2>Plots</h2>");
+        for plot in self.plots() {
+            let _ = writeln!(html, "<figure>\n{}</figure>", plot.svg);
+        }
+        let _ = writeln!(html, "</body>\n</html>");
+        html
+    }
+
+    fn title(&self) -> String {
+        format!("{} frame time comparison", self.timing.name())
+    }
+
+    fn tables(&self) -> Vec<Table> {
+        let baseline = &self.runs[0].label;
+
+        let runs = Table {
+            heading: "Runs".to_owned(),
+            note: None,
+            columns: &[
+                "Run",
+                "Backend",
+                "Adapter",
+                "Scene",
+                "MSAA",
+                "Vertex attributes",
+                "Frames",
+                "Duration (s)",
+                "FPS",
+            ],
+            rows: self
+                .runs
+                .iter()
+                .map(|run| {
+                    let metadata = &run.results.metadata;
+                    vec![
+                        run.label.clone(),
+                        metadata.backend.clone(),
+                        metadata.adapter.clone(),
+                        metadata.scene.clone(),
+                        metadata.msaa_samples.to_string(),
+                        metadata
+                            .vertex_attributes
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        metadata.frames.to_string(),
+                        format!("{:.2}", metadata.duration_secs),
+                        format!("{:.1}", metadata.frames as f64 / metadata.duration_secs),
+                    ]
+                })
+                .collect(),
+        };
+
+        let frame_times = Table {
+            heading: format!("{} frame times (ms)", self.timing.name()),
+            note: None,
+            columns: &[
+                "Run", "Mean", "Std dev", "Min", "p50", "p90", "p99", "p99.9", "Max",
+            ],
+            rows: self
+                .runs
+                .iter()
+                .zip(&self.summaries)
+                .map(|(run, summary)| {
+                    let mut row = vec![run.label.clone()];
+                    row.extend(
+                        [
+                            summary.mean_ms,
+                            summary.variance_ms2.sqrt(),
+                            summary.min_ms,
+                            summary.p50_ms,
+                            summary.p90_ms,
+                            summary.p99_ms,
+                            summary.p999_ms,
+                            summary.max_ms,
+                        ]
+                        .map(|ms| format!("{ms:.3}")),
+                    );
+                    row
+                })
+                .collect(),
+        };
+
+        let speedups = Table {
+            heading: format!("Speedup over {baseline}"),
+            note: Some(format!(
+                "Mean frame time of {baseline} over the mean frame time of the run, above 1 is \
+                 faster. The 95% confidence interval is from {RESAMPLES} bootstrap resamples of \
+                 the frames. Differences are significant if a two-sided Mann-Whitney U test of \
+                 the frame times has a p-value below {SIGNIFICANCE_LEVEL}."
+            )),
+            columns: &[
+                "Run",
+                "Speedup",
+                "95% CI",
+                "U",
+                "z",
+                "p-value",
+                "Significant",
+            ],
+            rows: self.runs[1..]
+                .iter()
+                .zip(&self.comparisons)
+                .map(|(run, comparison)| {
+                    let Comparison {
+                        speedup,
+                        mann_whitney,
+                    } = comparison;
+                    vec![
+                        run.label.clone(),
+                        format!("{:.3}×", speedup.ratio),
+                        format!("{:.3} – {:.3}", speedup.ci95_low, speedup.ci95_high),
+                        format!("{:.0}", mann_whitney.u),
+                        format!("{:.2}", mann_whitney.z),
+                        if mann_whitney.p_value < 0.001 {
+                            "< 0.001".to_owned()
+                        } else {
+                            format!("{:.3}", mann_whitney.p_value)
+                        },
+                        if comparison.is_significant() {
+                            "yes"
+                        } else {
+                            "no"
+                        }
+                        .to_owned(),
+                    ]
+                })
+                .collect(),
+        };
+
+        vec![runs, frame_times, speedups]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::{Histogram, RunMetadata, VertexAttributes};
+
+    fn run(label: &str, frame_times_ms: impl Iterator<Item = f64>) -> ReportRun {
+        let mut histogram = Histogram::new();
+        let samples: Vec<FrameSample> = frame_times_ms
+            .enumerate()
+            .map(|(frame, cpu_ms)| {
+                histogram.record(std::time::Duration::from_secs_f64(cpu_ms / 1e3));
+                FrameSample {
+                    frame: frame as u64,
+                    time_secs: 0.0,
+                    cpu_ms,
+                    gpu_ms: None,
+                    cpu_phases_ms: BTreeMap::new(),
+                }
+            })
+            .collect();
+
+        ReportRun {
+            label: label.to_owned(),
+            results: BenchmarkResults {
+                metadata: RunMetadata {
+                    backend: label.to_owned(),
+                    adapter: "adapter".to_owned(),
+                    driver: "driver".to_owned(),
+                    scene: "triangles".to_owned(),
+                    started_at: 0,
+                    duration_secs: 1.0,
+                    frames: samples.len() as u64,
+                    msaa_samples: 1,
+                    present_mode: None,
+                    max_frame_latency: None,
+                    warmup_frames: 0,
+                    repetition: 0,
+                    seed: None,
+                    fixed_step_secs: None,
+                    vertex_attributes: vec![VertexAttributes::POSITION_ONLY],
+                },
+                samples,
+                cpu_frame_time: histogram.summary(),
+                gpu_frame_time: None,
+                cpu_phases: BTreeMap::new(),
+                buffers: Vec::new(),
+            },
+        }
+    }
+
+    fn jittered(mean_ms: f64) -> impl Iterator<Item = f64> {
+        (0..300).map(move |frame| mean_ms + (frame % 7) as f64 * 0.1)
+    }
+
+    #[test]
+    fn compares_runs_to_the_baseline() {
+        let report = Report::new(
+            vec![
+                run("vulkan", jittered(4.0)),
+                run("wgpu", jittered(8.0)),
+                run("wgpu-again", jittered(4.0)),
+            ],
+            Timing::Cpu,
+        )
+        .unwrap();
+
+        let [slower, same] = report.comparisons() else {
+            panic!("expected two comparisons");
+        };
+        assert!(slower.speedup.ratio < 0.6 && slower.speedup.ci95_high < 1.0);
+        assert!(slower.is_significant());
+        assert!((same.speedup.ratio - 1.0).abs() < 1e-9);
+        assert!(!same.is_significant());
+
+        let markdown = report.to_markdown("report");
+        assert!(markdown.contains("## Speedup over vulkan"));
+        assert!(markdown.contains("![Frame times](report-frame-times.svg)"));
+        let html = report.to_html();
+        assert_eq!(html.matches("<svg").count(), 2);
+        assert_eq!(html.matches("<table>").count(), 3);
+    }
+
+    #[test]
+    fn rejects_missing_timings() {
+        assert!(matches!(
+            Report::new(vec![run("vulkan", jittered(4.0))], Timing::Cpu),
+            Err(ReportError::TooFewRuns)
+        ));
+        assert!(matches!(
+            Report::new(
+                vec![run("vulkan", jittered(4.0)), run("wgpu", jittered(4.0))],
+                Timing::Gpu
+            ),
+            Err(ReportError::MissingTimings { .. })
+        ));
+    }
+}
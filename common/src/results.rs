@@ -0,0 +1,176 @@
+//! Benchmark results of a single run, exportable as JSON (everything) or CSV (per-frame samples),
+//! and their aggregation across the repetitions of a scenario.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Aggregate, BufferStats, PresentMode, Summary, VertexAttributes};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BenchmarkResults {
+    pub metadata: RunMetadata,
+    pub samples: Vec<FrameSample>,
+    pub cpu_frame_time: Summary,
+    /// `None` if the renderer does not report GPU timings
+    pub gpu_frame_time: Option<Summary>,
+    /// CPU time spent in the phases tagged by the renderer, see [`crate::phase`]
+    pub cpu_phases: BTreeMap<String, Summary>,
+    /// The mesh buffers at the end of the run, see [`crate::Renderer::buffer_stats`]
+    #[serde(default)]
+    pub buffers: Vec<BufferStats>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RunMetadata {
+    pub backend: String,
+    pub adapter: String,
+    pub driver: String,
+    pub scene: String,
+    /// Seconds since the unix epoch
+    pub started_at: u64,
+    pub duration_secs: f64,
+    pub frames: u64,
+    /// Samples per pixel, 1 without MSAA
+    #[serde(default = "single_sample")]
+    pub msaa_samples: u32,
+    /// `None` when rendering offscreen
+    #[serde(default)]
+    pub present_mode: Option<PresentMode>,
+    /// `None` if the renderer does not limit how many frames are queued up
+    #[serde(default)]
+    pub max_frame_latency: Option<u32>,
+    /// Frames rendered before the first repetition, they are not part of the samples
+    #[serde(default)]
+    pub warmup_frames: u64,
+    /// Index of the run among the repetitions of its scenario
+    #[serde(default)]
+    pub repetition: u32,
+    /// Seed of the random numbers of the run, `None` in results from before it was recorded
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Animation time per frame, `None` if the animation followed the wall-clock time
+    #[serde(default)]
+    pub fixed_step_secs: Option<f64>,
+    /// The distinct attribute sets of the drawn meshes, empty in results from before they were
+    /// recorded
+    #[serde(default)]
+    pub vertex_attributes: Vec<VertexAttributes>,
+}
+
+/// The repetitions of a scenario combined, every run counts once regardless of its frame count
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AggregatedResults {
+    /// Metadata of the first repetition
+    pub metadata: RunMetadata,
+    pub repetitions: u32,
+    pub fps: Aggregate,
+    /// Of the mean CPU frame time of every run
+    pub cpu_frame_time_ms: Aggregate,
+    /// Of the mean GPU frame time of every run, `None` if not every run has GPU timings
+    pub gpu_frame_time_ms: Option<Aggregate>,
+    /// Phases that were recorded in every run
+    pub cpu_phases_ms: BTreeMap<String, Aggregate>,
+}
+
+/// Results from before MSAA was configurable were rendered without it
+fn single_sample() -> u32 {
+    1
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FrameSample {
+    pub frame: u64,
+    /// Seconds since the start of the run
+    pub time_secs: f64,
+    pub cpu_ms: f64,
+    pub gpu_ms: Option<f64>,
+    pub cpu_phases_ms: BTreeMap<String, f64>,
+}
+
+impl BenchmarkResults {
+    pub fn write_json(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()
+    }
+
+    pub fn read_json(path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = io::BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Writes one row per frame. Metadata and aggregates are only part of the JSON export.
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        write!(writer, "frame,time_secs,cpu_ms,gpu_ms")?;
+        for phase in self.cpu_phases.keys() {
+            write!(writer, ",cpu_{phase}_ms")?;
+        }
+        writeln!(writer)?;
+
+        for sample in &self.samples {
+            write!(
+                writer,
+                "{},{},{},",
+                sample.frame, sample.time_secs, sample.cpu_ms
+            )?;
+            if let Some(gpu_ms) = sample.gpu_ms {
+                write!(writer, "{gpu_ms}")?;
+            }
+            for phase in self.cpu_phases.keys() {
+                write!(writer, ",")?;
+                if let Some(phase_ms) = sample.cpu_phases_ms.get(phase) {
+                    write!(writer, "{phase_ms}")?;
+                }
+            }
+            writeln!(writer)?;
+        }
+
+        writer.flush()
+    }
+}
+
+impl AggregatedResults {
+    /// `None` without any runs
+    pub fn of(runs: &[BenchmarkResults]) -> Option<Self> {
+        let first = runs.first()?;
+        let aggregate = |mean: &dyn Fn(&BenchmarkResults) -> Option<f64>| {
+            let means: Option<Vec<f64>> = runs.iter().map(mean).collect();
+            means.map(|means| Aggregate::of(&means))
+        };
+
+        Some(Self {
+            metadata: first.metadata.clone(),
+            repetitions: runs.len() as u32,
+            fps: aggregate(&|run| Some(run.metadata.frames as f64 / run.metadata.duration_secs))?,
+            cpu_frame_time_ms: aggregate(&|run| Some(run.cpu_frame_time.mean_ms))?,
+            gpu_frame_time_ms: aggregate(&|run| Some(run.gpu_frame_time?.mean_ms)),
+            cpu_phases_ms: first
+                .cpu_phases
+                .keys()
+                .filter_map(|phase| {
+                    let phase_ms = aggregate(&|run| Some(run.cpu_phases.get(phase)?.mean_ms))?;
+                    Some((phase.clone(), phase_ms))
+                })
+                .collect(),
+        })
+    }
+
+    pub fn write_json(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()
+    }
+
+    pub fn read_json(path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = io::BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
@@ -0,0 +1,330 @@
+//! Benchmark scenarios, which describe what a run renders and measures. They are usually loaded
+//! from TOML files, so that benchmark definitions can be versioned together with their results:
+//!
+//! ```toml
+//! name = "flythrough"
+//! scene = { model = "sponza.glb" }  # or { triangles = 100 }
+//! length = { seconds = 10.0 }       # or { frames = 1000 }
+//! clock = { fixed_step = 0.0166 }   # or "real_time", default real_time
+//! warmup_frames = 60                # default 0
+//! repetitions = 3                   # default 1
+//! metrics = ["gpu_frame_time"]      # default all
+//! seed = 42                         # default random, --seed takes precedence
+//!
+//! [camera]
+//! interpolation = "catmull_rom"     # or "linear", default catmull_rom
+//! repeat = "loop"                   # or "once" or "ping_pong", default once
+//!
+//! [[camera.keyframes]]
+//! at = 0.0                          # fraction of the run
+//! position = [0.0, 0.0, 10.0]
+//! pitch = 0.0                       # degrees, default 0
+//! yaw = 270.0                       # degrees
+//! ```
+
+use std::{fmt, io, path::Path};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{CameraPath, Cli, ClockMode, Interpolation, Keyframe, Repeat, RunLength, SceneSource};
+
+/// The camera path of runs that are not described by a scenario file
+const DEFAULT_KEYFRAMES: [Keyframe; 2] = [
+    Keyframe {
+        at: 0.0,
+        position: [0.0, 0.0, 10.0],
+        pitch: 0.0,
+        yaw: 270.0,
+    },
+    Keyframe {
+        at: 1.0,
+        position: [10.0, 0.0, 0.0],
+        pitch: 0.0,
+        yaw: 180.0,
+    },
+];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Recorded as the scene of the results
+    pub name: String,
+    /// Model paths are relative to the scenario file
+    pub scene: SceneSource,
+    /// Length of every repetition
+    #[serde(
+        serialize_with = "serialize_length",
+        deserialize_with = "deserialize_length"
+    )]
+    pub length: RunLength,
+    /// How the camera path advances, a fixed step renders the same frames on every backend
+    #[serde(default)]
+    pub clock: ClockMode,
+    /// Frames rendered before the first repetition, they are not measured
+    #[serde(default)]
+    pub warmup_frames: u64,
+    /// Number of measured runs, each of them is exported on its own and more than one are also
+    /// aggregated into a summary
+    #[serde(default = "one")]
+    pub repetitions: u32,
+    /// Metrics that are recorded besides the CPU frame times
+    #[serde(default = "all_metrics")]
+    pub metrics: Vec<Metric>,
+    /// Seed of the random triangles, `None` uses the one of the command line or a random one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Keyframes are at fractions of the run, from 0 to 1
+    pub camera: CameraPath,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// Only recorded if the renderer supports GPU timestamps
+    GpuFrameTime,
+    CpuPhases,
+}
+
+impl Metric {
+    pub const ALL: [Self; 2] = [Self::GpuFrameTime, Self::CpuPhases];
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(io::Error),
+    Toml(toml::de::Error),
+    Invalid(&'static str),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => err.fmt(f),
+            Self::Toml(err) => err.fmt(f),
+            Self::Invalid(reason) => write!(f, "invalid scenario: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Toml(err) => Some(err),
+            Self::Invalid(_) => None,
+        }
+    }
+}
+
+impl Scenario {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ScenarioError> {
+        let path = path.as_ref();
+        let mut scenario = Self::parse(&std::fs::read_to_string(path).map_err(ScenarioError::Io)?)?;
+
+        if let (SceneSource::Model(model), Some(dir)) = (&mut scenario.scene, path.parent()) {
+            *model = dir.join(&*model);
+        }
+
+        Ok(scenario)
+    }
+
+    pub fn parse(toml: &str) -> Result<Self, ScenarioError> {
+        let scenario: Self = toml::from_str(toml).map_err(ScenarioError::Toml)?;
+
+        if scenario.repetitions == 0 {
+            return Err(ScenarioError::Invalid("repetitions must be at least 1"));
+        }
+        if !scenario
+            .camera
+            .keyframes()
+            .iter()
+            .all(|keyframe| (0.0..=1.0).contains(&keyframe.at))
+        {
+            return Err(ScenarioError::Invalid("keyframes have to be at 0 to 1"));
+        }
+
+        Ok(scenario)
+    }
+
+    /// Writes the scenario in the format of [`Scenario::load`]. Model paths are written as they
+    /// are, so they should be absolute or relative to `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let toml = toml::to_string_pretty(self).map_err(io::Error::other)?;
+        std::fs::write(path, toml)
+    }
+
+    /// The scenario of the command line options, for runs without a scenario file
+    pub fn from_cli(cli: &Cli) -> Self {
+        Self {
+            name: match &cli.scene {
+                SceneSource::Triangles(_) => "triangles".to_owned(),
+                SceneSource::Model(path) => path.display().to_string(),
+            },
+            scene: cli.scene.clone(),
+            length: cli.length(),
+            clock: cli.clock,
+            warmup_frames: cli.warmup_frames,
+            repetitions: cli.repetitions,
+            metrics: Metric::ALL.to_vec(),
+            seed: None,
+            camera: CameraPath::new(
+                DEFAULT_KEYFRAMES.to_vec(),
+                Interpolation::default(),
+                Repeat::Once,
+            )
+            .expect("the default keyframes are sorted"),
+        }
+    }
+
+    pub fn records(&self, metric: Metric) -> bool {
+        self.metrics.contains(&metric)
+    }
+}
+
+/// How [`RunLength`]s are written in scenario files
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum Length {
+    Seconds(f64),
+    Frames(u64),
+}
+
+fn serialize_length<S: Serializer>(length: &RunLength, serializer: S) -> Result<S::Ok, S::Error> {
+    match *length {
+        RunLength::Duration(duration) => Length::Seconds(duration.as_secs_f64()),
+        RunLength::Frames(frames) => Length::Frames(frames),
+    }
+    .serialize(serializer)
+}
+
+fn deserialize_length<'de, D: Deserializer<'de>>(deserializer: D) -> Result<RunLength, D::Error> {
+    match Length::deserialize(deserializer)? {
+        Length::Seconds(seconds) => std::time::Duration::try_from_secs_f64(seconds)
+            .ok()
+            .filter(|duration| !duration.is_zero())
+            .map(RunLength::Duration)
+            .ok_or_else(|| serde::de::Error::custom("the length has to be a positive duration")),
+        Length::Frames(0) => Err(serde::de::Error::custom(
+            "the length has to be at least one frame",
+        )),
+        Length::Frames(frames) => Ok(RunLength::Frames(frames)),
+    }
+}
+
+fn one() -> u32 {
+    1
+}
+
+fn all_metrics() -> Vec<Metric> {
+    Metric::ALL.to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_scenario_file_matches_defaults() {
+        let scenario = Scenario::parse(include_str!("../../scenarios/default.toml")).unwrap();
+        assert_eq!(scenario, Scenario::from_cli(&Cli::default()));
+    }
+
+    #[test]
+    fn scenario_files_are_valid() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../scenarios");
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if let Err(err) = Scenario::load(&path) {
+                panic!("{}: {err}", path.display());
+            }
+        }
+    }
+
+    #[test]
+    fn saved_scenarios_load_again() {
+        let mut scenario = Scenario::parse(include_str!("../../scenarios/orbit.toml")).unwrap();
+        scenario.scene = SceneSource::Model("/models/sponza.glb".into());
+        scenario.length = RunLength::Frames(100);
+        scenario.seed = Some(7);
+        scenario.clock = ClockMode::FixedStep(std::time::Duration::from_millis(10));
+
+        let path = std::env::temp_dir().join("saved_scenarios_load_again.toml");
+        scenario.save(&path).unwrap();
+        assert_eq!(Scenario::load(&path).unwrap(), scenario);
+    }
+
+    #[test]
+    fn parses_camera_path() {
+        let scenario = Scenario::parse(
+            r#"
+            name = "test"
+            scene = { triangles = 1 }
+            length = { frames = 10 }
+            metrics = []
+
+            [camera]
+            interpolation = "linear"
+
+            [[camera.keyframes]]
+            at = 0.5
+            position = [0.0, 0.0, 0.0]
+            yaw = 0.0
+
+            [[camera.keyframes]]
+            at = 1.0
+            position = [2.0, 4.0, 0.0]
+            pitch = 90.0
+            yaw = 90.0
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(scenario.length, RunLength::Frames(10));
+        assert_eq!(scenario.repetitions, 1);
+        assert!(!scenario.records(Metric::GpuFrameTime));
+
+        let camera = scenario.camera.camera_at(0.25);
+        assert_eq!(camera.xyz, (0.0, 0.0, 0.0));
+
+        let camera = scenario.camera.camera_at(0.75);
+        assert_eq!(camera.xyz, (1.0, 2.0, 0.0));
+        assert!((camera.pitch - 45f32.to_radians()).abs() < 1e-6);
+        assert!((camera.yaw - 45f32.to_radians()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rejects_invalid_scenarios() {
+        let invalid = |toml: &str| {
+            matches!(
+                Scenario::parse(&format!(
+                    "name = \"test\"\nscene = {{ triangles = 1 }}\n{toml}"
+                )),
+                Err(ScenarioError::Toml(_) | ScenarioError::Invalid(_))
+            )
+        };
+
+        assert!(invalid("length = { frames = 0 }\ncamera.keyframes = []"));
+        assert!(invalid("length = { frames = 1 }\ncamera.keyframes = []"));
+        assert!(invalid(
+            "length = { seconds = 1.0 }\nrepetitions = 0\n\
+             camera.keyframes = [{ at = 0.0, position = [0, 0, 0], yaw = 0 }]"
+        ));
+        assert!(invalid(
+            "length = { seconds = 1.0 }\ncamera.keyframes = [\n\
+             { at = 1.0, position = [0, 0, 0], yaw = 0 },\n\
+             { at = 0.5, position = [0, 0, 0], yaw = 0 },\n]"
+        ));
+        assert!(invalid(
+            "length = { seconds = 1.0 }\n\
+             camera.keyframes = [{ at = 1.5, position = [0, 0, 0], yaw = 0 }]"
+        ));
+        assert!(invalid(
+            "length = { seconds = 1.0 }\n\
+             camera.keyframes = [{ at = -0.5, position = [0, 0, 0], yaw = 0 }]"
+        ));
+        assert!(invalid(
+            "length = { seconds = 1.0 }\nfov = 90\n\
+             camera.keyframes = [{ at = 0.0, position = [0, 0, 0], yaw = 0 }]"
+        ));
+    }
+}
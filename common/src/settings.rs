@@ -0,0 +1,287 @@
+//! Settings that every backend implements the same way, so that results stay comparable.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// How a renderer is set up, passed to [`crate::Renderer::new`] and
+/// [`crate::Renderer::new_headless`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RendererSettings {
+    /// `None` draws overlapping triangles in submission order, without a depth buffer
+    pub depth: Option<DepthSettings>,
+    /// The requested sample count, backends fall back to a lower one if it is not supported
+    pub msaa: SampleCount,
+    /// Only applies to windows, offscreen targets are never presented
+    pub present_mode: PresentMode,
+    /// How many frames may be queued up before the CPU waits for the GPU, see
+    /// [`RendererSettings::frame_latency`]
+    pub max_frame_latency: u32,
+}
+
+impl RendererSettings {
+    /// No depth buffer, which is how the backends rendered before depth testing existed
+    pub fn without_depth() -> Self {
+        Self {
+            depth: None,
+            ..Self::default()
+        }
+    }
+
+    /// [`RendererSettings::max_frame_latency`] clamped to `1..=MAX_FRAME_LATENCY`, which all
+    /// backends support. The command line rejects other values already.
+    pub fn frame_latency(&self) -> u32 {
+        debug_assert!(
+            (1..=MAX_FRAME_LATENCY).contains(&self.max_frame_latency),
+            "unsupported maximum frame latency {}",
+            self.max_frame_latency
+        );
+        self.max_frame_latency.clamp(1, MAX_FRAME_LATENCY)
+    }
+}
+
+pub const MAX_FRAME_LATENCY: u32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepthSettings {
+    pub format: DepthFormat,
+    pub compare: DepthCompare,
+    /// Maps the near plane to depth 1 and the far plane to 0, which spreads the precision of
+    /// floating point depth buffers more evenly. Nearer fragments then have a greater depth, so
+    /// `compare` has to be [`DepthCompare::Greater`] or [`DepthCompare::GreaterEqual`].
+    pub reverse_z: bool,
+}
+
+impl DepthSettings {
+    /// `None` if reverse-Z is combined with a compare other than [`DepthCompare::Greater`] or
+    /// [`DepthCompare::GreaterEqual`], which would draw the far side of the scene
+    pub fn new(format: DepthFormat, compare: DepthCompare, reverse_z: bool) -> Option<Self> {
+        let nearer_passes = matches!(compare, DepthCompare::Greater | DepthCompare::GreaterEqual);
+        (!reverse_z || nearer_passes).then_some(Self {
+            format,
+            compare,
+            reverse_z,
+        })
+    }
+
+    /// A 32-bit float depth buffer with reverse-Z
+    pub fn reverse_z() -> Self {
+        Self::new(DepthFormat::Depth32Float, DepthCompare::Greater, true).unwrap()
+    }
+
+    /// The depth that the depth buffer is cleared to, which is the far plane
+    pub fn clear_value(&self) -> f32 {
+        if self.reverse_z {
+            0.0
+        } else {
+            1.0
+        }
+    }
+}
+
+impl Default for DepthSettings {
+    fn default() -> Self {
+        Self {
+            format: DepthFormat::Depth32Float,
+            compare: DepthCompare::Less,
+            reverse_z: false,
+        }
+    }
+}
+
+impl Default for RendererSettings {
+    fn default() -> Self {
+        Self {
+            depth: Some(DepthSettings::default()),
+            msaa: SampleCount::ONE,
+            present_mode: PresentMode::Fifo,
+            max_frame_latency: 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DepthFormat {
+    Depth16Unorm,
+    /// At least 24 bits, backends choose the exact format
+    Depth24Plus,
+    Depth32Float,
+}
+
+/// Passes if the depth of the fragment compares like this to the stored depth
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DepthCompare {
+    Never,
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+impl DepthCompare {
+    pub fn passes(self, fragment: f32, stored: f32) -> bool {
+        match self {
+            Self::Never => false,
+            Self::Less => fragment < stored,
+            Self::Equal => fragment == stored,
+            Self::LessEqual => fragment <= stored,
+            Self::Greater => fragment > stored,
+            Self::NotEqual => fragment != stored,
+            Self::GreaterEqual => fragment >= stored,
+            Self::Always => true,
+        }
+    }
+}
+
+/// Samples per pixel of the color and depth targets. Multisampled targets are resolved into the
+/// render target at the end of every pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SampleCount(u32);
+
+impl SampleCount {
+    /// No multisample anti-aliasing, which every backend supports
+    pub const ONE: Self = Self(1);
+    /// All counts that can be requested, in increasing order
+    pub const ALL: [Self; 4] = [Self(1), Self(2), Self(4), Self(8)];
+
+    /// Returns `None` unless `count` is 1, 2, 4 or 8
+    pub fn new(count: u32) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|sample_count| sample_count.0 == count)
+    }
+
+    pub fn get(self) -> u32 {
+        self.0
+    }
+
+    /// Returns the highest count that is at most `self` and that `supported` accepts, and tells
+    /// the user if that is not `self`
+    pub fn negotiate(self, supported: impl Fn(Self) -> bool) -> Self {
+        let chosen = Self::ALL
+            .into_iter()
+            .rev()
+            .filter(|&sample_count| sample_count <= self)
+            .find(|&sample_count| sample_count == Self::ONE || supported(sample_count))
+            .unwrap_or(Self::ONE);
+
+        if chosen != self {
+            println!("{self} MSAA is not supported, falling back to {chosen}");
+        }
+        chosen
+    }
+}
+
+impl fmt::Display for SampleCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x", self.0)
+    }
+}
+
+/// When frames are shown on the screen, named like in Vulkan and wgpu
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PresentMode {
+    /// Waits for vertical blanks, so the frame rate is capped at the refresh rate. Always
+    /// supported.
+    Fifo,
+    /// Like [`PresentMode::Fifo`], but late frames are shown immediately and may tear
+    FifoRelaxed,
+    /// Uncapped without tearing, a newer frame replaces the one waiting for the vertical blank
+    Mailbox,
+    /// Uncapped, frames are shown immediately and may tear
+    Immediate,
+}
+
+impl PresentMode {
+    pub const ALL: [Self; 4] = [
+        Self::Fifo,
+        Self::FifoRelaxed,
+        Self::Mailbox,
+        Self::Immediate,
+    ];
+
+    /// The modes to try if this one is not supported, ending with [`PresentMode::Fifo`]. Uncapped
+    /// modes fall back to each other first, so that the frame rate stays uncapped if possible.
+    fn fallbacks(self) -> &'static [Self] {
+        match self {
+            Self::Fifo => &[Self::Fifo],
+            Self::FifoRelaxed => &[Self::FifoRelaxed, Self::Fifo],
+            Self::Mailbox => &[Self::Mailbox, Self::Immediate, Self::Fifo],
+            Self::Immediate => &[Self::Immediate, Self::Mailbox, Self::Fifo],
+        }
+    }
+
+    /// Returns the first mode of [`PresentMode::fallbacks`] that `supported` accepts, and tells
+    /// the user if that is not `self`
+    pub fn negotiate(self, supported: impl Fn(Self) -> bool) -> Self {
+        let chosen = self
+            .fallbacks()
+            .iter()
+            .copied()
+            .find(|&mode| mode == Self::Fifo || supported(mode))
+            .unwrap_or(Self::Fifo);
+
+        if chosen != self {
+            println!("Present mode {self} is not supported, falling back to {chosen}");
+        }
+        chosen
+    }
+}
+
+impl fmt::Display for PresentMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_powers_of_two_up_to_eight_are_sample_counts() {
+        assert_eq!(SampleCount::new(4).map(SampleCount::get), Some(4));
+        assert_eq!(SampleCount::new(0), None);
+        assert_eq!(SampleCount::new(3), None);
+        assert_eq!(SampleCount::new(16), None);
+    }
+
+    #[test]
+    fn reverse_z_needs_a_greater_compare() {
+        let new = |compare| DepthSettings::new(DepthFormat::Depth32Float, compare, true);
+        assert!(new(DepthCompare::Greater).is_some());
+        assert!(new(DepthCompare::GreaterEqual).is_some());
+        assert_eq!(new(DepthCompare::Less), None);
+        assert_eq!(new(DepthCompare::LessEqual), None);
+        assert!(DepthSettings::new(DepthFormat::Depth16Unorm, DepthCompare::Less, false).is_some());
+    }
+
+    #[test]
+    fn negotiation_falls_back_to_the_next_lower_supported_count() {
+        let eight = SampleCount::new(8).unwrap();
+        let four = SampleCount::new(4).unwrap();
+
+        assert_eq!(eight.negotiate(|_| true), eight);
+        assert_eq!(eight.negotiate(|count| count <= four), four);
+        assert_eq!(four.negotiate(|count| count == eight), SampleCount::ONE);
+    }
+
+    #[test]
+    fn uncapped_present_modes_fall_back_to_each_other_before_fifo() {
+        assert_eq!(
+            PresentMode::Mailbox.negotiate(|mode| mode == PresentMode::Immediate),
+            PresentMode::Immediate
+        );
+        assert_eq!(
+            PresentMode::Immediate.negotiate(|_| false),
+            PresentMode::Fifo
+        );
+        assert_eq!(
+            PresentMode::FifoRelaxed.negotiate(|mode| mode == PresentMode::Mailbox),
+            PresentMode::Fifo
+        );
+    }
+}
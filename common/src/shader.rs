@@ -0,0 +1,97 @@
+//! Shader variants for the different vertex attribute sets.
+//!
+//! The shader source is shared by all GPU backends and contains `#ifdef NAME`, `#ifndef NAME`,
+//! `#else` and `#endif` lines, where `NAME` is the [`VertexAttribute::define`] of an attribute.
+//! Lines that are not part of the variant are replaced by empty lines, so line numbers in shader
+//! compiler errors still match the source.
+
+use crate::{VertexAttribute, VertexAttributes};
+
+/// Panics if the conditionals in `source` are not balanced
+pub fn shader_variant(source: &str, attributes: VertexAttributes) -> String {
+    let defined = |name: &str| {
+        attributes
+            .iter()
+            .any(|attribute: VertexAttribute| attribute.define() == name)
+    };
+
+    // Whether the lines of each enclosing conditional are kept
+    let mut conditions: Vec<bool> = Vec::new();
+    let mut output = String::with_capacity(source.len());
+
+    for (line_index, line) in source.lines().enumerate() {
+        let mut tokens = line.split_whitespace();
+        let directive = tokens.next().filter(|token| token.starts_with('#'));
+        let line_number = line_index + 1;
+
+        match (directive, tokens.next()) {
+            (Some("#ifdef"), Some(name)) => conditions.push(defined(name)),
+            (Some("#ifndef"), Some(name)) => conditions.push(!defined(name)),
+            (Some("#else"), None) => {
+                let condition = conditions
+                    .last_mut()
+                    .unwrap_or_else(|| panic!("line {line_number}: #else without #ifdef"));
+                *condition = !*condition;
+            }
+            (Some("#endif"), None) => {
+                conditions
+                    .pop()
+                    .unwrap_or_else(|| panic!("line {line_number}: #endif without #ifdef"));
+            }
+            (Some(directive), _) => panic!("line {line_number}: malformed `{directive}`"),
+            (None, _) => {
+                if conditions.iter().all(|&keep| keep) {
+                    output.push_str(line);
+                }
+            }
+        }
+        output.push('\n');
+    }
+
+    assert!(conditions.is_empty(), "unterminated #ifdef");
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "a
+#ifdef NORMAL
+b
+#ifndef COLOR
+c
+#else
+d
+#endif
+#endif
+e";
+
+    fn lines(attributes: VertexAttributes) -> Vec<String> {
+        shader_variant(SOURCE, attributes)
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(str::to_owned)
+            .collect()
+    }
+
+    #[test]
+    fn keeps_the_lines_of_defined_attributes() {
+        let normal = VertexAttributes::POSITION_ONLY.with(VertexAttribute::Normal);
+
+        assert_eq!(lines(VertexAttributes::POSITION_ONLY), ["a", "e"]);
+        assert_eq!(lines(normal), ["a", "b", "c", "e"]);
+        assert_eq!(
+            lines(normal.with(VertexAttribute::Color)),
+            ["a", "b", "d", "e"]
+        );
+    }
+
+    #[test]
+    fn preserves_line_numbers() {
+        let variant = shader_variant(SOURCE, VertexAttributes::POSITION_ONLY);
+        assert_eq!(variant.lines().count(), SOURCE.lines().count());
+        assert_eq!(variant.lines().last(), Some("e"));
+    }
+}
@@ -0,0 +1,565 @@
+//! Frame time statistics.
+//!
+//! Samples are recorded into a log-linear [`Histogram`], so percentiles can be queried without
+//! keeping every sample around. Min, max, mean and variance are tracked exactly.
+//!
+//! Repeated runs are combined into an [`Aggregate`] of one value per run. Runs are compared with a
+//! bootstrapped [`Speedup`] and a [`MannWhitney`] U test on their frame times.
+
+use std::{fmt, time::Duration};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Every power of two is split into `2^SUB_BUCKET_BITS` linear sub-buckets.
+/// With 7 bits the relative error of a percentile is below 1%.
+const SUB_BUCKET_BITS: u32 = 7;
+const SUB_BUCKET_COUNT: u64 = 1 << SUB_BUCKET_BITS;
+
+/// A histogram of durations with nanosecond resolution
+#[derive(Clone, Debug, Default)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    samples: u64,
+    min: u64,
+    max: u64,
+    // Welford's online algorithm for mean and variance
+    mean: f64,
+    m2: f64,
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, duration: Duration) {
+        let value = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+
+        let index = bucket_index(value);
+        if index >= self.counts.len() {
+            self.counts.resize(index + 1, 0);
+        }
+        self.counts[index] += 1;
+
+        if self.samples == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+
+        self.samples += 1;
+        let delta = value as f64 - self.mean;
+        self.mean += delta / self.samples as f64;
+        self.m2 += delta * (value as f64 - self.mean);
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    pub fn min(&self) -> Duration {
+        Duration::from_nanos(self.min)
+    }
+
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max)
+    }
+
+    pub fn mean(&self) -> Duration {
+        Duration::from_nanos(self.mean as u64)
+    }
+
+    /// Population variance in nanoseconds squared
+    pub fn variance(&self) -> f64 {
+        if self.samples == 0 {
+            0.0
+        } else {
+            self.m2 / self.samples as f64
+        }
+    }
+
+    /// Returns the value below which the fraction `quantile` (0.0..=1.0) of all samples lie.
+    pub fn percentile(&self, quantile: f64) -> Duration {
+        if self.samples == 0 {
+            return Duration::ZERO;
+        }
+
+        let rank = ((quantile.clamp(0.0, 1.0) * self.samples as f64).ceil() as u64).max(1);
+
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let (low, width) = bucket_range(index);
+                let value = low + width / 2;
+                return Duration::from_nanos(value.clamp(self.min, self.max));
+            }
+        }
+
+        self.max()
+    }
+
+    pub fn summary(&self) -> Summary {
+        Summary {
+            samples: self.samples,
+            min_ms: to_ms(self.min()),
+            max_ms: to_ms(self.max()),
+            mean_ms: self.mean / 1e6,
+            p50_ms: to_ms(self.percentile(0.5)),
+            p90_ms: to_ms(self.percentile(0.9)),
+            p99_ms: to_ms(self.percentile(0.99)),
+            p999_ms: to_ms(self.percentile(0.999)),
+            variance_ms2: self.variance() / 1e12,
+        }
+    }
+}
+
+fn bucket_index(value: u64) -> usize {
+    if value < SUB_BUCKET_COUNT {
+        return value as usize;
+    }
+
+    let shift = 63 - value.leading_zeros() - SUB_BUCKET_BITS;
+    let sub_bucket = (value >> shift) - SUB_BUCKET_COUNT;
+
+    (SUB_BUCKET_COUNT * (shift as u64 + 1) + sub_bucket) as usize
+}
+
+/// Returns the lowest value and the width of a bucket
+fn bucket_range(index: usize) -> (u64, u64) {
+    let index = index as u64;
+    if index < SUB_BUCKET_COUNT {
+        return (index, 1);
+    }
+
+    let shift = index / SUB_BUCKET_COUNT - 1;
+    let sub_bucket = index % SUB_BUCKET_COUNT;
+
+    ((SUB_BUCKET_COUNT + sub_bucket) << shift, 1 << shift)
+}
+
+fn to_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1e3
+}
+
+/// Aggregated statistics of a [`Histogram`]. All times are in milliseconds.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct Summary {
+    pub samples: u64,
+    pub min_ms: f64,
+    pub max_ms: f64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub p999_ms: f64,
+    pub variance_ms2: f64,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} samples | min {:.3}ms | mean {:.3}ms | max {:.3}ms | p50 {:.3}ms | p90 {:.3}ms | p99 {:.3}ms | p99.9 {:.3}ms | variance {:.4}ms²",
+            self.samples,
+            self.min_ms,
+            self.mean_ms,
+            self.max_ms,
+            self.p50_ms,
+            self.p90_ms,
+            self.p99_ms,
+            self.p999_ms,
+            self.variance_ms2,
+        )
+    }
+}
+
+/// Statistics of a value measured once per run, like the mean frame time of every repetition
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Aggregate {
+    pub runs: u64,
+    /// Mean of the values of all runs, for mean frame times the mean of means
+    pub mean: f64,
+    /// Sample standard deviation across runs, 0 for a single run
+    pub std_dev: f64,
+    /// Half the width of the 95% confidence interval of the mean, from Student's t-distribution.
+    /// 0 for a single run, as the spread between runs is unknown.
+    pub ci95: f64,
+}
+
+impl Aggregate {
+    pub fn of(values: &[f64]) -> Self {
+        let runs = values.len();
+        if runs == 0 {
+            return Self::default();
+        }
+
+        let mean = values.iter().sum::<f64>() / runs as f64;
+        if runs == 1 {
+            return Self {
+                runs: 1,
+                mean,
+                ..Self::default()
+            };
+        }
+
+        let variance = values
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f64>()
+            / (runs - 1) as f64;
+        let std_dev = variance.sqrt();
+
+        Self {
+            runs: runs as u64,
+            mean,
+            std_dev,
+            ci95: t_critical_95(runs as u64 - 1) * std_dev / (runs as f64).sqrt(),
+        }
+    }
+
+    /// Bounds of the 95% confidence interval of the mean
+    pub fn ci95_range(&self) -> (f64, f64) {
+        (self.mean - self.ci95, self.mean + self.ci95)
+    }
+}
+
+impl fmt::Display for Aggregate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.3} ± {:.3} (95% CI) | std dev {:.3} | {} runs",
+            self.mean, self.ci95, self.std_dev, self.runs
+        )
+    }
+}
+
+/// The two-sided 95% critical value of Student's t-distribution with `degrees_of_freedom`
+pub fn t_critical_95(degrees_of_freedom: u64) -> f64 {
+    const TABLE: [f64; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+        2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+        2.052, 2.048, 2.045, 2.042,
+    ];
+    const Z: f64 = 1.959964;
+
+    match degrees_of_freedom {
+        0 => f64::INFINITY,
+        1..=30 => TABLE[degrees_of_freedom as usize - 1],
+        // The first term of the Cornish-Fisher expansion, within 0.001 from here on
+        _ => Z + (Z.powi(3) + Z) / (4.0 * degrees_of_freedom as f64),
+    }
+}
+
+/// How much faster a run is than a baseline, as the ratio of their mean frame times
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Speedup {
+    /// Baseline mean over the mean of the run, above 1 if the run is faster
+    pub ratio: f64,
+    pub ci95_low: f64,
+    pub ci95_high: f64,
+}
+
+impl Speedup {
+    /// The speedup of `frame_times` over `baseline`, with a 95% confidence interval from
+    /// `resamples` percentile bootstrap resamples. Frames are resampled independently, which
+    /// makes the interval too narrow if consecutive frame times are correlated.
+    ///
+    /// `None` if either is empty.
+    pub fn bootstrap(
+        baseline: &[f64],
+        frame_times: &[f64],
+        resamples: usize,
+        rng: &mut impl Rng,
+    ) -> Option<Self> {
+        if baseline.is_empty() || frame_times.is_empty() {
+            return None;
+        }
+
+        let mut resample_mean = |values: &[f64]| {
+            (0..values.len())
+                .map(|_| values[rng.gen_range(0..values.len())])
+                .sum::<f64>()
+                / values.len() as f64
+        };
+        let mut ratios: Vec<f64> = (0..resamples)
+            .map(|_| resample_mean(baseline) / resample_mean(frame_times))
+            .collect();
+        ratios.sort_by(f64::total_cmp);
+
+        let ratio = mean(baseline) / mean(frame_times);
+        let quantile = |quantile: f64| {
+            let index = (quantile * (ratios.len() as f64 - 1.0)).round() as usize;
+            ratios.get(index).copied().unwrap_or(ratio)
+        };
+
+        Some(Self {
+            ratio,
+            ci95_low: quantile(0.025),
+            ci95_high: quantile(0.975),
+        })
+    }
+}
+
+/// The Mann-Whitney U test of whether the frame times of two runs come from the same distribution,
+/// with the normal approximation that is accurate for the thousands of frames of a run
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MannWhitney {
+    /// U of the first sample, how many pairs of frames have the frame of the first run be slower
+    pub u: f64,
+    /// Standard score of `u`, corrected for ties and continuity
+    pub z: f64,
+    /// Two-sided probability of a `z` at least this extreme if both are the same
+    pub p_value: f64,
+}
+
+impl MannWhitney {
+    /// `None` if either sample is empty
+    pub fn test(a: &[f64], b: &[f64]) -> Option<Self> {
+        if a.is_empty() || b.is_empty() {
+            return None;
+        }
+
+        let mut values: Vec<(f64, bool)> = a
+            .iter()
+            .map(|&value| (value, true))
+            .chain(b.iter().map(|&value| (value, false)))
+            .collect();
+        values.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+        // Tied values share the mean of their ranks
+        let mut rank_sum_a = 0.0;
+        let mut tie_term = 0.0;
+        let mut start = 0;
+        while start < values.len() {
+            let end = start
+                + values[start..]
+                    .iter()
+                    .take_while(|(value, _)| *value == values[start].0)
+                    .count();
+            let ties = (end - start) as f64;
+            let rank = (start + end + 1) as f64 / 2.0;
+            rank_sum_a += rank * values[start..end].iter().filter(|(_, in_a)| *in_a).count() as f64;
+            tie_term += ties.powi(3) - ties;
+            start = end;
+        }
+
+        let (n_a, n_b) = (a.len() as f64, b.len() as f64);
+        let n = n_a + n_b;
+        let u = rank_sum_a - n_a * (n_a + 1.0) / 2.0;
+        let mean = n_a * n_b / 2.0;
+        let variance = n_a * n_b / 12.0 * ((n + 1.0) - tie_term / (n * (n - 1.0)));
+
+        if variance <= 0.0 {
+            return Some(Self {
+                u,
+                z: 0.0,
+                p_value: 1.0,
+            });
+        }
+
+        let difference = u - mean;
+        let z = (difference.abs() - 0.5).max(0.0).copysign(difference) / variance.sqrt();
+        Some(Self {
+            u,
+            z,
+            p_value: (2.0 * (1.0 - normal_cdf(z.abs()))).clamp(0.0, 1.0),
+        })
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// The cumulative distribution function of the standard normal distribution
+fn normal_cdf(z: f64) -> f64 {
+    0.5 * (1.0 + erf(z / std::f64::consts::SQRT_2))
+}
+
+/// Abramowitz and Stegun 7.1.26, accurate to 1.5e-7
+fn erf(x: f64) -> f64 {
+    const P: f64 = 0.3275911;
+    const A: [f64; 5] = [
+        0.254829592,
+        -0.284496736,
+        1.421413741,
+        -1.453152027,
+        1.061405429,
+    ];
+
+    let t = 1.0 / (1.0 + P * x.abs());
+    let polynomial = A.iter().rev().fold(0.0, |sum, a| sum * t + a) * t;
+    (1.0 - polynomial * (-x * x).exp()).copysign(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn histogram_of(nanos: impl IntoIterator<Item = u64>) -> Histogram {
+        let mut histogram = Histogram::new();
+        for value in nanos {
+            histogram.record(Duration::from_nanos(value));
+        }
+        histogram
+    }
+
+    fn relative_error(actual: Duration, expected: Duration) -> f64 {
+        (actual.as_nanos() as f64 - expected.as_nanos() as f64).abs() / expected.as_nanos() as f64
+    }
+
+    #[test]
+    fn buckets_are_contiguous() {
+        // Below 2 * SUB_BUCKET_COUNT every value has its own bucket
+        assert_eq!(bucket_index(0), 0);
+        assert_eq!(bucket_index(127), 127);
+        assert_eq!(bucket_index(255), 255);
+        assert_eq!(bucket_range(255), (255, 1));
+        // Above, every power of two halves the resolution
+        assert_eq!(bucket_range(256), (256, 2));
+        assert_eq!(bucket_index(257), 256);
+        assert_eq!(bucket_index(258), 257);
+        assert_eq!(bucket_range(384), (512, 4));
+
+        let mut next_low = 0;
+        for index in 0..bucket_index(u64::MAX) {
+            let (low, width) = bucket_range(index);
+            assert_eq!(low, next_low, "gap before bucket {index}");
+            assert_eq!(bucket_index(low), index);
+            assert_eq!(bucket_index(low + width - 1), index);
+            next_low = low + width;
+        }
+
+        let (low, width) = bucket_range(bucket_index(u64::MAX));
+        assert_eq!(low + (width - 1), u64::MAX);
+    }
+
+    #[test]
+    fn empty_histograms_report_zero() {
+        let histogram = Histogram::new();
+        assert_eq!(histogram.samples(), 0);
+        for quantile in [0.0, 0.5, 1.0] {
+            assert_eq!(histogram.percentile(quantile), Duration::ZERO);
+        }
+        assert_eq!(histogram.variance(), 0.0);
+
+        let summary = histogram.summary();
+        assert_eq!(summary.samples, 0);
+        assert_eq!(
+            (summary.min_ms, summary.p50_ms, summary.max_ms),
+            (0.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn percentiles_of_known_distributions() {
+        // Small values are recorded exactly
+        let exact = histogram_of(1..=100);
+        assert_eq!(exact.percentile(0.0), Duration::from_nanos(1));
+        assert_eq!(exact.percentile(0.5), Duration::from_nanos(50));
+        assert_eq!(exact.percentile(0.99), Duration::from_nanos(99));
+        assert_eq!(exact.percentile(1.0), Duration::from_nanos(100));
+
+        // 1µs to 1ms
+        let uniform = histogram_of((1..=1000).map(|micros| micros * 1000));
+        let micros = Duration::from_micros;
+        for (quantile, expected) in [(0.0, 1), (0.5, 500), (0.99, 990), (1.0, 1000)] {
+            let error = relative_error(uniform.percentile(quantile), micros(expected));
+            assert!(error < 0.01, "p{} is off by {error}", quantile * 100.0);
+        }
+        assert_eq!((uniform.min(), uniform.max()), (micros(1), micros(1000)));
+        assert_eq!(uniform.mean(), Duration::from_nanos(500_500));
+
+        // Every sample is the same, so the percentiles are clamped to it
+        let constant = histogram_of([16_666_667; 50]);
+        for quantile in [0.0, 0.5, 0.99, 1.0] {
+            assert_eq!(
+                constant.percentile(quantile),
+                Duration::from_nanos(16_666_667)
+            );
+        }
+        assert_eq!(constant.variance(), 0.0);
+    }
+
+    #[test]
+    fn percentiles_are_within_one_percent() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..10_000 {
+            // Log-uniform from 1ns to about 18 minutes
+            let value = 2f64.powf(rng.gen_range(0.0..40.0)) as u64;
+            // The median lies in the bucket of the value, but isn't clamped to it
+            let histogram = histogram_of([0, value, 4 * value]);
+            let error = relative_error(histogram.percentile(0.5), Duration::from_nanos(value));
+            assert!(error < 0.01, "{value}ns is off by {:.3}%", error * 100.0);
+        }
+    }
+
+    #[test]
+    fn aggregates_runs() {
+        let aggregate = Aggregate::of(&[10.0, 12.0, 11.0, 13.0, 9.0]);
+        assert_eq!(aggregate.runs, 5);
+        assert_eq!(aggregate.mean, 11.0);
+        assert!((aggregate.std_dev - 2.5f64.sqrt()).abs() < 1e-9);
+        // t(4) = 2.776, std error = sqrt(2.5 / 5)
+        assert!((aggregate.ci95 - 2.776 * 0.5f64.sqrt()).abs() < 1e-9);
+
+        let single = Aggregate::of(&[4.0]);
+        assert_eq!((single.mean, single.std_dev, single.ci95), (4.0, 0.0, 0.0));
+        assert_eq!(Aggregate::of(&[]).runs, 0);
+    }
+
+    #[test]
+    fn t_critical_values_approach_the_normal_distribution() {
+        assert_eq!(t_critical_95(1), 12.706);
+        assert!((t_critical_95(60) - 2.000).abs() < 1e-3);
+        assert!((t_critical_95(120) - 1.980).abs() < 1e-3);
+        assert!(t_critical_95(31) < t_critical_95(30));
+    }
+
+    #[test]
+    fn mann_whitney_detects_shifted_distributions() {
+        // 7 of the 126 orders have a U of at most 3, so the exact p-value is 14 / 126
+        let test = MannWhitney::test(&[1.0, 2.0, 4.0, 6.0], &[3.0, 5.0, 7.0, 8.0, 9.0]).unwrap();
+        assert_eq!(test.u, 3.0);
+        assert!((test.p_value - 14.0 / 126.0).abs() < 0.01, "{}", test.p_value);
+
+        let a: Vec<f64> = (0..200).map(|i| 10.0 + (i % 20) as f64 * 0.1).collect();
+        let shifted: Vec<f64> = a.iter().map(|value| value + 0.5).collect();
+        assert!(MannWhitney::test(&a, &shifted).unwrap().p_value < 1e-6);
+        assert!(MannWhitney::test(&a, &a).unwrap().p_value > 0.99);
+        assert_eq!(
+            MannWhitney::test(&[1.0; 5], &[1.0; 5]).unwrap().p_value,
+            1.0
+        );
+        assert!(MannWhitney::test(&a, &[]).is_none());
+    }
+
+    #[test]
+    fn bootstrap_speedup_contains_the_ratio() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let baseline: Vec<f64> = (0..500).map(|i| 20.0 + (i % 10) as f64).collect();
+        let faster: Vec<f64> = baseline.iter().map(|value| value / 2.0).collect();
+
+        let speedup = Speedup::bootstrap(&baseline, &faster, 1000, &mut rng).unwrap();
+        assert!((speedup.ratio - 2.0).abs() < 1e-9);
+        assert!(speedup.ci95_low < 2.0 && speedup.ci95_high > 2.0);
+        assert!(speedup.ci95_high - speedup.ci95_low < 0.2);
+    }
+
+    #[test]
+    fn normal_cdf_matches_known_values() {
+        assert!((normal_cdf(0.0) - 0.5).abs() < 1e-7);
+        assert!((normal_cdf(1.959964) - 0.975).abs() < 1e-6);
+        assert!((normal_cdf(-1.0) - 0.158655).abs() < 1e-6);
+    }
+}
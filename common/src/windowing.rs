@@ -7,6 +7,7 @@ use std::{num::NonZeroU32, sync::Arc};
 
 use winit::{
     application::ApplicationHandler,
+    dpi::PhysicalSize,
     event::{ElementState, KeyEvent, WindowEvent},
     event_loop::EventLoop,
     keyboard::{KeyCode, PhysicalKey},
@@ -16,13 +17,23 @@ use winit::{
 use crate::has_window_and_display_handle::HasWindowAndDisplayHandle;
 
 pub trait Application: Sized {
+    /// Passed to [`Application::new`] whenever the window is created
+    type Config: Clone;
+
     fn new(
+        config: Self::Config,
         window: impl HasWindowAndDisplayHandle + Send + Sync + 'static,
         initial_window_size: (u32, u32),
     ) -> Self;
     fn handle_event(&mut self, event: Event);
+
+    /// Checked after every rendered frame, the event loop exits once this returns `true`
+    fn should_exit(&self) -> bool {
+        false
+    }
 }
 
+#[allow(dead_code)] // Not all events are handled by the app yet
 pub enum Event {
     KeyboardInput(winit::event::KeyEvent),
     ModifiersChanged(winit::event::Modifiers),
@@ -39,33 +50,46 @@ pub enum Event {
     Render,
 }
 
-/// This makes winit fun to use again for simple single-window applications
-pub fn run_window_app<T: Application>() {
+/// This makes winit fun to use again for simple single-window applications. Without an
+/// `initial_size` the window system picks one. Returns the application once the window is closed.
+pub fn run_window_app<T: Application>(
+    config: T::Config,
+    initial_size: Option<(u32, u32)>,
+) -> Option<T> {
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
 
-    let mut wtf = Wtf::<T>::new();
+    let mut wtf = Wtf::<T>::new(config, initial_size);
 
-    event_loop.run_app(&mut wtf).unwrap()
+    event_loop.run_app(&mut wtf).unwrap();
+    wtf.window_state.map(|(_, state)| state)
 }
 
-struct Wtf<T> {
+struct Wtf<T: Application> {
+    config: T::Config,
+    initial_size: Option<(u32, u32)>,
     window_state: Option<(Arc<Window>, T)>,
 }
-impl<T> Wtf<T> {
-    pub fn new() -> Self {
-        Self { window_state: None }
+impl<T: Application> Wtf<T> {
+    pub fn new(config: T::Config, initial_size: Option<(u32, u32)>) -> Self {
+        Self {
+            config,
+            initial_size,
+            window_state: None,
+        }
     }
 }
 
 impl<T: Application> ApplicationHandler for Wtf<T> {
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
-        let window = event_loop
-            .create_window(WindowAttributes::default())
-            .unwrap();
+        let mut attributes = WindowAttributes::default();
+        if let Some((width, height)) = self.initial_size {
+            attributes = attributes.with_inner_size(PhysicalSize::new(width, height));
+        }
+        let window = event_loop.create_window(attributes).unwrap();
         let window = Arc::new(window);
 
-        let state = T::new(window.clone(), window.inner_size().into());
+        let state = T::new(self.config.clone(), window.clone(), window.inner_size().into());
 
         self.window_state = Some((window, state));
 
@@ -93,7 +117,11 @@ impl<T: Application> ApplicationHandler for Wtf<T> {
             }
             WindowEvent::RedrawRequested => {
                 state.handle_event(Event::Render);
-                window.request_redraw();
+                if state.should_exit() {
+                    event_loop.exit();
+                } else {
+                    window.request_redraw();
+                }
                 return;
             }
             WindowEvent::Resized(physical_size) => Event::Resize {
@@ -0,0 +1,184 @@
+use std::{collections::BTreeMap, num::NonZeroU32};
+
+use common::{
+    HasWindowAndDisplayHandle, Image, Mesh, MeshHandle, RendererInfo, RendererSettings,
+    VertexAttributes,
+};
+use raster::Framebuffer;
+use target::RenderTarget;
+
+mod raster;
+mod target;
+
+/// Same as in the GPU backends
+const CLEAR_COLOR: [f32; 3] = [0.4, 0.9, 1.0];
+
+/// Rasterizes on the CPU, as a ground truth for the GPU backends that does not depend on any
+/// driver. Rendering is single-threaded and supports every sample count. Optional vertex attributes
+/// are ignored, so every mesh looks like it does with the position-only shader variant of the GPU
+/// backends. Benchmark runs strip them before loading, see [`RendererInfo::vertex_attributes`].
+struct SoftwareRenderer {
+    target: RenderTarget,
+    framebuffer: Framebuffer,
+    settings: RendererSettings,
+    /// Drawn in the order they were loaded in
+    meshes: BTreeMap<MeshHandle, MeshData>,
+    /// The id of the next loaded mesh
+    next_mesh: u64,
+    /// Set by [`common::Renderer::request_capture`] until the next frame was rendered
+    capture_requested: bool,
+    /// Taken by [`common::Renderer::capture`]
+    captured_frame: Option<Image>,
+}
+
+impl common::Renderer for SoftwareRenderer {
+    fn new(
+        window: impl HasWindowAndDisplayHandle + Send + Sync + 'static,
+        initial_window_size: (u32, u32),
+        settings: &RendererSettings,
+    ) -> Self {
+        Self::with_target(
+            RenderTarget::window(window, initial_window_size),
+            initial_window_size,
+            settings,
+        )
+    }
+
+    fn new_headless(size: (u32, u32), settings: &RendererSettings) -> Self {
+        Self::with_target(RenderTarget::Offscreen, size, settings)
+    }
+
+    fn render(&mut self, camera: common::Camera) {
+        let view_proj = {
+            let _phase = common::phase("camera_upload");
+            let aspect_ratio = self.framebuffer.width as f32 / self.framebuffer.height as f32;
+            let reverse_z = self.settings.depth.is_some_and(|depth| depth.reverse_z);
+            cgmath::Matrix4::from(camera.view_proj(aspect_ratio, reverse_z))
+        };
+
+        {
+            let _phase = common::phase("rasterize");
+            self.framebuffer.clear(CLEAR_COLOR);
+            for mesh in self.meshes.values() {
+                self.framebuffer
+                    .draw_triangles(view_proj, &mesh.positions, &mesh.indices);
+            }
+            self.framebuffer.resolve();
+        }
+
+        {
+            let _phase = common::phase("present");
+            self.target.present(&self.framebuffer);
+        }
+
+        if std::mem::take(&mut self.capture_requested) {
+            self.captured_frame = Some(Image::new(
+                self.framebuffer.width,
+                self.framebuffer.height,
+                self.framebuffer.data.clone(),
+            ));
+        }
+    }
+
+    fn resize(&mut self, size: (NonZeroU32, NonZeroU32), _scale_factor: f64) {
+        let size = (size.0.get(), size.1.get());
+        self.framebuffer = Framebuffer::new(size, self.settings.depth, self.settings.msaa);
+        self.target.resize(size);
+        self.captured_frame = None;
+    }
+
+    fn load_mesh(&mut self, mesh: Mesh) -> MeshHandle {
+        let handle = MeshHandle(self.next_mesh);
+        self.next_mesh += 1;
+
+        self.meshes.insert(handle, MeshData::new(mesh));
+        handle
+    }
+
+    fn unload_mesh(&mut self, handle: MeshHandle) {
+        self.meshes
+            .remove(&handle)
+            .unwrap_or_else(|| panic!("{handle:?} is not loaded"));
+    }
+
+    fn update_mesh(&mut self, handle: MeshHandle, mesh: Mesh) {
+        let data = self
+            .meshes
+            .get_mut(&handle)
+            .unwrap_or_else(|| panic!("{handle:?} is not loaded"));
+        *data = MeshData::new(mesh);
+    }
+
+    fn info(&self) -> RendererInfo {
+        RendererInfo {
+            backend: "software".to_owned(),
+            adapter: "CPU (single-threaded)".to_owned(),
+            driver: format!("software {}", env!("CARGO_PKG_VERSION")),
+            msaa: self.settings.msaa,
+            present_mode: self.target.present_mode(),
+            // Frames are rendered and presented synchronously
+            max_frame_latency: Some(1),
+            vertex_attributes: VertexAttributes::POSITION_ONLY,
+        }
+    }
+
+    /// The framebuffer is in CPU memory already
+    fn request_capture(&mut self) {
+        self.capture_requested = true;
+    }
+
+    fn capture(&mut self) -> Option<Image> {
+        self.captured_frame.take()
+    }
+}
+
+impl SoftwareRenderer {
+    fn with_target(target: RenderTarget, size: (u32, u32), settings: &RendererSettings) -> Self {
+        Self {
+            target,
+            framebuffer: Framebuffer::new(size, settings.depth, settings.msaa),
+            settings: settings.clone(),
+            meshes: BTreeMap::new(),
+            next_mesh: 0,
+            capture_requested: false,
+            captured_frame: None,
+        }
+    }
+}
+
+/// Only positions are kept, optional vertex attributes are ignored
+struct MeshData {
+    positions: Vec<[f32; 3]>,
+    indices: Vec<u32>,
+}
+
+impl MeshData {
+    fn new(mesh: Mesh) -> Self {
+        assert_eq!(mesh.indices.len() % 3, 0);
+
+        Self {
+            positions: mesh.vertices.into_iter().map(|vertex| vertex.xyz).collect(),
+            indices: mesh.indices,
+        }
+    }
+}
+
+fn main() {
+    common::run::<SoftwareRenderer>(common::Cli::from_env());
+}
+
+#[cfg(test)]
+mod tests {
+    use common::golden::{self, GoldenScene, Tolerance};
+
+    use super::SoftwareRenderer;
+
+    /// Runs without a GPU, unlike the tests of the other backends
+    #[test]
+    fn triangles_match_golden_image() {
+        golden::assert_matches_golden::<SoftwareRenderer>(
+            &GoldenScene::triangles(),
+            &Tolerance::default(),
+        );
+    }
+}
@@ -0,0 +1,353 @@
+//! Rasterizes triangles the way GPUs do: clipping in clip space, pixel centers at half-integer
+//! coordinates, the top-left fill rule and perspective-correct interpolation. With MSAA, coverage
+//! and depth are evaluated at the standard sample positions of Vulkan and Direct3D, while the
+//! fragment shader runs once per pixel at its center.
+
+use cgmath::{Matrix4, Vector3, Vector4};
+use common::{DepthFormat, DepthSettings, SampleCount};
+
+/// An 8-bit sRGB RGBA image, like the offscreen targets of the GPU backends
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    /// The resolved image, see [`Framebuffer::resolve`]
+    pub data: Vec<u8>,
+    samples: SampleCount,
+    /// The colors of all samples of each pixel with MSAA, `None` if `data` is rendered into
+    /// directly
+    multisampled: Option<Vec<u8>>,
+    /// `None` if depth testing is disabled
+    depth: Option<DepthBuffer>,
+}
+
+/// One depth per sample
+struct DepthBuffer {
+    settings: DepthSettings,
+    data: Vec<f32>,
+}
+
+/// A vertex after the vertex shader
+#[derive(Clone, Copy)]
+struct ClipVertex {
+    position: Vector4<f32>,
+    world_position: Vector3<f32>,
+}
+
+/// A vertex after the perspective divide and viewport transform
+#[derive(Clone, Copy)]
+struct ScreenVertex {
+    x: f32,
+    y: f32,
+    /// `1 / w`, for perspective-correct interpolation
+    inv_w: f32,
+    /// `z / w`, which is interpolated linearly in screen space
+    depth: f32,
+    /// The world position divided by `w`
+    world_position: Vector3<f32>,
+}
+
+impl Framebuffer {
+    pub fn new(size: (u32, u32), depth: Option<DepthSettings>, samples: SampleCount) -> Self {
+        let pixels = (size.0 * size.1) as usize;
+        let sample_slots = pixels * samples.get() as usize;
+        Self {
+            width: size.0,
+            height: size.1,
+            data: vec![0; pixels * 4],
+            samples,
+            multisampled: (samples != SampleCount::ONE).then(|| vec![0; sample_slots * 4]),
+            depth: depth.map(|settings| DepthBuffer {
+                settings,
+                data: vec![settings.clear_value(); sample_slots],
+            }),
+        }
+    }
+
+    /// Fills the whole framebuffer with a linear color and clears the depth buffer
+    pub fn clear(&mut self, color: [f32; 3]) {
+        let pixel = encode_color(color);
+        let colors = self.multisampled.as_mut().unwrap_or(&mut self.data);
+        for chunk in colors.chunks_exact_mut(4) {
+            chunk.copy_from_slice(&pixel);
+        }
+
+        if let Some(depth) = &mut self.depth {
+            depth.data.fill(depth.settings.clear_value());
+        }
+    }
+
+    /// Draws the triangles in order without culling, with the fragment shader of the
+    /// position-only variant of `shader.wgsl`
+    pub fn draw_triangles(
+        &mut self,
+        view_proj: Matrix4<f32>,
+        positions: &[[f32; 3]],
+        indices: &[u32],
+    ) {
+        for triangle in indices.chunks_exact(3) {
+            let vertices = [triangle[0], triangle[1], triangle[2]].map(|index| {
+                // The vertex shader
+                let [x, y, z] = positions[index as usize];
+                ClipVertex {
+                    position: view_proj * Vector4::new(x, y, z, 1.0),
+                    world_position: Vector3::new(x, y, z),
+                }
+            });
+
+            let polygon = clip_depth(&vertices);
+            let screen: Vec<ScreenVertex> = polygon
+                .iter()
+                .map(|vertex| self.to_screen(vertex))
+                .collect();
+
+            for i in 1..screen.len().saturating_sub(1) {
+                self.rasterize(screen[0], screen[i], screen[i + 1]);
+            }
+        }
+    }
+
+    fn to_screen(&self, vertex: &ClipVertex) -> ScreenVertex {
+        let inv_w = 1.0 / vertex.position.w;
+        ScreenVertex {
+            x: (vertex.position.x * inv_w * 0.5 + 0.5) * self.width as f32,
+            y: (0.5 - vertex.position.y * inv_w * 0.5) * self.height as f32,
+            inv_w,
+            depth: vertex.position.z * inv_w,
+            world_position: vertex.world_position * inv_w,
+        }
+    }
+
+    fn rasterize(&mut self, a: ScreenVertex, mut b: ScreenVertex, mut c: ScreenVertex) {
+        let mut area = edge(&a, &b, c.x, c.y);
+        if area == 0.0 || !area.is_finite() {
+            return;
+        }
+        // Nothing is culled, so both windings are brought into the same order
+        if area < 0.0 {
+            std::mem::swap(&mut b, &mut c);
+            area = -area;
+        }
+
+        let min_x = a.x.min(b.x).min(c.x).floor().max(0.0) as u32;
+        let min_y = a.y.min(b.y).min(c.y).floor().max(0.0) as u32;
+        let max_x = (a.x.max(b.x).max(c.x).ceil() as u32).min(self.width);
+        let max_y = (a.y.max(b.y).max(c.y).ceil() as u32).min(self.height);
+
+        let top_left = [
+            is_top_left(&b, &c),
+            is_top_left(&c, &a),
+            is_top_left(&a, &b),
+        ];
+
+        let weights_at = |px: f32, py: f32| {
+            [
+                edge(&b, &c, px, py),
+                edge(&c, &a, px, py),
+                edge(&a, &b, px, py),
+            ]
+        };
+        let positions = sample_positions(self.samples);
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let pixel = (y * self.width + x) as usize;
+
+                // Bit `i` is set if sample `i` is covered and passes the depth test
+                let mut written_samples = 0u8;
+                for (sample, &(sx, sy)) in positions.iter().enumerate() {
+                    let weights = weights_at(x as f32 + sx, y as f32 + sy);
+                    let covered = weights
+                        .iter()
+                        .zip(top_left)
+                        .all(|(&weight, top_left)| weight > 0.0 || (weight == 0.0 && top_left));
+                    if !covered {
+                        continue;
+                    }
+
+                    if let Some(depth) = &mut self.depth {
+                        let [wa, wb, wc] = weights.map(|weight| weight / area);
+                        let fragment_depth = quantize(
+                            depth.settings.format,
+                            wa * a.depth + wb * b.depth + wc * c.depth,
+                        );
+                        let slot = pixel * positions.len() + sample;
+                        if !depth
+                            .settings
+                            .compare
+                            .passes(fragment_depth, depth.data[slot])
+                        {
+                            continue;
+                        }
+                        depth.data[slot] = fragment_depth;
+                    }
+
+                    written_samples |= 1 << sample;
+                }
+                if written_samples == 0 {
+                    continue;
+                }
+
+                // Attributes are interpolated at the pixel center, even if it is not covered
+                let [wa, wb, wc] = weights_at(x as f32 + 0.5, y as f32 + 0.5)
+                    .map(|weight| weight / area);
+                let inv_w = wa * a.inv_w + wb * b.inv_w + wc * c.inv_w;
+                let world_position =
+                    (a.world_position * wa + b.world_position * wb + c.world_position * wc) / inv_w;
+
+                // The fragment shader
+                let color = [
+                    world_position.x.abs(),
+                    world_position.y.abs(),
+                    world_position.z.abs(),
+                ];
+
+                let encoded = encode_color(color);
+                match &mut self.multisampled {
+                    Some(colors) => {
+                        for sample in 0..positions.len() {
+                            if written_samples & (1 << sample) != 0 {
+                                let offset = (pixel * positions.len() + sample) * 4;
+                                colors[offset..offset + 4].copy_from_slice(&encoded);
+                            }
+                        }
+                    }
+                    None => {
+                        let offset = pixel * 4;
+                        self.data[offset..offset + 4].copy_from_slice(&encoded);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Averages the samples of each pixel into `data`. Like resolves of sRGB targets on GPUs, the
+    /// average is taken of the linear colors.
+    pub fn resolve(&mut self) {
+        let Some(colors) = &self.multisampled else {
+            return;
+        };
+
+        let decoded: Vec<f32> = (0..=255).map(decode_channel).collect();
+        let samples = self.samples.get() as usize;
+        for (pixel, pixel_samples) in self
+            .data
+            .chunks_exact_mut(4)
+            .zip(colors.chunks_exact(samples * 4))
+        {
+            let mut sum = [0.0; 3];
+            for sample in pixel_samples.chunks_exact(4) {
+                for (channel, &value) in sum.iter_mut().zip(sample) {
+                    *channel += decoded[value as usize];
+                }
+            }
+            pixel.copy_from_slice(&encode_color(sum.map(|channel| channel / samples as f32)));
+        }
+    }
+}
+
+/// The standard sample positions within a pixel, with the origin in its top left corner
+fn sample_positions(samples: SampleCount) -> &'static [(f32, f32)] {
+    match samples.get() {
+        1 => &[(0.5, 0.5)],
+        2 => &[(0.75, 0.75), (0.25, 0.25)],
+        4 => &[(0.375, 0.125), (0.875, 0.375), (0.125, 0.625), (0.625, 0.875)],
+        8 => &[
+            (0.5625, 0.3125),
+            (0.4375, 0.6875),
+            (0.8125, 0.5625),
+            (0.3125, 0.1875),
+            (0.1875, 0.8125),
+            (0.0625, 0.4375),
+            (0.6875, 0.9375),
+            (0.9375, 0.0625),
+        ],
+        count => unreachable!("invalid sample count {count}"),
+    }
+}
+
+/// Twice the signed area of the triangle `a`, `b`, `p`. Positive if `p` is to the right of the
+/// edge from `a` to `b` on the screen, where y points down.
+fn edge(a: &ScreenVertex, b: &ScreenVertex, px: f32, py: f32) -> f32 {
+    (b.x - a.x) * (py - a.y) - (b.y - a.y) * (px - a.x)
+}
+
+/// Pixel centers exactly on a top or left edge belong to the triangle, others don't. This way
+/// pixels on edges shared by two triangles are drawn exactly once.
+fn is_top_left(a: &ScreenVertex, b: &ScreenVertex) -> bool {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    dy < 0.0 || (dy == 0.0 && dx > 0.0)
+}
+
+/// Clips a triangle against the near and far planes, `0 <= z <= w`. Clipping against the other
+/// planes is not needed, as the rasterized area is limited to the framebuffer anyway.
+fn clip_depth(triangle: &[ClipVertex; 3]) -> Vec<ClipVertex> {
+    let near = |vertex: &ClipVertex| vertex.position.z;
+    let far = |vertex: &ClipVertex| vertex.position.w - vertex.position.z;
+
+    let polygon = clip_polygon(triangle.to_vec(), near);
+    clip_polygon(polygon, far)
+}
+
+/// Sutherland-Hodgman clipping against the plane where `distance` is zero
+fn clip_polygon(
+    polygon: Vec<ClipVertex>,
+    distance: impl Fn(&ClipVertex) -> f32,
+) -> Vec<ClipVertex> {
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+
+    for (i, current) in polygon.iter().enumerate() {
+        let next = &polygon[(i + 1) % polygon.len()];
+        let (current_distance, next_distance) = (distance(current), distance(next));
+
+        if current_distance >= 0.0 {
+            clipped.push(*current);
+        }
+        if (current_distance >= 0.0) != (next_distance >= 0.0) {
+            let t = current_distance / (current_distance - next_distance);
+            clipped.push(ClipVertex {
+                position: current.position + (next.position - current.position) * t,
+                world_position: current.world_position
+                    + (next.world_position - current.world_position) * t,
+            });
+        }
+    }
+
+    clipped
+}
+
+/// Rounds a depth to what a depth buffer of `format` can store, for the same depth test results
+/// as on GPUs
+fn quantize(format: DepthFormat, depth: f32) -> f32 {
+    let bits = match format {
+        DepthFormat::Depth16Unorm => 16,
+        DepthFormat::Depth24Plus => 24,
+        DepthFormat::Depth32Float => return depth,
+    };
+    let max = ((1u32 << bits) - 1) as f32;
+    (depth.clamp(0.0, 1.0) * max).round() / max
+}
+
+/// The inverse of [`encode_color`] for a single channel
+fn decode_channel(value: u8) -> f32 {
+    let channel = value as f32 / 255.0;
+    if channel <= 0.040_45 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Stores a linear color like an `Rgba8UnormSrgb` render target does
+fn encode_color(color: [f32; 3]) -> [u8; 4] {
+    let [r, g, b] = color.map(|channel| {
+        let channel = channel.clamp(0.0, 1.0);
+        let encoded = if channel <= 0.003_130_8 {
+            channel * 12.92
+        } else {
+            1.055 * channel.powf(1.0 / 2.4) - 0.055
+        };
+        (encoded * 255.0).round() as u8
+    });
+
+    [r, g, b, 255]
+}
@@ -0,0 +1,66 @@
+//! Where frames are presented to: either a window through softbuffer or nowhere at all.
+
+use std::{num::NonZeroU32, sync::Arc};
+
+use common::{HasWindowAndDisplayHandle, PresentMode};
+use softbuffer::{Context, Surface};
+
+use crate::raster::Framebuffer;
+
+type Window = Arc<dyn HasWindowAndDisplayHandle + Send + Sync>;
+
+pub enum RenderTarget {
+    Window(Surface<Window, Window>),
+    /// The framebuffer is only read back with [`common::Renderer::capture`]
+    Offscreen,
+}
+
+impl RenderTarget {
+    pub fn window(
+        window: impl HasWindowAndDisplayHandle + Send + Sync + 'static,
+        size: (u32, u32),
+    ) -> Self {
+        let window: Window = Arc::new(window);
+        let context = Context::new(window.clone()).unwrap();
+        let mut surface = Surface::new(&context, window).unwrap();
+        resize_surface(&mut surface, size);
+
+        Self::Window(surface)
+    }
+
+    pub fn resize(&mut self, size: (u32, u32)) {
+        if let Self::Window(surface) = self {
+            resize_surface(surface, size);
+        }
+    }
+
+    /// softbuffer shows frames as soon as they are presented, without waiting for vertical blanks,
+    /// so the requested present mode is ignored
+    pub fn present_mode(&self) -> Option<PresentMode> {
+        match self {
+            Self::Window(_) => Some(PresentMode::Immediate),
+            Self::Offscreen => None,
+        }
+    }
+
+    /// Copies the framebuffer into the window, which must have the same size
+    pub fn present(&mut self, framebuffer: &Framebuffer) {
+        let Self::Window(surface) = self else {
+            return;
+        };
+
+        let mut buffer = surface.buffer_mut().unwrap();
+        for (pixel, rgba) in buffer.iter_mut().zip(framebuffer.data.chunks_exact(4)) {
+            // softbuffer expects 0RGB
+            *pixel = u32::from_be_bytes([0, rgba[0], rgba[1], rgba[2]]);
+        }
+        buffer.present().unwrap();
+    }
+}
+
+fn resize_surface(surface: &mut Surface<Window, Window>, size: (u32, u32)) {
+    let (Some(width), Some(height)) = (NonZeroU32::new(size.0), NonZeroU32::new(size.1)) else {
+        return;
+    };
+    surface.resize(width, height).unwrap();
+}
@@ -0,0 +1,187 @@
+//! The attachments that are rendered to together with the target image: the depth image and, with
+//! MSAA, the multisampled color image that is resolved into the target image.
+
+use ash::vk;
+use common::{RendererSettings, SampleCount};
+
+use crate::{
+    context::{Device, Instance},
+    depth,
+    swapchain::COLOR_SUBRESOURCE_RANGE,
+};
+
+const DEPTH_SUBRESOURCE_RANGE: vk::ImageSubresourceRange = vk::ImageSubresourceRange {
+    aspect_mask: vk::ImageAspectFlags::DEPTH,
+    base_mip_level: 0,
+    level_count: 1,
+    base_array_layer: 0,
+    layer_count: 1,
+};
+
+/// What the render pass, the pipeline and the framebuffers have to agree on, besides the format
+/// of the target image
+#[derive(Clone, Copy, Debug)]
+pub struct AttachmentConfig {
+    /// `None` if depth testing is disabled
+    pub depth_format: Option<vk::Format>,
+    /// Negotiated from the requested sample count. Without MSAA the target image is rendered into
+    /// directly.
+    pub sample_count: SampleCount,
+}
+
+impl AttachmentConfig {
+    pub fn new(instance: &Instance, device: &Device, settings: &RendererSettings) -> Self {
+        let depth_format = settings
+            .depth
+            .map(|depth| depth::choose_format(instance, device, depth.format));
+
+        let limits = &device.properties.limits;
+        let mut supported = limits.framebuffer_color_sample_counts;
+        if depth_format.is_some() {
+            supported &= limits.framebuffer_depth_sample_counts;
+        }
+        let sample_count = settings
+            .msaa
+            .negotiate(|sample_count| supported.contains(samples(sample_count)));
+
+        Self {
+            depth_format,
+            sample_count,
+        }
+    }
+
+    pub fn samples(&self) -> vk::SampleCountFlags {
+        samples(self.sample_count)
+    }
+
+    pub fn is_multisampled(&self) -> bool {
+        self.sample_count != SampleCount::ONE
+    }
+}
+
+/// The attachments of a render target. They are shared by all of its images, the render pass
+/// dependency orders the writes of consecutive frames.
+pub struct Attachments {
+    pub config: AttachmentConfig,
+    /// `Some` with MSAA
+    color: Option<AttachmentImage>,
+    depth: Option<AttachmentImage>,
+}
+
+impl Attachments {
+    pub fn new(
+        device: &Device,
+        config: AttachmentConfig,
+        color_format: vk::Format,
+        extent: vk::Extent2D,
+    ) -> Self {
+        let color = config.is_multisampled().then(|| {
+            AttachmentImage::new(
+                device,
+                color_format,
+                config.samples(),
+                extent,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+                COLOR_SUBRESOURCE_RANGE,
+            )
+        });
+        let depth = config.depth_format.map(|depth_format| {
+            AttachmentImage::new(
+                device,
+                depth_format,
+                config.samples(),
+                extent,
+                vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                DEPTH_SUBRESOURCE_RANGE,
+            )
+        });
+
+        Self {
+            config,
+            color,
+            depth,
+        }
+    }
+
+    /// The attachments of a framebuffer that renders into `target`, in the order of the render
+    /// pass attachments
+    pub fn framebuffer_views(&self, target: vk::ImageView) -> Vec<vk::ImageView> {
+        [Some(target)]
+            .into_iter()
+            .chain([&self.color, &self.depth].map(|image| image.as_ref().map(|image| image.view)))
+            .flatten()
+            .collect()
+    }
+
+    pub unsafe fn destroy(&self, device: &Device) {
+        for image in [&self.color, &self.depth].into_iter().flatten() {
+            image.destroy(device);
+        }
+    }
+}
+
+/// A device-local image that is only accessed within the render pass
+struct AttachmentImage {
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+}
+
+impl AttachmentImage {
+    fn new(
+        device: &Device,
+        format: vk::Format,
+        samples: vk::SampleCountFlags,
+        extent: vk::Extent2D,
+        usage: vk::ImageUsageFlags,
+        subresource_range: vk::ImageSubresourceRange,
+    ) -> Self {
+        let create_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(format)
+            .extent(extent.into())
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(samples)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+        let image = unsafe { device.device.create_image(&create_info, None) }.unwrap();
+
+        let requirements = unsafe { device.device.get_image_memory_requirements(image) };
+        let allocate_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(requirements.size)
+            .memory_type_index(device.find_memory_type(
+                requirements.memory_type_bits,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            ));
+        let memory = unsafe { device.device.allocate_memory(&allocate_info, None) }.unwrap();
+
+        unsafe { device.device.bind_image_memory(image, memory, 0) }.unwrap();
+
+        let view_info = vk::ImageViewCreateInfo::default()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(format)
+            .subresource_range(subresource_range);
+        let view = unsafe { device.device.create_image_view(&view_info, None) }.unwrap();
+
+        Self {
+            image,
+            memory,
+            view,
+        }
+    }
+
+    unsafe fn destroy(&self, device: &Device) {
+        device.device.destroy_image_view(self.view, None);
+        device.device.destroy_image(self.image, None);
+        device.device.free_memory(self.memory, None);
+    }
+}
+
+/// The bits of the sample count flags are the counts themselves
+fn samples(sample_count: SampleCount) -> vk::SampleCountFlags {
+    vk::SampleCountFlags::from_raw(sample_count.get())
+}
@@ -0,0 +1,259 @@
+//! Meshes are grouped by their vertex attributes, as each attribute set needs its own vertex
+//! input layout and shader variant. Same as the batches of the wgpu backend.
+
+use std::{collections::BTreeMap, ops::Range};
+
+use ash::vk;
+use common::{BufferStats, DepthSettings, Mesh, MeshHandle, RangeAllocator, VertexAttributes};
+
+use crate::{
+    buffer::{self, DeviceBuffer, Upload},
+    context::Device,
+    pipeline::{self, PipelineLayout},
+    vertex,
+};
+
+/// Capacities of new batches before they grow, same as in the wgpu backend
+const INITIAL_VERTICES: u64 = 1024;
+const INITIAL_INDICES: u64 = 3 * 1024;
+
+const INDEX_SIZE: u64 = std::mem::size_of::<u32>() as u64;
+
+/// All meshes with the same vertex attributes. The meshes share one buffer per attribute and an
+/// index buffer, in which the ranges of unloaded meshes are reused.
+pub struct Batch {
+    pub attributes: VertexAttributes,
+    pipeline: vk::Pipeline,
+    /// One per attribute, in the order of [`VertexAttributes::iter`]
+    vertex_buffers: Vec<DeviceBuffer>,
+    vertex_allocator: RangeAllocator,
+    index_buffer: DeviceBuffer,
+    index_allocator: RangeAllocator,
+    /// Drawn in the order they were loaded in
+    meshes: BTreeMap<MeshHandle, MeshRanges>,
+}
+
+/// Where a mesh is stored, in vertices and indices
+pub struct MeshRanges {
+    vertices: Range<u64>,
+    indices: Range<u64>,
+}
+
+impl Batch {
+    pub fn new(
+        device: &Device,
+        render_pass: vk::RenderPass,
+        layout: &PipelineLayout,
+        depth: Option<DepthSettings>,
+        samples: vk::SampleCountFlags,
+        attributes: VertexAttributes,
+    ) -> Self {
+        let vertex_buffers = attributes
+            .iter()
+            .map(|attribute| {
+                DeviceBuffer::new(
+                    device,
+                    INITIAL_VERTICES * vertex::size(attribute),
+                    vk::BufferUsageFlags::VERTEX_BUFFER,
+                )
+            })
+            .collect();
+
+        let index_buffer = DeviceBuffer::new(
+            device,
+            INITIAL_INDICES * INDEX_SIZE,
+            vk::BufferUsageFlags::INDEX_BUFFER,
+        );
+
+        Self {
+            attributes,
+            pipeline: pipeline::create_pipeline(
+                device,
+                render_pass,
+                layout,
+                depth,
+                samples,
+                attributes,
+            ),
+            vertex_buffers,
+            vertex_allocator: RangeAllocator::new(INITIAL_VERTICES),
+            index_buffer,
+            index_allocator: RangeAllocator::new(INITIAL_INDICES),
+            meshes: BTreeMap::new(),
+        }
+    }
+
+    pub fn contains(&self, handle: MeshHandle) -> bool {
+        self.meshes.contains_key(&handle)
+    }
+
+    /// Whether the mesh fits into free ranges, without growing the buffers
+    pub fn fits(&self, mesh: &Mesh) -> bool {
+        self.vertex_allocator.largest_free_range() >= mesh.vertices.len() as u64
+            && self.index_allocator.largest_free_range() >= mesh.indices.len() as u64
+    }
+
+    /// The mesh must have exactly the attributes of the batch. If it doesn't [fit](Self::fits),
+    /// the buffers grow, so the GPU must not be using them. Waits until the mesh is uploaded.
+    pub fn insert(
+        &mut self,
+        device: &Device,
+        command_pool: vk::CommandPool,
+        handle: MeshHandle,
+        mesh: &Mesh,
+    ) {
+        assert_eq!(mesh.attributes(), self.attributes);
+        assert_eq!(mesh.indices.len() % 3, 0);
+
+        let (vertex_count, index_count) = (mesh.vertices.len() as u64, mesh.indices.len() as u64);
+        self.reserve(device, command_pool, vertex_count, index_count);
+        let vertices = self.vertex_allocator.allocate(vertex_count).unwrap();
+        let indices = self.index_allocator.allocate(index_count).unwrap();
+
+        let attribute_data: Vec<_> = self
+            .attributes
+            .iter()
+            .map(|attribute| {
+                let data = mesh.attribute_data(attribute).unwrap();
+                assert_eq!(
+                    data.len(),
+                    mesh.vertices.len() * attribute.components(),
+                    "{attribute:?} needs one entry per vertex"
+                );
+                data
+            })
+            .collect();
+
+        let mut uploads: Vec<_> = self
+            .attributes
+            .iter()
+            .zip(&self.vertex_buffers)
+            .zip(&attribute_data)
+            .map(|((attribute, buffer), data)| Upload {
+                buffer,
+                offset: vertices.start * vertex::size(attribute),
+                data: bytemuck::cast_slice(data),
+            })
+            .collect();
+        // Indices stay relative to the mesh, the draw call offsets them
+        uploads.push(Upload {
+            buffer: &self.index_buffer,
+            offset: indices.start * INDEX_SIZE,
+            data: bytemuck::cast_slice(&mesh.indices),
+        });
+        // Frames in flight don't read free ranges, so they don't have to finish first
+        buffer::upload(device, command_pool, &uploads);
+
+        self.meshes.insert(handle, MeshRanges { vertices, indices });
+    }
+
+    /// Stops drawing the mesh, panics if it is not in this batch. Its ranges stay allocated until
+    /// they are passed to [`Self::free`].
+    pub fn remove(&mut self, handle: MeshHandle) -> MeshRanges {
+        self.meshes
+            .remove(&handle)
+            .expect("mesh is not in this batch")
+    }
+
+    /// Frees the ranges of a removed mesh for later meshes. Frames in flight must not draw the
+    /// mesh anymore.
+    pub fn free(&mut self, ranges: MeshRanges) {
+        self.vertex_allocator.free(ranges.vertices);
+        self.index_allocator.free(ranges.indices);
+    }
+
+    pub fn buffer_stats(&self) -> [BufferStats; 2] {
+        [
+            BufferStats::of(
+                format!("{} vertices", self.attributes),
+                &self.vertex_allocator,
+            ),
+            BufferStats::of(
+                format!("{} indices", self.attributes),
+                &self.index_allocator,
+            ),
+        ]
+    }
+
+    /// The viewport, scissor and camera descriptor set have to be set already
+    pub unsafe fn record_draws(&self, device: &Device, command_buffer: vk::CommandBuffer) {
+        if self.meshes.is_empty() {
+            return;
+        }
+
+        let device = &device.device;
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline,
+        );
+
+        let buffers: Vec<_> = self
+            .vertex_buffers
+            .iter()
+            .map(|buffer| buffer.buffer)
+            .collect();
+        device.cmd_bind_vertex_buffers(command_buffer, 0, &buffers, &vec![0; buffers.len()]);
+        device.cmd_bind_index_buffer(
+            command_buffer,
+            self.index_buffer.buffer,
+            0,
+            vk::IndexType::UINT32,
+        );
+
+        for ranges in self.meshes.values() {
+            if ranges.indices.is_empty() {
+                continue;
+            }
+
+            device.cmd_draw_indexed(
+                command_buffer,
+                (ranges.indices.end - ranges.indices.start) as u32,
+                1,
+                ranges.indices.start as u32,
+                ranges.vertices.start as i32,
+                0,
+            );
+        }
+    }
+
+    /// Grows the buffers unless the vertices and indices fit into free ranges already. The
+    /// buffers must not be in use by the GPU.
+    fn reserve(
+        &mut self,
+        device: &Device,
+        command_pool: vk::CommandPool,
+        vertex_count: u64,
+        index_count: u64,
+    ) {
+        if self.vertex_allocator.largest_free_range() < vertex_count {
+            let capacity = grown_capacity(&self.vertex_allocator, vertex_count);
+            for (attribute, buffer) in self.attributes.iter().zip(&mut self.vertex_buffers) {
+                buffer.reserve(device, command_pool, capacity * vertex::size(attribute));
+            }
+            self.vertex_allocator.grow(capacity);
+        }
+
+        if self.index_allocator.largest_free_range() < index_count {
+            let capacity = grown_capacity(&self.index_allocator, index_count);
+            self.index_buffer
+                .reserve(device, command_pool, capacity * INDEX_SIZE);
+            self.index_allocator.grow(capacity);
+        }
+    }
+
+    pub unsafe fn destroy(&self, device: &Device) {
+        device.device.destroy_pipeline(self.pipeline, None);
+        for buffer in &self.vertex_buffers {
+            buffer.destroy(device);
+        }
+        self.index_buffer.destroy(device);
+    }
+}
+
+/// At least doubles the capacity like the wgpu backend. The added space alone fits `len`, so the
+/// allocation succeeds regardless of fragmentation.
+fn grown_capacity(allocator: &RangeAllocator, len: u64) -> u64 {
+    let capacity = allocator.capacity();
+    (capacity * 2).max(capacity + len)
+}
@@ -0,0 +1,214 @@
+use std::ffi::c_void;
+
+use ash::vk;
+
+use crate::context::Device;
+
+/// A buffer in host-visible, coherent memory that stays mapped for its whole lifetime. Used for
+/// data the CPU writes every frame or reads back, everything else is in a [`DeviceBuffer`].
+pub struct HostBuffer {
+    pub buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    mapped: *mut c_void,
+    size: u64,
+}
+
+impl HostBuffer {
+    pub fn new(device: &Device, size: u64, usage: vk::BufferUsageFlags) -> Self {
+        let (buffer, memory) = create_buffer(
+            device,
+            size,
+            usage,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+
+        let mapped = unsafe {
+            device
+                .device
+                .map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+        }
+        .unwrap();
+
+        Self {
+            buffer,
+            memory,
+            mapped,
+            size,
+        }
+    }
+
+    /// The written range must not be in use by the GPU
+    pub fn write(&mut self, offset: u64, data: &[u8]) {
+        assert!(
+            offset + data.len() as u64 <= self.size,
+            "write out of bounds"
+        );
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                data.as_ptr(),
+                self.mapped.cast::<u8>().add(offset as usize),
+                data.len(),
+            );
+        }
+    }
+
+    /// The buffer must not be written to by the GPU while the contents are borrowed
+    pub fn contents(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.mapped.cast::<u8>(), self.size as usize) }
+    }
+
+    pub unsafe fn destroy(&self, device: &Device) {
+        device.device.unmap_memory(self.memory);
+        device.device.destroy_buffer(self.buffer, None);
+        device.device.free_memory(self.memory, None);
+    }
+}
+
+/// A buffer in device-local memory, which the CPU can't access. It is written with [`upload`].
+pub struct DeviceBuffer {
+    pub buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    size: u64,
+    usage: vk::BufferUsageFlags,
+}
+
+impl DeviceBuffer {
+    /// The buffer can also be copied from and to, besides `usage`
+    pub fn new(device: &Device, size: u64, usage: vk::BufferUsageFlags) -> Self {
+        let (buffer, memory) = create_buffer(
+            device,
+            size,
+            usage | vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+
+        Self {
+            buffer,
+            memory,
+            size,
+            usage,
+        }
+    }
+
+    /// Makes room for at least `size` bytes, at least doubling the size of the buffer. The
+    /// contents are copied into the new buffer on the GPU, so the buffer must not be in use.
+    pub fn reserve(&mut self, device: &Device, command_pool: vk::CommandPool, size: u64) {
+        if size <= self.size {
+            return;
+        }
+
+        let grown = Self::new(device, size.max(self.size * 2), self.usage);
+        device.submit_and_wait(command_pool, |command_buffer| unsafe {
+            let region = vk::BufferCopy::default().size(self.size);
+            device
+                .device
+                .cmd_copy_buffer(command_buffer, self.buffer, grown.buffer, &[region]);
+            record_transfer_barrier(device, command_buffer);
+        });
+        unsafe { self.destroy(device) };
+        *self = grown;
+    }
+
+    pub unsafe fn destroy(&self, device: &Device) {
+        device.device.destroy_buffer(self.buffer, None);
+        device.device.free_memory(self.memory, None);
+    }
+}
+
+/// A write of `data` at a byte offset into a device-local buffer
+pub struct Upload<'a> {
+    pub buffer: &'a DeviceBuffer,
+    pub offset: u64,
+    pub data: &'a [u8],
+}
+
+/// Copies all uploads through one staging buffer and waits until they are finished. The written
+/// ranges must not be in use by the GPU.
+pub fn upload(device: &Device, command_pool: vk::CommandPool, uploads: &[Upload]) {
+    let uploads: Vec<_> = uploads
+        .iter()
+        .filter(|upload| !upload.data.is_empty())
+        .collect();
+    let size: u64 = uploads.iter().map(|upload| upload.data.len() as u64).sum();
+    if size == 0 {
+        return;
+    }
+
+    let mut staging = HostBuffer::new(device, size, vk::BufferUsageFlags::TRANSFER_SRC);
+    let mut staging_offset = 0;
+    let mut copies = Vec::new();
+    for upload in uploads {
+        let len = upload.data.len() as u64;
+        assert!(
+            upload.offset + len <= upload.buffer.size,
+            "upload out of bounds"
+        );
+
+        staging.write(staging_offset, upload.data);
+        copies.push((
+            upload.buffer.buffer,
+            vk::BufferCopy {
+                src_offset: staging_offset,
+                dst_offset: upload.offset,
+                size: len,
+            },
+        ));
+        staging_offset += len;
+    }
+
+    device.submit_and_wait(command_pool, |command_buffer| unsafe {
+        for (buffer, region) in copies {
+            device
+                .device
+                .cmd_copy_buffer(command_buffer, staging.buffer, buffer, &[region]);
+        }
+        record_transfer_barrier(device, command_buffer);
+    });
+
+    unsafe { staging.destroy(device) };
+}
+
+/// Makes the copies visible to the vertex input of later frames and to later copies
+unsafe fn record_transfer_barrier(device: &Device, command_buffer: vk::CommandBuffer) {
+    let barrier = vk::MemoryBarrier::default()
+        .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .dst_access_mask(
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ
+                | vk::AccessFlags::INDEX_READ
+                | vk::AccessFlags::TRANSFER_READ
+                | vk::AccessFlags::TRANSFER_WRITE,
+        );
+    device.device.cmd_pipeline_barrier(
+        command_buffer,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::PipelineStageFlags::VERTEX_INPUT | vk::PipelineStageFlags::TRANSFER,
+        vk::DependencyFlags::empty(),
+        &[barrier],
+        &[],
+        &[],
+    );
+}
+
+fn create_buffer(
+    device: &Device,
+    size: u64,
+    usage: vk::BufferUsageFlags,
+    memory_flags: vk::MemoryPropertyFlags,
+) -> (vk::Buffer, vk::DeviceMemory) {
+    let create_info = vk::BufferCreateInfo::default()
+        .size(size)
+        .usage(usage)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE);
+    let buffer = unsafe { device.device.create_buffer(&create_info, None) }.unwrap();
+
+    let requirements = unsafe { device.device.get_buffer_memory_requirements(buffer) };
+    let allocate_info = vk::MemoryAllocateInfo::default()
+        .allocation_size(requirements.size)
+        .memory_type_index(device.find_memory_type(requirements.memory_type_bits, memory_flags));
+    let memory = unsafe { device.device.allocate_memory(&allocate_info, None) }.unwrap();
+
+    unsafe { device.device.bind_buffer_memory(buffer, memory, 0) }.unwrap();
+
+    (buffer, memory)
+}
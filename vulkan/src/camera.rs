@@ -0,0 +1,16 @@
+use bytemuck::{Pod, Zeroable};
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+pub struct CameraRaw {
+    view_proj: [[f32; 4]; 4],
+}
+
+impl CameraRaw {
+    /// With `reverse_z`, the near plane is mapped to depth 1 and the far plane to 0
+    pub fn new(camera: &common::Camera, aspect_ratio: f32, reverse_z: bool) -> Self {
+        Self {
+            view_proj: camera.view_proj(aspect_ratio, reverse_z),
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! Reads rendered images back into CPU memory.
+
+use ash::vk;
+use common::Image;
+
+use crate::{buffer::HostBuffer, context::Device, swapchain::COLOR_SUBRESOURCE_RANGE};
+
+/// An image that holds the last frame in `TRANSFER_SRC_OPTIMAL` layout, either the offscreen
+/// target itself or the [`LastFrame`] of a swapchain
+#[derive(Clone, Copy)]
+pub struct FrameImage {
+    pub image: vk::Image,
+    pub format: vk::Format,
+    pub extent: vk::Extent2D,
+}
+
+/// A copy of the last frame rendered into a swapchain, as swapchain images can't be read anymore
+/// once they were presented
+pub struct LastFrame {
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    format: vk::Format,
+    extent: vk::Extent2D,
+}
+
+impl LastFrame {
+    pub fn new(device: &Device, format: vk::Format, extent: vk::Extent2D) -> Self {
+        let create_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(format)
+            .extent(extent.into())
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::TRANSFER_SRC)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+        let image = unsafe { device.device.create_image(&create_info, None) }.unwrap();
+
+        let requirements = unsafe { device.device.get_image_memory_requirements(image) };
+        let allocate_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(requirements.size)
+            .memory_type_index(device.find_memory_type(
+                requirements.memory_type_bits,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            ));
+        let memory = unsafe { device.device.allocate_memory(&allocate_info, None) }.unwrap();
+
+        unsafe { device.device.bind_image_memory(image, memory, 0) }.unwrap();
+
+        Self {
+            image,
+            memory,
+            format,
+            extent,
+        }
+    }
+
+    pub fn frame_image(&self) -> FrameImage {
+        FrameImage {
+            image: self.image,
+            format: self.format,
+            extent: self.extent,
+        }
+    }
+
+    /// Records a copy of `swapchain_image`, which was just rendered to and is in
+    /// `PRESENT_SRC_KHR` layout, and leaves it in that layout again
+    pub unsafe fn record_copy_from(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        swapchain_image: vk::Image,
+    ) {
+        // The previous frame might still be read back from the copy
+        let before = [
+            image_barrier(
+                swapchain_image,
+                vk::ImageLayout::PRESENT_SRC_KHR,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            )
+            .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_READ),
+            image_barrier(
+                self.image,
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            )
+            .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE),
+        ];
+        device.device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &before,
+        );
+
+        let subresource = vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let region = vk::ImageCopy::default()
+            .src_subresource(subresource)
+            .dst_subresource(subresource)
+            .extent(self.extent.into());
+        device.device.cmd_copy_image(
+            command_buffer,
+            swapchain_image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            self.image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &[region],
+        );
+
+        // Presenting waits for a semaphore, which makes the swapchain image available already
+        let after = [
+            image_barrier(
+                swapchain_image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::ImageLayout::PRESENT_SRC_KHR,
+            ),
+            image_barrier(
+                self.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            )
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_READ),
+        ];
+        device.device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::TRANSFER | vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &after,
+        );
+    }
+
+    pub unsafe fn destroy(&self, device: &Device) {
+        device.device.destroy_image(self.image, None);
+        device.device.free_memory(self.memory, None);
+    }
+}
+
+/// A buffer the image can be copied into with [`record_copy`]
+pub fn create_readback_buffer(device: &Device, image: FrameImage) -> HostBuffer {
+    let size = image.extent.width * image.extent.height * Image::BYTES_PER_PIXEL;
+    HostBuffer::new(device, size as u64, vk::BufferUsageFlags::TRANSFER_DST)
+}
+
+/// Records a copy of the image, which was last written by a render pass or a copy, into
+/// `buffer`. Unlike wgpu, Vulkan does not require the rows in the buffer to be aligned.
+pub unsafe fn record_copy(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    image: FrameImage,
+    buffer: &HostBuffer,
+) {
+    let image_barrier = image_barrier(
+        image.image,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+    )
+    .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE | vk::AccessFlags::TRANSFER_WRITE)
+    .dst_access_mask(vk::AccessFlags::TRANSFER_READ);
+    device.device.cmd_pipeline_barrier(
+        command_buffer,
+        vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::TRANSFER,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        &[image_barrier],
+    );
+
+    let region = vk::BufferImageCopy::default()
+        .image_subresource(vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count: 1,
+        })
+        .image_extent(image.extent.into());
+    device.device.cmd_copy_image_to_buffer(
+        command_buffer,
+        image.image,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        buffer.buffer,
+        &[region],
+    );
+
+    let buffer_barrier = vk::BufferMemoryBarrier::default()
+        .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .dst_access_mask(vk::AccessFlags::HOST_READ)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .buffer(buffer.buffer)
+        .size(vk::WHOLE_SIZE);
+    device.device.cmd_pipeline_barrier(
+        command_buffer,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::PipelineStageFlags::HOST,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[buffer_barrier],
+        &[],
+    );
+}
+
+/// Converts the copied pixels to RGBA. Only 8-bit RGBA and BGRA formats are supported, which
+/// covers the formats used by the targets.
+pub fn read_buffer(image: FrameImage, buffer: &HostBuffer) -> Image {
+    let swap_red_blue = match image.format {
+        vk::Format::R8G8B8A8_UNORM | vk::Format::R8G8B8A8_SRGB => false,
+        vk::Format::B8G8R8A8_UNORM | vk::Format::B8G8R8A8_SRGB => true,
+        format => panic!("capturing {format:?} images is not supported"),
+    };
+
+    let mut captured = Image::new(
+        image.extent.width,
+        image.extent.height,
+        buffer.contents().to_vec(),
+    );
+    if swap_red_blue {
+        captured.swap_red_blue();
+    }
+
+    captured
+}
+
+/// A layout transition of the color image, without any access masks yet
+fn image_barrier<'a>(
+    image: vk::Image,
+    old_layout: vk::ImageLayout,
+    new_layout: vk::ImageLayout,
+) -> vk::ImageMemoryBarrier<'a> {
+    vk::ImageMemoryBarrier::default()
+        .old_layout(old_layout)
+        .new_layout(new_layout)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(COLOR_SUBRESOURCE_RANGE)
+}
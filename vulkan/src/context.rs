@@ -0,0 +1,299 @@
+//! Vulkan objects that live as long as the renderer: instance, surface and device.
+
+use std::ffi::{c_char, CStr};
+
+use ash::{khr, vk};
+use common::HasWindowAndDisplayHandle;
+use raw_window_handle::RawDisplayHandle;
+
+const VALIDATION_LAYER: &CStr = c"VK_LAYER_KHRONOS_validation";
+
+pub struct Instance {
+    pub entry: ash::Entry,
+    pub instance: ash::Instance,
+    pub api_version: u32,
+}
+
+impl Instance {
+    /// Creates an instance with the extensions required to create a surface for `display_handle`
+    pub fn new(display_handle: Option<RawDisplayHandle>) -> Self {
+        let entry = unsafe { ash::Entry::load() }.expect("failed to load the vulkan library");
+
+        let api_version = unsafe { entry.try_enumerate_instance_version() }
+            .unwrap()
+            .unwrap_or(vk::API_VERSION_1_0)
+            .min(vk::API_VERSION_1_3);
+
+        let app_info = vk::ApplicationInfo::default()
+            .application_name(c"wgpu-vulkan-comparison")
+            .api_version(api_version);
+
+        let extensions: Vec<*const c_char> = match display_handle {
+            Some(display_handle) => ash_window::enumerate_required_extensions(display_handle)
+                .unwrap()
+                .to_vec(),
+            None => Vec::new(),
+        };
+
+        // Same as `InstanceFlags::debugging()` in the wgpu backend
+        let layers: Vec<*const c_char> = if cfg!(debug_assertions) {
+            let available = unsafe { entry.enumerate_instance_layer_properties() }.unwrap();
+            available
+                .iter()
+                .any(|layer| layer.layer_name_as_c_str() == Ok(VALIDATION_LAYER))
+                .then_some(VALIDATION_LAYER.as_ptr())
+                .into_iter()
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let create_info = vk::InstanceCreateInfo::default()
+            .application_info(&app_info)
+            .enabled_extension_names(&extensions)
+            .enabled_layer_names(&layers);
+
+        let instance = unsafe { entry.create_instance(&create_info, None) }.unwrap();
+
+        Self {
+            entry,
+            instance,
+            api_version,
+        }
+    }
+
+    pub unsafe fn destroy(&self) {
+        self.instance.destroy_instance(None);
+    }
+}
+
+pub struct Surface {
+    pub loader: khr::surface::Instance,
+    pub surface: vk::SurfaceKHR,
+}
+
+impl Surface {
+    pub fn new(instance: &Instance, window: &dyn HasWindowAndDisplayHandle) -> Self {
+        let loader = khr::surface::Instance::new(&instance.entry, &instance.instance);
+
+        let surface = unsafe {
+            ash_window::create_surface(
+                &instance.entry,
+                &instance.instance,
+                window.display_handle().unwrap().as_raw(),
+                window.window_handle().unwrap().as_raw(),
+                None,
+            )
+        }
+        .unwrap();
+
+        Self { loader, surface }
+    }
+
+    pub unsafe fn destroy(&self) {
+        self.loader.destroy_surface(self.surface, None);
+    }
+}
+
+pub struct Device {
+    pub physical_device: vk::PhysicalDevice,
+    pub properties: vk::PhysicalDeviceProperties,
+    pub memory_properties: vk::PhysicalDeviceMemoryProperties,
+    pub device: ash::Device,
+    pub queue_family_index: u32,
+    pub queue: vk::Queue,
+    /// Zero if the queue does not support timestamp queries
+    pub timestamp_valid_bits: u32,
+}
+
+impl Device {
+    /// Picks a physical device, preferring discrete GPUs, with a queue that supports graphics and
+    /// presenting to `surface`.
+    pub fn new(instance: &Instance, surface: Option<&Surface>) -> Self {
+        let physical_devices = unsafe { instance.instance.enumerate_physical_devices() }.unwrap();
+
+        let (physical_device, queue_family_index, properties) = physical_devices
+            .into_iter()
+            .filter_map(|physical_device| {
+                let properties = unsafe {
+                    instance
+                        .instance
+                        .get_physical_device_properties(physical_device)
+                };
+                let queue_family_index = find_queue_family(instance, physical_device, surface)?;
+                Some((physical_device, queue_family_index, properties))
+            })
+            .max_by_key(|(_, _, properties)| match properties.device_type {
+                vk::PhysicalDeviceType::DISCRETE_GPU => 3,
+                vk::PhysicalDeviceType::INTEGRATED_GPU => 2,
+                vk::PhysicalDeviceType::VIRTUAL_GPU => 1,
+                _ => 0,
+            })
+            .expect("no suitable vulkan device found");
+
+        let memory_properties = unsafe {
+            instance
+                .instance
+                .get_physical_device_memory_properties(physical_device)
+        };
+
+        let timestamp_valid_bits = unsafe {
+            instance
+                .instance
+                .get_physical_device_queue_family_properties(physical_device)
+        }[queue_family_index as usize]
+            .timestamp_valid_bits;
+
+        let queue_create_infos = [vk::DeviceQueueCreateInfo::default()
+            .queue_family_index(queue_family_index)
+            .queue_priorities(&[1.0])];
+
+        let extensions: Vec<*const c_char> = match surface {
+            Some(_) => vec![khr::swapchain::NAME.as_ptr()],
+            None => Vec::new(),
+        };
+
+        let create_info = vk::DeviceCreateInfo::default()
+            .queue_create_infos(&queue_create_infos)
+            .enabled_extension_names(&extensions);
+
+        let device = unsafe {
+            instance
+                .instance
+                .create_device(physical_device, &create_info, None)
+        }
+        .unwrap();
+
+        let queue = unsafe { device.get_device_queue(queue_family_index, 0) };
+
+        Self {
+            physical_device,
+            properties,
+            memory_properties,
+            device,
+            queue_family_index,
+            queue,
+            timestamp_valid_bits,
+        }
+    }
+
+    pub fn find_memory_type(&self, type_bits: u32, flags: vk::MemoryPropertyFlags) -> u32 {
+        (0..self.memory_properties.memory_type_count)
+            .find(|&index| {
+                let memory_type = self.memory_properties.memory_types[index as usize];
+                type_bits & (1 << index) != 0 && memory_type.property_flags.contains(flags)
+            })
+            .expect("no suitable memory type")
+    }
+
+    /// Records commands with `record` into a new command buffer from `command_pool`, submits it
+    /// and waits until it finished
+    pub fn submit_and_wait(
+        &self,
+        command_pool: vk::CommandPool,
+        record: impl FnOnce(vk::CommandBuffer),
+    ) {
+        unsafe {
+            let command_buffers = self
+                .device
+                .allocate_command_buffers(
+                    &vk::CommandBufferAllocateInfo::default()
+                        .command_pool(command_pool)
+                        .level(vk::CommandBufferLevel::PRIMARY)
+                        .command_buffer_count(1),
+                )
+                .unwrap();
+
+            self.device
+                .begin_command_buffer(
+                    command_buffers[0],
+                    &vk::CommandBufferBeginInfo::default()
+                        .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+                )
+                .unwrap();
+            record(command_buffers[0]);
+            self.device.end_command_buffer(command_buffers[0]).unwrap();
+
+            let fence = self
+                .device
+                .create_fence(&vk::FenceCreateInfo::default(), None)
+                .unwrap();
+            let submit_info = vk::SubmitInfo::default().command_buffers(&command_buffers);
+            self.device
+                .queue_submit(self.queue, &[submit_info], fence)
+                .unwrap();
+            self.device
+                .wait_for_fences(&[fence], true, u64::MAX)
+                .unwrap();
+
+            self.device.destroy_fence(fence, None);
+            self.device
+                .free_command_buffers(command_pool, &command_buffers);
+        }
+    }
+
+    /// Driver name and version, if the device exposes them
+    pub fn driver_info(&self, instance: &Instance) -> Option<String> {
+        if instance.api_version < vk::API_VERSION_1_2
+            || self.properties.api_version < vk::API_VERSION_1_2
+        {
+            return None;
+        }
+
+        let mut driver_properties = vk::PhysicalDeviceDriverProperties::default();
+        let mut properties =
+            vk::PhysicalDeviceProperties2::default().push_next(&mut driver_properties);
+        unsafe {
+            instance
+                .instance
+                .get_physical_device_properties2(self.physical_device, &mut properties)
+        };
+
+        Some(format!(
+            "{} {}",
+            driver_properties
+                .driver_name_as_c_str()
+                .ok()?
+                .to_string_lossy(),
+            driver_properties
+                .driver_info_as_c_str()
+                .ok()?
+                .to_string_lossy()
+        ))
+    }
+
+    pub unsafe fn destroy(&self) {
+        self.device.destroy_device(None);
+    }
+}
+
+fn find_queue_family(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+    surface: Option<&Surface>,
+) -> Option<u32> {
+    let queue_families = unsafe {
+        instance
+            .instance
+            .get_physical_device_queue_family_properties(physical_device)
+    };
+
+    queue_families
+        .iter()
+        .enumerate()
+        .position(|(index, queue_family)| {
+            let supports_present = surface.is_none_or(|surface| unsafe {
+                surface
+                    .loader
+                    .get_physical_device_surface_support(
+                        physical_device,
+                        index as u32,
+                        surface.surface,
+                    )
+                    .unwrap_or(false)
+            });
+
+            queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS) && supports_present
+        })
+        .map(|index| index as u32)
+}
@@ -0,0 +1,45 @@
+//! Maps [`common::DepthSettings`] to Vulkan, like the depth buffer of the wgpu backend. The depth
+//! image itself is created with the other attachments, see [`crate::attachment`].
+
+use ash::vk;
+use common::{DepthCompare, DepthFormat};
+
+use crate::context::{Device, Instance};
+
+/// Picks the same format wgpu does. 16-bit and 32-bit float depth attachments are supported by
+/// every device, 24-bit ones are not.
+pub fn choose_format(instance: &Instance, device: &Device, format: DepthFormat) -> vk::Format {
+    let candidates: &[vk::Format] = match format {
+        DepthFormat::Depth16Unorm => &[vk::Format::D16_UNORM],
+        DepthFormat::Depth24Plus => &[vk::Format::X8_D24_UNORM_PACK32, vk::Format::D32_SFLOAT],
+        DepthFormat::Depth32Float => &[vk::Format::D32_SFLOAT],
+    };
+
+    candidates
+        .iter()
+        .copied()
+        .find(|&candidate| {
+            let properties = unsafe {
+                instance
+                    .instance
+                    .get_physical_device_format_properties(device.physical_device, candidate)
+            };
+            properties
+                .optimal_tiling_features
+                .contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+        })
+        .expect("no supported depth format")
+}
+
+pub fn compare_op(compare: DepthCompare) -> vk::CompareOp {
+    match compare {
+        DepthCompare::Never => vk::CompareOp::NEVER,
+        DepthCompare::Less => vk::CompareOp::LESS,
+        DepthCompare::Equal => vk::CompareOp::EQUAL,
+        DepthCompare::LessEqual => vk::CompareOp::LESS_OR_EQUAL,
+        DepthCompare::Greater => vk::CompareOp::GREATER,
+        DepthCompare::NotEqual => vk::CompareOp::NOT_EQUAL,
+        DepthCompare::GreaterEqual => vk::CompareOp::GREATER_OR_EQUAL,
+        DepthCompare::Always => vk::CompareOp::ALWAYS,
+    }
+}
@@ -0,0 +1,123 @@
+//! Measures how long the GPU spends on the render pass using timestamp queries.
+//!
+//! Every frame in flight has its own pair of queries. They are read back once the frame's fence
+//! was waited on, so reading them never stalls.
+
+use std::time::Duration;
+
+use ash::vk;
+use common::{GpuFrameTiming, GpuPassTiming};
+
+use crate::context::Device;
+
+/// Beginning and end of the render pass
+const QUERIES_PER_SLOT: u32 = 2;
+
+pub struct GpuTimer {
+    query_pool: vk::QueryPool,
+    /// The frame whose timestamps are written by each slot
+    slots: Vec<Option<u64>>,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    valid_mask: u64,
+    timings: Vec<GpuFrameTiming>,
+}
+
+impl GpuTimer {
+    /// Returns `None` if the device's queue does not support timestamps
+    pub fn new(device: &Device, slots: usize) -> Option<Self> {
+        if device.timestamp_valid_bits == 0 {
+            return None;
+        }
+
+        let create_info = vk::QueryPoolCreateInfo::default()
+            .query_type(vk::QueryType::TIMESTAMP)
+            .query_count(slots as u32 * QUERIES_PER_SLOT);
+        let query_pool = unsafe { device.device.create_query_pool(&create_info, None) }.unwrap();
+
+        let valid_mask = match device.timestamp_valid_bits {
+            64.. => u64::MAX,
+            bits => (1 << bits) - 1,
+        };
+
+        Some(Self {
+            query_pool,
+            slots: vec![None; slots],
+            period: device.properties.limits.timestamp_period,
+            valid_mask,
+            timings: Vec::new(),
+        })
+    }
+
+    /// Reads back the timestamps of the frame that previously used `slot`.
+    /// The GPU must have finished that frame.
+    pub fn collect(&mut self, device: &Device, slot: usize) {
+        let Some(frame) = self.slots[slot].take() else {
+            return;
+        };
+
+        let mut timestamps = [0u64; QUERIES_PER_SLOT as usize];
+        let result = unsafe {
+            device.device.get_query_pool_results(
+                self.query_pool,
+                slot as u32 * QUERIES_PER_SLOT,
+                &mut timestamps,
+                vk::QueryResultFlags::TYPE_64,
+            )
+        };
+        if result.is_err() {
+            return;
+        }
+
+        let ticks =
+            (timestamps[1] & self.valid_mask).saturating_sub(timestamps[0] & self.valid_mask);
+        self.timings.push(GpuFrameTiming {
+            frame,
+            passes: vec![GpuPassTiming {
+                name: "main",
+                duration: Duration::from_nanos((ticks as f64 * self.period as f64) as u64),
+            }],
+        });
+    }
+
+    /// Must be recorded outside of a render pass
+    pub unsafe fn begin(
+        &mut self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        slot: usize,
+        frame: u64,
+    ) {
+        let first_query = slot as u32 * QUERIES_PER_SLOT;
+        device.device.cmd_reset_query_pool(
+            command_buffer,
+            self.query_pool,
+            first_query,
+            QUERIES_PER_SLOT,
+        );
+        device.device.cmd_write_timestamp(
+            command_buffer,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            self.query_pool,
+            first_query,
+        );
+        self.slots[slot] = Some(frame);
+    }
+
+    pub unsafe fn end(&self, device: &Device, command_buffer: vk::CommandBuffer, slot: usize) {
+        device.device.cmd_write_timestamp(
+            command_buffer,
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            self.query_pool,
+            slot as u32 * QUERIES_PER_SLOT + 1,
+        );
+    }
+
+    pub fn take_timings(&mut self) -> Vec<GpuFrameTiming> {
+        std::mem::take(&mut self.timings)
+    }
+
+    pub unsafe fn destroy(&self, device: &Device) {
+        device.device.destroy_query_pool(self.query_pool, None);
+    }
+}
@@ -1,29 +1,719 @@
 use std::num::NonZeroU32;
 
-use common::has_window_and_display_handle::HasWindowAndDisplayHandle;
+use ash::vk;
+use attachment::AttachmentConfig;
+use batch::{Batch, MeshRanges};
+use buffer::HostBuffer;
+use camera::CameraRaw;
+use common::{
+    BufferStats, DepthSettings, GpuFrameTiming, HasWindowAndDisplayHandle, Image, Mesh, MeshHandle,
+    RendererInfo, RendererSettings, VertexAttributes,
+};
+use context::{Device, Instance, Surface};
+use gpu_timer::GpuTimer;
+use pipeline::PipelineLayout;
+use swapchain::SwapchainConfig;
+use raw_window_handle::HasDisplayHandle;
+use target::RenderTarget;
 
-struct VulkanRenderer {
+mod attachment;
+mod batch;
+mod buffer;
+mod camera;
+mod capture;
+mod context;
+mod depth;
+mod gpu_timer;
+mod pipeline;
+mod swapchain;
+mod target;
+mod vertex;
+
+/// Resources that are used by one frame in flight
+struct Frame {
+    command_buffer: vk::CommandBuffer,
+    image_available: vk::Semaphore,
+    in_flight: vk::Fence,
+    camera_buffer: HostBuffer,
+    descriptor_set: vk::DescriptorSet,
+    /// Meshes that were unloaded while this was the last submitted frame, by the index of their
+    /// batch. Their ranges are freed once the frame finished, as it might still draw them.
+    unloaded: Vec<(usize, MeshRanges)>,
+}
 
+struct VulkanRenderer {
+    instance: Instance,
+    device: Device,
+    render_pass: vk::RenderPass,
+    pipeline_layout: PipelineLayout,
+    /// `None` if depth testing is disabled
+    depth: Option<DepthSettings>,
+    target: RenderTarget,
+    size: (u32, u32),
+    command_pool: vk::CommandPool,
+    descriptor_pool: vk::DescriptorPool,
+    /// One per frame in flight
+    frames: Vec<Frame>,
+    current_frame: usize,
+    /// One per vertex attribute set of the loaded meshes, in the order they were first loaded
+    batches: Vec<Batch>,
+    /// The id of the next loaded mesh
+    next_mesh: u64,
+    /// `None` if the device does not support timestamp queries
+    gpu_timer: Option<GpuTimer>,
+    /// Number of frames rendered so far
+    frame: u64,
+    /// Set by [`common::Renderer::request_capture`] until the next frame was rendered
+    capture_requested: bool,
+    /// Whether a frame was captured since the target was recreated and can be read back
+    frame_captured: bool,
+    /// Must outlive the surface, which is destroyed in `drop`. `None` when rendering offscreen.
+    _window: Option<Box<dyn HasWindowAndDisplayHandle + Send + Sync>>,
 }
 
 impl common::Renderer for VulkanRenderer {
-    fn new(_window: impl HasWindowAndDisplayHandle + 'static, initial_window_size: (u32, u32)) -> Self {
-        todo!("init renderer")
+    fn new(
+        window: impl HasWindowAndDisplayHandle + Send + Sync + 'static,
+        initial_window_size: (u32, u32),
+        settings: &RendererSettings,
+    ) -> Self {
+        let window: Box<dyn HasWindowAndDisplayHandle + Send + Sync> = Box::new(window);
+
+        let instance = Instance::new(Some(window.display_handle().unwrap().as_raw()));
+        let surface = Surface::new(&instance, &*window);
+        let device = Device::new(&instance, Some(&surface));
+
+        let swapchain_config = SwapchainConfig::new(&device, &surface, settings.present_mode);
+        let attachment_config = AttachmentConfig::new(&instance, &device, settings);
+        let render_pass = pipeline::create_render_pass(
+            &device,
+            swapchain_config.format.format,
+            attachment_config,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+        );
+        let target = RenderTarget::swapchain(
+            &instance,
+            &device,
+            surface,
+            swapchain_config,
+            attachment_config,
+            render_pass,
+            initial_window_size,
+        );
+
+        Self::with_target(
+            instance,
+            device,
+            render_pass,
+            target,
+            initial_window_size,
+            settings,
+            Some(window),
+        )
+    }
+
+    fn new_headless(size: (u32, u32), settings: &RendererSettings) -> Self {
+        let instance = Instance::new(None);
+        let device = Device::new(&instance, None);
+
+        let attachment_config = AttachmentConfig::new(&instance, &device, settings);
+        // Left in a layout that can be copied from, so that frames can be read back
+        let render_pass = pipeline::create_render_pass(
+            &device,
+            target::OFFSCREEN_FORMAT,
+            attachment_config,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        );
+        let target = RenderTarget::offscreen(&device, render_pass, attachment_config, size);
+
+        Self::with_target(instance, device, render_pass, target, size, settings, None)
     }
 
     fn render(&mut self, camera: common::Camera) {
-        todo!("render")
+        let frame_index = self.frame;
+        self.frame += 1;
+        let slot = self.current_frame;
+
+        let image_index = {
+            let _phase = common::phase("acquire");
+            let in_flight = self.frames[slot].in_flight;
+
+            unsafe {
+                self.device
+                    .device
+                    .wait_for_fences(&[in_flight], true, u64::MAX)
+            }
+            .unwrap();
+
+            if let Some(timer) = &mut self.gpu_timer {
+                timer.collect(&self.device, slot);
+            }
+            self.free_unloaded_meshes(slot);
+
+            match self.target.acquire(self.frames[slot].image_available) {
+                Some(image_index) => image_index,
+                None => {
+                    self.recreate_target();
+                    return;
+                }
+            }
+        };
+
+        {
+            let _phase = common::phase("camera_upload");
+            let extent = self.target.extent();
+            let reverse_z = self.depth.is_some_and(|depth| depth.reverse_z);
+            let camera_raw = CameraRaw::new(
+                &camera,
+                extent.width as f32 / extent.height as f32,
+                reverse_z,
+            );
+            self.frames[slot]
+                .camera_buffer
+                .write(0, bytemuck::bytes_of(&camera_raw));
+        }
+
+        {
+            let _phase = common::phase("encode");
+            self.record_command_buffer(slot, image_index, frame_index);
+        }
+
+        {
+            let _phase = common::phase("submit");
+            let frame = &self.frames[slot];
+
+            let (wait_semaphore, signal_semaphore) = self
+                .target
+                .submit_semaphores(frame.image_available, image_index);
+            let wait_semaphores: Vec<_> = wait_semaphore.into_iter().collect();
+            let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
+            let command_buffers = [frame.command_buffer];
+            let signal_semaphores: Vec<_> = signal_semaphore.into_iter().collect();
+            let submit_info = vk::SubmitInfo::default()
+                .wait_semaphores(&wait_semaphores)
+                .wait_dst_stage_mask(&wait_stages[..wait_semaphores.len()])
+                .command_buffers(&command_buffers)
+                .signal_semaphores(&signal_semaphores);
+
+            unsafe {
+                self.device.device.reset_fences(&[frame.in_flight]).unwrap();
+                self.device
+                    .device
+                    .queue_submit(self.device.queue, &[submit_info], frame.in_flight)
+                    .unwrap();
+            }
+        }
+
+        let up_to_date = {
+            let _phase = common::phase("present");
+            self.target.present(self.device.queue, image_index)
+        };
+
+        self.current_frame = (slot + 1) % self.frames.len();
+
+        if self.capture_requested {
+            // Recreating the swapchain without copying also takes care of it being out of date
+            unsafe { self.device.device.device_wait_idle() }.unwrap();
+            self.target
+                .end_capture(&self.instance, &self.device, self.render_pass, self.size);
+            self.capture_requested = false;
+            self.frame_captured = true;
+        } else if !up_to_date {
+            self.recreate_target();
+        }
+    }
+
+    fn resize(&mut self, size: (NonZeroU32, NonZeroU32), _scale_factor: f64) {
+        self.size = (size.0.get(), size.1.get());
+        self.recreate_target();
+    }
+
+    fn load_mesh(&mut self, mesh: Mesh) -> MeshHandle {
+        let handle = MeshHandle(self.next_mesh);
+        self.next_mesh += 1;
+
+        self.insert_mesh(handle, &mesh);
+        handle
+    }
+
+    /// The mesh is not drawn by later frames, but its ranges are only freed once the frames in
+    /// flight finished, see [`Frame::unloaded`]
+    fn unload_mesh(&mut self, handle: MeshHandle) {
+        let batch = self.batch_of(handle);
+        let ranges = self.batches[batch].remove(handle);
+
+        // Frames finish in the order they were submitted, so the last one finishes last
+        let last_slot = (self.current_frame + self.frames.len() - 1) % self.frames.len();
+        self.frames[last_slot].unloaded.push((batch, ranges));
+    }
+
+    /// Space that was freed by the old mesh is reused once the frames in flight finished, if the
+    /// new one has the same attributes
+    fn update_mesh(&mut self, handle: MeshHandle, mesh: Mesh) {
+        self.unload_mesh(handle);
+        self.insert_mesh(handle, &mesh);
+    }
+
+    fn info(&self) -> RendererInfo {
+        let properties = &self.device.properties;
+        let name = properties
+            .device_name_as_c_str()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        RendererInfo {
+            backend: "vulkan".to_owned(),
+            adapter: format!("{name} ({:?})", properties.device_type),
+            driver: self
+                .device
+                .driver_info(&self.instance)
+                .unwrap_or_else(|| format!("driver version {}", properties.driver_version)),
+            msaa: self.target.attachment_config().sample_count,
+            present_mode: self.target.present_mode(),
+            max_frame_latency: Some(self.frames.len() as u32),
+            vertex_attributes: VertexAttributes::ALL,
+        }
+    }
+
+    fn gpu_timings(&mut self) -> Vec<GpuFrameTiming> {
+        match &mut self.gpu_timer {
+            Some(timer) => timer.take_timings(),
+            None => Vec::new(),
+        }
+    }
+
+    fn finish_gpu_timings(&mut self) {
+        let Some(timer) = &mut self.gpu_timer else {
+            return;
+        };
+
+        let fences: Vec<vk::Fence> = self.frames.iter().map(|frame| frame.in_flight).collect();
+        unsafe { self.device.device.wait_for_fences(&fences, true, u64::MAX) }.unwrap();
+        for slot in 0..self.frames.len() {
+            timer.collect(&self.device, slot);
+        }
+    }
+
+    fn buffer_stats(&self) -> Vec<BufferStats> {
+        self.batches.iter().flat_map(Batch::buffer_stats).collect()
+    }
+
+    /// Swapchain images can't be read anymore once they were presented, so the swapchain is
+    /// recreated for copying until the next frame was copied into an image
+    fn request_capture(&mut self) {
+        if !self.capture_requested {
+            unsafe { self.device.device.device_wait_idle() }.unwrap();
+            self.target
+                .begin_capture(&self.instance, &self.device, self.render_pass, self.size);
+            self.capture_requested = true;
+        }
+    }
+
+    /// Offscreen images are read back directly, swapchain images from their copy
+    fn capture(&mut self) -> Option<Image> {
+        if !std::mem::take(&mut self.frame_captured) {
+            return None;
+        }
+        let image = self.target.captured_frame()?;
+
+        // Waits for the last frame, which wrote the image
+        let buffer = capture::create_readback_buffer(&self.device, image);
+        self.device
+            .submit_and_wait(self.command_pool, |command_buffer| unsafe {
+                capture::record_copy(&self.device, command_buffer, image, &buffer);
+            });
+        let captured = capture::read_buffer(image, &buffer);
+
+        unsafe { buffer.destroy(&self.device) };
+
+        Some(captured)
+    }
+}
+
+impl VulkanRenderer {
+    /// Creates everything that does not depend on whether a window or an offscreen image is
+    /// rendered to
+    fn with_target(
+        instance: Instance,
+        device: Device,
+        render_pass: vk::RenderPass,
+        target: RenderTarget,
+        size: (u32, u32),
+        settings: &RendererSettings,
+        window: Option<Box<dyn HasWindowAndDisplayHandle + Send + Sync>>,
+    ) -> Self {
+        let pipeline_layout = PipelineLayout::new(&device);
+
+        // Each frame in flight has its own resources, so this limits how many frames are queued
+        // up, like `desired_maximum_frame_latency` of the wgpu backend
+        let frames_in_flight = settings.frame_latency() as usize;
+
+        let command_pool = unsafe {
+            device.device.create_command_pool(
+                &vk::CommandPoolCreateInfo::default()
+                    .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+                    .queue_family_index(device.queue_family_index),
+                None,
+            )
+        }
+        .unwrap();
+
+        let pool_sizes = [vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::UNIFORM_BUFFER,
+            descriptor_count: frames_in_flight as u32,
+        }];
+        let descriptor_pool = unsafe {
+            device.device.create_descriptor_pool(
+                &vk::DescriptorPoolCreateInfo::default()
+                    .max_sets(frames_in_flight as u32)
+                    .pool_sizes(&pool_sizes),
+                None,
+            )
+        }
+        .unwrap();
+
+        let frames = (0..frames_in_flight)
+            .map(|_| create_frame(&device, command_pool, descriptor_pool, &pipeline_layout))
+            .collect();
+
+        let gpu_timer = GpuTimer::new(&device, frames_in_flight);
+        if gpu_timer.is_none() {
+            println!("Timestamp queries are not supported, GPU timings will not be available");
+        }
+
+        Self {
+            instance,
+            device,
+            render_pass,
+            pipeline_layout,
+            depth: settings.depth,
+            target,
+            size,
+            command_pool,
+            descriptor_pool,
+            frames,
+            current_frame: 0,
+            batches: Vec::new(),
+            next_mesh: 0,
+            gpu_timer,
+            frame: 0,
+            capture_requested: false,
+            frame_captured: false,
+            _window: window,
+        }
+    }
+
+    fn insert_mesh(&mut self, handle: MeshHandle, mesh: &Mesh) {
+        let attributes = mesh.attributes();
+
+        let index = match self
+            .batches
+            .iter()
+            .position(|batch| batch.attributes == attributes)
+        {
+            Some(index) => index,
+            None => {
+                self.batches.push(Batch::new(
+                    &self.device,
+                    self.render_pass,
+                    &self.pipeline_layout,
+                    self.depth,
+                    self.target.attachment_config().samples(),
+                    attributes,
+                ));
+                self.batches.len() - 1
+            }
+        };
+
+        if !self.batches[index].fits(mesh) {
+            // Growing replaces the buffers that frames in flight are reading from. Once they
+            // finished, the ranges of all unloaded meshes can be freed, which might leave enough
+            // space already.
+            unsafe { self.device.device.device_wait_idle() }.unwrap();
+            for slot in 0..self.frames.len() {
+                self.free_unloaded_meshes(slot);
+            }
+        }
+
+        self.batches[index].insert(&self.device, self.command_pool, handle, mesh);
+    }
+
+    /// Frees the ranges of the meshes that were unloaded while the frame of `slot` was in flight,
+    /// which must have finished
+    fn free_unloaded_meshes(&mut self, slot: usize) {
+        for (batch, ranges) in self.frames[slot].unloaded.drain(..) {
+            self.batches[batch].free(ranges);
+        }
+    }
+
+    /// The index of the batch that holds the mesh
+    fn batch_of(&self, handle: MeshHandle) -> usize {
+        self.batches
+            .iter()
+            .position(|batch| batch.contains(handle))
+            .unwrap_or_else(|| panic!("{handle:?} is not loaded"))
+    }
+
+    fn record_command_buffer(&mut self, slot: usize, image_index: u32, frame_index: u64) {
+        let device = &self.device.device;
+        let frame = &self.frames[slot];
+        let command_buffer = frame.command_buffer;
+        let extent = self.target.extent();
+
+        unsafe {
+            device
+                .reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())
+                .unwrap();
+            device
+                .begin_command_buffer(
+                    command_buffer,
+                    &vk::CommandBufferBeginInfo::default()
+                        .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+                )
+                .unwrap();
+
+            if let Some(timer) = &mut self.gpu_timer {
+                timer.begin(&self.device, command_buffer, slot, frame_index);
+            }
+
+            self.record_scene(
+                command_buffer,
+                self.target.framebuffer(image_index),
+                extent,
+                frame.descriptor_set,
+            );
+
+            if let Some(timer) = &self.gpu_timer {
+                timer.end(&self.device, command_buffer, slot);
+            }
+
+            // After the timer, so the copy doesn't count towards the frame time
+            if self.capture_requested {
+                self.target
+                    .record_keep_frame(&self.device, command_buffer, image_index);
+            }
+
+            device.end_command_buffer(command_buffer).unwrap();
+        }
     }
 
-    fn resize(&mut self, size: (NonZeroU32, NonZeroU32), scale_factor: f64) {
-        todo!("handle resize")
+    /// Records the render pass that draws all loaded meshes into `framebuffer`
+    unsafe fn record_scene(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        framebuffer: vk::Framebuffer,
+        extent: vk::Extent2D,
+        descriptor_set: vk::DescriptorSet,
+    ) {
+        let device = &self.device.device;
+
+        // One per attachment, the target image is not cleared with MSAA but still needs a value
+        let clear_color = vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: [0.4, 0.9, 1.0, 1.0],
+            },
+        };
+        let mut clear_values = vec![clear_color];
+        if self.target.attachment_config().is_multisampled() {
+            clear_values.push(clear_color);
+        }
+        if let Some(depth) = self.depth {
+            clear_values.push(vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: depth.clear_value(),
+                    stencil: 0,
+                },
+            });
+        }
+        let render_pass_begin = vk::RenderPassBeginInfo::default()
+            .render_pass(self.render_pass)
+            .framebuffer(framebuffer)
+            .render_area(extent.into())
+            .clear_values(&clear_values);
+        device.cmd_begin_render_pass(
+            command_buffer,
+            &render_pass_begin,
+            vk::SubpassContents::INLINE,
+        );
+
+        if !self.batches.is_empty() {
+            let viewport = vk::Viewport {
+                x: 0.0,
+                y: 0.0,
+                width: extent.width as f32,
+                height: extent.height as f32,
+                min_depth: 0.0,
+                max_depth: 1.0,
+            };
+            device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+            device.cmd_set_scissor(command_buffer, 0, &[extent.into()]);
+
+            // All pipelines share the layout, so the camera stays bound across them
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout.layout,
+                0,
+                &[descriptor_set],
+                &[],
+            );
+
+            for batch in &self.batches {
+                batch.record_draws(&self.device, command_buffer);
+            }
+        }
+
+        device.cmd_end_render_pass(command_buffer);
     }
-    
-    fn load_mesh(&mut self, mesh: common::Mesh) {
-        todo!()
+
+    fn recreate_target(&mut self) {
+        unsafe { self.device.device.device_wait_idle() }.unwrap();
+
+        self.target
+            .recreate(&self.instance, &self.device, self.render_pass, self.size);
+        self.frame_captured = false;
+    }
+}
+
+impl Drop for VulkanRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.device.device_wait_idle().unwrap();
+
+            if let Some(timer) = &self.gpu_timer {
+                timer.destroy(&self.device);
+            }
+            for batch in &self.batches {
+                batch.destroy(&self.device);
+            }
+            for frame in &self.frames {
+                frame.camera_buffer.destroy(&self.device);
+                self.device
+                    .device
+                    .destroy_semaphore(frame.image_available, None);
+                self.device.device.destroy_fence(frame.in_flight, None);
+            }
+            self.device
+                .device
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+            self.device
+                .device
+                .destroy_command_pool(self.command_pool, None);
+            self.target.destroy(&self.device);
+            self.pipeline_layout.destroy(&self.device);
+            self.device
+                .device
+                .destroy_render_pass(self.render_pass, None);
+            self.device.destroy();
+            self.instance.destroy();
+        }
+    }
+}
+
+fn create_frame(
+    device: &Device,
+    command_pool: vk::CommandPool,
+    descriptor_pool: vk::DescriptorPool,
+    pipeline_layout: &PipelineLayout,
+) -> Frame {
+    let command_buffer = unsafe {
+        device.device.allocate_command_buffers(
+            &vk::CommandBufferAllocateInfo::default()
+                .command_pool(command_pool)
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .command_buffer_count(1),
+        )
+    }
+    .unwrap()[0];
+
+    let image_available = unsafe {
+        device
+            .device
+            .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)
+    }
+    .unwrap();
+
+    // Signaled, so that waiting for the first frame does not block
+    let in_flight = unsafe {
+        device.device.create_fence(
+            &vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED),
+            None,
+        )
+    }
+    .unwrap();
+
+    let camera_buffer = HostBuffer::new(
+        device,
+        std::mem::size_of::<CameraRaw>() as u64,
+        vk::BufferUsageFlags::UNIFORM_BUFFER,
+    );
+
+    let set_layouts = [pipeline_layout.descriptor_set_layout];
+    let descriptor_set = unsafe {
+        device.device.allocate_descriptor_sets(
+            &vk::DescriptorSetAllocateInfo::default()
+                .descriptor_pool(descriptor_pool)
+                .set_layouts(&set_layouts),
+        )
+    }
+    .unwrap()[0];
+
+    let buffer_infos = [vk::DescriptorBufferInfo {
+        buffer: camera_buffer.buffer,
+        offset: 0,
+        range: vk::WHOLE_SIZE,
+    }];
+    let write = vk::WriteDescriptorSet::default()
+        .dst_set(descriptor_set)
+        .dst_binding(0)
+        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+        .buffer_info(&buffer_infos);
+    unsafe { device.device.update_descriptor_sets(&[write], &[]) };
+
+    Frame {
+        command_buffer,
+        image_available,
+        in_flight,
+        camera_buffer,
+        descriptor_set,
+        unloaded: Vec::new(),
     }
 }
 
 fn main() {
-    common::run_app::<VulkanRenderer>();
-}
\ No newline at end of file
+    common::run::<VulkanRenderer>(common::Cli::from_env());
+}
+
+#[cfg(test)]
+mod tests {
+    use common::golden::{self, GoldenScene, Tolerance};
+
+    use ash::vk;
+
+    use super::VulkanRenderer;
+
+    /// Also finds software devices like lavapipe, see [`common::golden`]
+    fn device_available() -> bool {
+        let Ok(entry) = (unsafe { ash::Entry::load() }) else {
+            return false;
+        };
+        let create_info = vk::InstanceCreateInfo::default();
+        let Ok(instance) = (unsafe { entry.create_instance(&create_info, None) }) else {
+            return false;
+        };
+        let available = unsafe { instance.enumerate_physical_devices() }
+            .is_ok_and(|physical_devices| !physical_devices.is_empty());
+        unsafe { instance.destroy_instance(None) };
+
+        available
+    }
+
+    #[test]
+    fn triangles_match_golden_image() {
+        if !device_available() {
+            eprintln!("No vulkan device found, skipping the golden image test");
+            return;
+        }
+        golden::assert_matches_golden::<VulkanRenderer>(&GoldenScene::triangles(), &Tolerance::default());
+    }
+}
@@ -0,0 +1,309 @@
+//! The render pass and graphics pipeline, equivalent to the ones of the wgpu backend.
+
+use ash::vk;
+use common::{DepthSettings, VertexAttributes};
+
+use crate::{attachment::AttachmentConfig, context::Device, depth, vertex};
+
+/// The shader is shared with the wgpu backend and translated to SPIR-V with naga, which is also
+/// what wgpu does internally. This way both backends run exactly the same shader code.
+const SHADER_SOURCE: &str = include_str!("../../wgpu/src/shader.wgsl");
+
+/// The variant for meshes with `attributes`, like the one the wgpu backend compiles for them
+fn compile_shader(attributes: VertexAttributes) -> Vec<u32> {
+    let source = common::shader_variant(SHADER_SOURCE, attributes);
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    let info = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    // The default options flip the y axis, just like wgpu does
+    naga::back::spv::write_vec(&module, &info, &naga::back::spv::Options::default(), None).unwrap()
+}
+
+/// `final_layout` is the layout the target image is left in, depending on whether it is presented
+/// or read back. The attachments are ordered like [`Attachments::framebuffer_views`]: the target
+/// image, the multisampled color image with MSAA and the depth image with depth testing.
+///
+/// [`Attachments::framebuffer_views`]: crate::attachment::Attachments::framebuffer_views
+pub fn create_render_pass(
+    device: &Device,
+    format: vk::Format,
+    config: AttachmentConfig,
+    final_layout: vk::ImageLayout,
+) -> vk::RenderPass {
+    // With MSAA the target image is only written by the resolve, which overwrites all of it
+    let target_load_op = if config.is_multisampled() {
+        vk::AttachmentLoadOp::DONT_CARE
+    } else {
+        vk::AttachmentLoadOp::CLEAR
+    };
+    let mut attachments = vec![vk::AttachmentDescription::default()
+        .format(format)
+        .samples(vk::SampleCountFlags::TYPE_1)
+        .load_op(target_load_op)
+        .store_op(vk::AttachmentStoreOp::STORE)
+        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .final_layout(final_layout)];
+
+    // The samples and the depth are not needed after the frame, just like in the wgpu backend
+    if config.is_multisampled() {
+        attachments.push(
+            vk::AttachmentDescription::default()
+                .format(format)
+                .samples(config.samples())
+                .load_op(vk::AttachmentLoadOp::CLEAR)
+                .store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .initial_layout(vk::ImageLayout::UNDEFINED)
+                .final_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL),
+        );
+    }
+    if let Some(depth_format) = config.depth_format {
+        attachments.push(
+            vk::AttachmentDescription::default()
+                .format(depth_format)
+                .samples(config.samples())
+                .load_op(vk::AttachmentLoadOp::CLEAR)
+                .store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .initial_layout(vk::ImageLayout::UNDEFINED)
+                .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL),
+        );
+    }
+
+    let target_attachments = [vk::AttachmentReference::default()
+        .attachment(0)
+        .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)];
+    let multisampled_attachments = [vk::AttachmentReference::default()
+        .attachment(1)
+        .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)];
+    let depth_attachment = vk::AttachmentReference::default()
+        .attachment(attachments.len() as u32 - 1)
+        .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+
+    let mut subpass =
+        vk::SubpassDescription::default().pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS);
+    subpass = if config.is_multisampled() {
+        subpass
+            .color_attachments(&multisampled_attachments)
+            .resolve_attachments(&target_attachments)
+    } else {
+        subpass.color_attachments(&target_attachments)
+    };
+    if config.depth_format.is_some() {
+        subpass = subpass.depth_stencil_attachment(&depth_attachment);
+    }
+    let subpasses = [subpass];
+
+    // Wait for the presentation engine to release the image, or for the previous frame to finish
+    // writing to it, before writing to it. The other attachments are shared by all frames in
+    // flight.
+    let dependencies = [vk::SubpassDependency::default()
+        .src_subpass(vk::SUBPASS_EXTERNAL)
+        .dst_subpass(0)
+        .src_stage_mask(
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+        )
+        .dst_stage_mask(
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+        )
+        .src_access_mask(
+            vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        )
+        .dst_access_mask(
+            vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        )];
+
+    let create_info = vk::RenderPassCreateInfo::default()
+        .attachments(&attachments)
+        .subpasses(&subpasses)
+        .dependencies(&dependencies);
+
+    unsafe { device.device.create_render_pass(&create_info, None) }.unwrap()
+}
+
+/// The layout shared by the pipelines of all vertex attribute sets: the camera uniform buffer
+pub struct PipelineLayout {
+    pub descriptor_set_layout: vk::DescriptorSetLayout,
+    pub layout: vk::PipelineLayout,
+}
+
+impl PipelineLayout {
+    pub fn new(device: &Device) -> Self {
+        let bindings = [vk::DescriptorSetLayoutBinding::default()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::VERTEX)];
+        let descriptor_set_layout = unsafe {
+            device.device.create_descriptor_set_layout(
+                &vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings),
+                None,
+            )
+        }
+        .unwrap();
+
+        let set_layouts = [descriptor_set_layout];
+        let layout = unsafe {
+            device.device.create_pipeline_layout(
+                &vk::PipelineLayoutCreateInfo::default().set_layouts(&set_layouts),
+                None,
+            )
+        }
+        .unwrap();
+
+        Self {
+            descriptor_set_layout,
+            layout,
+        }
+    }
+
+    pub unsafe fn destroy(&self, device: &Device) {
+        device.device.destroy_pipeline_layout(self.layout, None);
+        device
+            .device
+            .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+    }
+}
+
+/// The pipeline for meshes with `attributes`. `depth` must be `Some` if the render pass has a
+/// depth attachment, `samples` must match its color and depth attachments.
+pub fn create_pipeline(
+    device: &Device,
+    render_pass: vk::RenderPass,
+    layout: &PipelineLayout,
+    depth: Option<DepthSettings>,
+    samples: vk::SampleCountFlags,
+    attributes: VertexAttributes,
+) -> vk::Pipeline {
+    let code = compile_shader(attributes);
+    let shader_module = unsafe {
+        device
+            .device
+            .create_shader_module(&vk::ShaderModuleCreateInfo::default().code(&code), None)
+    }
+    .unwrap();
+
+    let stages = [
+        vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::VERTEX)
+            .module(shader_module)
+            .name(c"vertex_main"),
+        vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::FRAGMENT)
+            .module(shader_module)
+            .name(c"fragment_main"),
+    ];
+
+    let vertex_bindings = vertex::bindings(attributes);
+    let vertex_attributes = vertex::attribute_descriptions(attributes);
+    let vertex_input = vk::PipelineVertexInputStateCreateInfo::default()
+        .vertex_binding_descriptions(&vertex_bindings)
+        .vertex_attribute_descriptions(&vertex_attributes);
+
+    let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::default()
+        .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+
+    // Viewport and scissor are dynamic, so the pipeline survives resizes
+    let viewport = vk::PipelineViewportStateCreateInfo::default()
+        .viewport_count(1)
+        .scissor_count(1);
+    let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let dynamic_state =
+        vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+
+    let rasterization = vk::PipelineRasterizationStateCreateInfo::default()
+        .polygon_mode(vk::PolygonMode::FILL)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+        .line_width(1.0);
+
+    let multisample =
+        vk::PipelineMultisampleStateCreateInfo::default().rasterization_samples(samples);
+
+    let depth_stencil = match depth {
+        Some(depth) => vk::PipelineDepthStencilStateCreateInfo::default()
+            .depth_test_enable(true)
+            .depth_write_enable(true)
+            .depth_compare_op(depth::compare_op(depth.compare)),
+        None => vk::PipelineDepthStencilStateCreateInfo::default(),
+    };
+
+    let color_blend_attachments = [vk::PipelineColorBlendAttachmentState::default()
+        .blend_enable(false)
+        .color_write_mask(vk::ColorComponentFlags::RGBA)];
+    let color_blend =
+        vk::PipelineColorBlendStateCreateInfo::default().attachments(&color_blend_attachments);
+
+    let create_info = vk::GraphicsPipelineCreateInfo::default()
+        .stages(&stages)
+        .vertex_input_state(&vertex_input)
+        .input_assembly_state(&input_assembly)
+        .viewport_state(&viewport)
+        .rasterization_state(&rasterization)
+        .multisample_state(&multisample)
+        .depth_stencil_state(&depth_stencil)
+        .color_blend_state(&color_blend)
+        .dynamic_state(&dynamic_state)
+        .layout(layout.layout)
+        .render_pass(render_pass)
+        .subpass(0);
+
+    let pipeline = unsafe {
+        device
+            .device
+            .create_graphics_pipelines(vk::PipelineCache::null(), &[create_info], None)
+    }
+    .map_err(|(_, err)| err)
+    .unwrap()[0];
+
+    unsafe { device.device.destroy_shader_module(shader_module, None) };
+
+    pipeline
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{VertexAttribute, VertexAttributes};
+
+    use super::SHADER_SOURCE;
+
+    /// Both backends compile a variant for every attribute set they encounter
+    #[test]
+    fn all_shader_variants_are_valid() {
+        let optional = &VertexAttribute::ALL[1..];
+        for subset in 0..1 << optional.len() {
+            let attributes = optional
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| subset & (1 << i) != 0)
+                .fold(
+                    VertexAttributes::POSITION_ONLY,
+                    |attributes, (_, &attribute)| attributes.with(attribute),
+                );
+
+            let source = common::shader_variant(SHADER_SOURCE, attributes);
+            let module = naga::front::wgsl::parse_str(&source)
+                .unwrap_or_else(|err| panic!("{attributes:?}: {}", err.emit_to_string(&source)));
+            naga::valid::Validator::new(
+                naga::valid::ValidationFlags::all(),
+                naga::valid::Capabilities::empty(),
+            )
+            .validate(&module)
+            .unwrap_or_else(|err| panic!("{attributes:?}: {err:?}"));
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use ash::{khr, vk};
+use common::PresentMode;
+
+use crate::{
+    attachment::{AttachmentConfig, Attachments},
+    context::{Device, Instance, Surface},
+};
+
+pub struct Swapchain {
+    loader: khr::swapchain::Device,
+    pub swapchain: vk::SwapchainKHR,
+    pub extent: vk::Extent2D,
+    pub images: Vec<vk::Image>,
+    views: Vec<vk::ImageView>,
+    pub attachments: Attachments,
+    pub framebuffers: Vec<vk::Framebuffer>,
+    /// One semaphore per image, as an image's semaphore may only be reused once it was presented
+    pub render_finished: Vec<vk::Semaphore>,
+    /// Whether the images can be copied from, which is only requested while a frame is captured
+    pub copyable: bool,
+}
+
+/// How the images of a swapchain are created, which stays the same when it is recreated
+#[derive(Clone, Copy, Debug)]
+pub struct SwapchainConfig {
+    pub format: vk::SurfaceFormatKHR,
+    /// Negotiated from the requested present mode
+    pub present_mode: PresentMode,
+}
+
+impl SwapchainConfig {
+    pub fn new(device: &Device, surface: &Surface, present_mode: PresentMode) -> Self {
+        let supported = unsafe {
+            surface
+                .loader
+                .get_physical_device_surface_present_modes(device.physical_device, surface.surface)
+        }
+        .unwrap();
+
+        Self {
+            format: choose_format(device, surface),
+            present_mode: present_mode.negotiate(|mode| supported.contains(&vk_present_mode(mode))),
+        }
+    }
+}
+
+/// Prefers an sRGB format, just like the wgpu backend
+fn choose_format(device: &Device, surface: &Surface) -> vk::SurfaceFormatKHR {
+    let formats = unsafe {
+        surface
+            .loader
+            .get_physical_device_surface_formats(device.physical_device, surface.surface)
+    }
+    .unwrap();
+
+    formats
+        .iter()
+        .find(|format| {
+            matches!(
+                format.format,
+                vk::Format::B8G8R8A8_SRGB | vk::Format::R8G8B8A8_SRGB
+            ) && format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
+        })
+        .copied()
+        .unwrap_or(formats[0])
+}
+
+impl Swapchain {
+    /// `old` is the swapchain that is being replaced, it still has to be destroyed by the caller.
+    /// With `capture`, the images can be copied from if the surface supports it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        instance: &Instance,
+        device: &Device,
+        surface: &Surface,
+        config: SwapchainConfig,
+        attachment_config: AttachmentConfig,
+        render_pass: vk::RenderPass,
+        size: (u32, u32),
+        capture: bool,
+        old: Option<&Swapchain>,
+    ) -> Self {
+        let loader = khr::swapchain::Device::new(&instance.instance, &device.device);
+
+        let capabilities = unsafe {
+            surface
+                .loader
+                .get_physical_device_surface_capabilities(device.physical_device, surface.surface)
+        }
+        .unwrap();
+
+        let extent = if capabilities.current_extent.width != u32::MAX {
+            capabilities.current_extent
+        } else {
+            vk::Extent2D {
+                width: size.0.clamp(
+                    capabilities.min_image_extent.width,
+                    capabilities.max_image_extent.width,
+                ),
+                height: size.1.clamp(
+                    capabilities.min_image_extent.height,
+                    capabilities.max_image_extent.height,
+                ),
+            }
+        };
+
+        let mut image_count = capabilities.min_image_count + 1;
+        if capabilities.max_image_count != 0 {
+            image_count = image_count.min(capabilities.max_image_count);
+        }
+
+        let composite_alpha = [
+            vk::CompositeAlphaFlagsKHR::OPAQUE,
+            vk::CompositeAlphaFlagsKHR::INHERIT,
+            vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED,
+            vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED,
+        ]
+        .into_iter()
+        .find(|&flag| capabilities.supported_composite_alpha.contains(flag))
+        .unwrap();
+
+        // Captured frames are copied after rendering them, as presented images can't be read back
+        let copyable = capture
+            && capabilities
+                .supported_usage_flags
+                .contains(vk::ImageUsageFlags::TRANSFER_SRC);
+        let usage = if copyable {
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC
+        } else {
+            vk::ImageUsageFlags::COLOR_ATTACHMENT
+        };
+
+        let create_info = vk::SwapchainCreateInfoKHR::default()
+            .surface(surface.surface)
+            .min_image_count(image_count)
+            .image_format(config.format.format)
+            .image_color_space(config.format.color_space)
+            .image_extent(extent)
+            .image_array_layers(1)
+            .image_usage(usage)
+            .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .pre_transform(capabilities.current_transform)
+            .composite_alpha(composite_alpha)
+            .present_mode(vk_present_mode(config.present_mode))
+            .clipped(true)
+            .old_swapchain(old.map_or(vk::SwapchainKHR::null(), |old| old.swapchain));
+
+        let swapchain = unsafe { loader.create_swapchain(&create_info, None) }.unwrap();
+
+        let images = unsafe { loader.get_swapchain_images(swapchain) }.unwrap();
+
+        let views: Vec<vk::ImageView> = images
+            .iter()
+            .map(|&image| {
+                let create_info = vk::ImageViewCreateInfo::default()
+                    .image(image)
+                    .view_type(vk::ImageViewType::TYPE_2D)
+                    .format(config.format.format)
+                    .subresource_range(COLOR_SUBRESOURCE_RANGE);
+                unsafe { device.device.create_image_view(&create_info, None) }.unwrap()
+            })
+            .collect();
+
+        let attachments = Attachments::new(device, attachment_config, config.format.format, extent);
+
+        let framebuffers = views
+            .iter()
+            .map(|&view| {
+                let views = attachments.framebuffer_views(view);
+                let create_info = vk::FramebufferCreateInfo::default()
+                    .render_pass(render_pass)
+                    .attachments(&views)
+                    .width(extent.width)
+                    .height(extent.height)
+                    .layers(1);
+                unsafe { device.device.create_framebuffer(&create_info, None) }.unwrap()
+            })
+            .collect();
+
+        let render_finished = images
+            .iter()
+            .map(|_| {
+                unsafe {
+                    device
+                        .device
+                        .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)
+                }
+                .unwrap()
+            })
+            .collect();
+
+        Self {
+            loader,
+            swapchain,
+            extent,
+            images,
+            views,
+            attachments,
+            framebuffers,
+            render_finished,
+            copyable,
+        }
+    }
+
+    /// Returns the index of the acquired image, or `None` if the swapchain is out of date
+    pub fn acquire(&self, semaphore: vk::Semaphore) -> Option<u32> {
+        match unsafe {
+            self.loader
+                .acquire_next_image(self.swapchain, u64::MAX, semaphore, vk::Fence::null())
+        } {
+            Ok((index, _suboptimal)) => Some(index),
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => None,
+            Err(err) => panic!("failed to acquire swapchain image: {err}"),
+        }
+    }
+
+    /// Returns `false` if the swapchain is out of date or suboptimal and should be recreated
+    pub fn present(&self, queue: vk::Queue, image_index: u32) -> bool {
+        let wait_semaphores = [self.render_finished[image_index as usize]];
+        let swapchains = [self.swapchain];
+        let image_indices = [image_index];
+        let present_info = vk::PresentInfoKHR::default()
+            .wait_semaphores(&wait_semaphores)
+            .swapchains(&swapchains)
+            .image_indices(&image_indices);
+
+        match unsafe { self.loader.queue_present(queue, &present_info) } {
+            Ok(suboptimal) => !suboptimal,
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => false,
+            Err(err) => panic!("failed to present swapchain image: {err}"),
+        }
+    }
+
+    pub unsafe fn destroy(&self, device: &Device) {
+        for &semaphore in &self.render_finished {
+            device.device.destroy_semaphore(semaphore, None);
+        }
+        for &framebuffer in &self.framebuffers {
+            device.device.destroy_framebuffer(framebuffer, None);
+        }
+        for &view in &self.views {
+            device.device.destroy_image_view(view, None);
+        }
+        self.attachments.destroy(device);
+        self.loader.destroy_swapchain(self.swapchain, None);
+    }
+}
+
+fn vk_present_mode(mode: PresentMode) -> vk::PresentModeKHR {
+    match mode {
+        PresentMode::Fifo => vk::PresentModeKHR::FIFO,
+        PresentMode::FifoRelaxed => vk::PresentModeKHR::FIFO_RELAXED,
+        PresentMode::Mailbox => vk::PresentModeKHR::MAILBOX,
+        PresentMode::Immediate => vk::PresentModeKHR::IMMEDIATE,
+    }
+}
+
+pub const COLOR_SUBRESOURCE_RANGE: vk::ImageSubresourceRange = vk::ImageSubresourceRange {
+    aspect_mask: vk::ImageAspectFlags::COLOR,
+    base_mip_level: 0,
+    level_count: 1,
+    base_array_layer: 0,
+    layer_count: 1,
+};
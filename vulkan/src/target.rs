@@ -0,0 +1,411 @@
+//! Where frames are rendered to: either a swapchain or an offscreen image.
+
+use ash::vk;
+use common::PresentMode;
+
+use crate::{
+    attachment::{AttachmentConfig, Attachments},
+    capture::{FrameImage, LastFrame},
+    context::{Device, Instance, Surface},
+    swapchain::{Swapchain, SwapchainConfig, COLOR_SUBRESOURCE_RANGE},
+};
+
+/// Same as the offscreen format of the wgpu backend
+pub const OFFSCREEN_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+// There is only one target per renderer, so its size doesn't matter
+#[allow(clippy::large_enum_variant)]
+pub enum RenderTarget {
+    Swapchain {
+        surface: Surface,
+        config: SwapchainConfig,
+        swapchain: Swapchain,
+        /// Whether the swapchain is created for copying the next frame with
+        /// [`RenderTarget::record_keep_frame`]
+        capturing: bool,
+        /// A copy of the captured frame, as swapchain images can't be read anymore once they
+        /// were presented
+        last_frame: Option<LastFrame>,
+    },
+    Offscreen(OffscreenImage),
+}
+
+impl RenderTarget {
+    pub fn swapchain(
+        instance: &Instance,
+        device: &Device,
+        surface: Surface,
+        config: SwapchainConfig,
+        attachment_config: AttachmentConfig,
+        render_pass: vk::RenderPass,
+        size: (u32, u32),
+    ) -> Self {
+        let swapchain = Swapchain::new(
+            instance,
+            device,
+            &surface,
+            config,
+            attachment_config,
+            render_pass,
+            size,
+            false,
+            None,
+        );
+
+        Self::Swapchain {
+            surface,
+            config,
+            swapchain,
+            capturing: false,
+            last_frame: None,
+        }
+    }
+
+    pub fn offscreen(
+        device: &Device,
+        render_pass: vk::RenderPass,
+        attachment_config: AttachmentConfig,
+        size: (u32, u32),
+    ) -> Self {
+        Self::Offscreen(OffscreenImage::new(
+            device,
+            render_pass,
+            OFFSCREEN_FORMAT,
+            attachment_config,
+            size,
+        ))
+    }
+
+    pub fn attachment_config(&self) -> AttachmentConfig {
+        match self {
+            Self::Swapchain { swapchain, .. } => swapchain.attachments.config,
+            Self::Offscreen(image) => image.attachments.config,
+        }
+    }
+
+    /// `None` for offscreen images, which are never presented
+    pub fn present_mode(&self) -> Option<PresentMode> {
+        match self {
+            Self::Swapchain { config, .. } => Some(config.present_mode),
+            Self::Offscreen(_) => None,
+        }
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        match self {
+            Self::Swapchain { swapchain, .. } => swapchain.extent,
+            Self::Offscreen(image) => image.extent,
+        }
+    }
+
+    pub fn framebuffer(&self, image_index: u32) -> vk::Framebuffer {
+        match self {
+            Self::Swapchain { swapchain, .. } => swapchain.framebuffers[image_index as usize],
+            Self::Offscreen(image) => image.framebuffer,
+        }
+    }
+
+    /// Recreates a swapchain so that the next frame can be copied with
+    /// [`RenderTarget::record_keep_frame`], which is not possible if the surface doesn't support
+    /// it. Offscreen images are read back directly. The device must be idle.
+    pub fn begin_capture(
+        &mut self,
+        instance: &Instance,
+        device: &Device,
+        render_pass: vk::RenderPass,
+        size: (u32, u32),
+    ) {
+        if let Self::Swapchain { capturing, .. } = self {
+            *capturing = true;
+            self.recreate(instance, device, render_pass, size);
+        }
+    }
+
+    /// Records a copy of the swapchain image that was just rendered to while capturing, so that
+    /// it can still be read back after it was presented
+    pub unsafe fn record_keep_frame(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        image_index: u32,
+    ) {
+        if let Self::Swapchain {
+            swapchain,
+            last_frame: Some(last_frame),
+            ..
+        } = self
+        {
+            last_frame.record_copy_from(
+                device,
+                command_buffer,
+                swapchain.images[image_index as usize],
+            );
+        }
+    }
+
+    /// Recreates a swapchain without copying again, so the following frames are not slowed down.
+    /// The captured frame is kept. The device must be idle.
+    pub fn end_capture(
+        &mut self,
+        instance: &Instance,
+        device: &Device,
+        render_pass: vk::RenderPass,
+        size: (u32, u32),
+    ) {
+        let attachment_config = self.attachment_config();
+        if let Self::Swapchain {
+            surface,
+            config,
+            swapchain,
+            capturing,
+            ..
+        } = self
+        {
+            *capturing = false;
+            replace_swapchain(
+                instance,
+                device,
+                surface,
+                *config,
+                swapchain,
+                attachment_config,
+                render_pass,
+                size,
+                false,
+            );
+        }
+    }
+
+    /// The image holding the captured frame, `None` if the swapchain images can't be copied
+    pub fn captured_frame(&self) -> Option<FrameImage> {
+        match self {
+            Self::Swapchain { last_frame, .. } => last_frame
+                .as_ref()
+                .map(|last_frame| last_frame.frame_image()),
+            Self::Offscreen(image) => Some(FrameImage {
+                image: image.image,
+                format: image.format,
+                extent: image.extent,
+            }),
+        }
+    }
+
+    /// Returns the index of the acquired image, or `None` if the target has to be recreated.
+    /// `semaphore` is only signaled for swapchains.
+    pub fn acquire(&self, semaphore: vk::Semaphore) -> Option<u32> {
+        match self {
+            Self::Swapchain { swapchain, .. } => swapchain.acquire(semaphore),
+            Self::Offscreen(_) => Some(0),
+        }
+    }
+
+    /// The semaphores a submission rendering to `image_index` has to wait on and signal
+    pub fn submit_semaphores(
+        &self,
+        image_available: vk::Semaphore,
+        image_index: u32,
+    ) -> (Option<vk::Semaphore>, Option<vk::Semaphore>) {
+        match self {
+            Self::Swapchain { swapchain, .. } => (
+                Some(image_available),
+                Some(swapchain.render_finished[image_index as usize]),
+            ),
+            Self::Offscreen(_) => (None, None),
+        }
+    }
+
+    /// Returns `false` if the target is out of date and should be recreated
+    pub fn present(&self, queue: vk::Queue, image_index: u32) -> bool {
+        match self {
+            Self::Swapchain { swapchain, .. } => swapchain.present(queue, image_index),
+            Self::Offscreen(_) => true,
+        }
+    }
+
+    /// The device must be idle. A captured frame is dropped, as it has the old size.
+    pub fn recreate(
+        &mut self,
+        instance: &Instance,
+        device: &Device,
+        render_pass: vk::RenderPass,
+        size: (u32, u32),
+    ) {
+        let attachment_config = self.attachment_config();
+        match self {
+            Self::Swapchain {
+                surface,
+                config,
+                swapchain,
+                capturing,
+                last_frame,
+            } => {
+                replace_swapchain(
+                    instance,
+                    device,
+                    surface,
+                    *config,
+                    swapchain,
+                    attachment_config,
+                    render_pass,
+                    size,
+                    *capturing,
+                );
+                if let Some(old) = last_frame.take() {
+                    unsafe { old.destroy(device) };
+                }
+                if swapchain.copyable {
+                    *last_frame = Some(LastFrame::new(
+                        device,
+                        config.format.format,
+                        swapchain.extent,
+                    ));
+                }
+            }
+            Self::Offscreen(image) => {
+                let new = OffscreenImage::new(
+                    device,
+                    render_pass,
+                    OFFSCREEN_FORMAT,
+                    attachment_config,
+                    size,
+                );
+                let old = std::mem::replace(image, new);
+                unsafe { old.destroy(device) };
+            }
+        }
+    }
+
+    pub unsafe fn destroy(&self, device: &Device) {
+        match self {
+            Self::Swapchain {
+                surface,
+                swapchain,
+                last_frame,
+                ..
+            } => {
+                if let Some(last_frame) = last_frame {
+                    last_frame.destroy(device);
+                }
+                swapchain.destroy(device);
+                surface.destroy();
+            }
+            Self::Offscreen(image) => image.destroy(device),
+        }
+    }
+}
+
+/// Replaces `swapchain` with a new one and destroys it
+#[allow(clippy::too_many_arguments)]
+fn replace_swapchain(
+    instance: &Instance,
+    device: &Device,
+    surface: &Surface,
+    config: SwapchainConfig,
+    swapchain: &mut Swapchain,
+    attachment_config: AttachmentConfig,
+    render_pass: vk::RenderPass,
+    size: (u32, u32),
+    capture: bool,
+) {
+    let new = Swapchain::new(
+        instance,
+        device,
+        surface,
+        config,
+        attachment_config,
+        render_pass,
+        size,
+        capture,
+        Some(swapchain),
+    );
+    let old = std::mem::replace(swapchain, new);
+    unsafe { old.destroy(device) };
+}
+
+/// A device-local image that can be rendered to and copied from
+pub struct OffscreenImage {
+    pub image: vk::Image,
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+    pub attachments: Attachments,
+    pub framebuffer: vk::Framebuffer,
+    pub format: vk::Format,
+    pub extent: vk::Extent2D,
+}
+
+impl OffscreenImage {
+    /// `render_pass` must be compatible with `format` and `attachment_config`
+    pub fn new(
+        device: &Device,
+        render_pass: vk::RenderPass,
+        format: vk::Format,
+        attachment_config: AttachmentConfig,
+        size: (u32, u32),
+    ) -> Self {
+        let extent = vk::Extent2D {
+            width: size.0,
+            height: size.1,
+        };
+
+        let create_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(format)
+            .extent(extent.into())
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+        let image = unsafe { device.device.create_image(&create_info, None) }.unwrap();
+
+        let requirements = unsafe { device.device.get_image_memory_requirements(image) };
+        let allocate_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(requirements.size)
+            .memory_type_index(device.find_memory_type(
+                requirements.memory_type_bits,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            ));
+        let memory = unsafe { device.device.allocate_memory(&allocate_info, None) }.unwrap();
+
+        unsafe { device.device.bind_image_memory(image, memory, 0) }.unwrap();
+
+        let view_info = vk::ImageViewCreateInfo::default()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(format)
+            .subresource_range(COLOR_SUBRESOURCE_RANGE);
+        let view = unsafe { device.device.create_image_view(&view_info, None) }.unwrap();
+
+        let attachments = Attachments::new(device, attachment_config, format, extent);
+
+        let views = attachments.framebuffer_views(view);
+        let framebuffer_info = vk::FramebufferCreateInfo::default()
+            .render_pass(render_pass)
+            .attachments(&views)
+            .width(extent.width)
+            .height(extent.height)
+            .layers(1);
+        let framebuffer =
+            unsafe { device.device.create_framebuffer(&framebuffer_info, None) }.unwrap();
+
+        Self {
+            image,
+            memory,
+            view,
+            attachments,
+            framebuffer,
+            format,
+            extent,
+        }
+    }
+
+    pub unsafe fn destroy(&self, device: &Device) {
+        device.device.destroy_framebuffer(self.framebuffer, None);
+        device.device.destroy_image_view(self.view, None);
+        device.device.destroy_image(self.image, None);
+        device.device.free_memory(self.memory, None);
+        self.attachments.destroy(device);
+    }
+}
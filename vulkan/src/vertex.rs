@@ -0,0 +1,46 @@
+//! Vertex input layouts for the attribute sets of meshes, the same as in the wgpu backend: every
+//! attribute is stored in its own buffer, which is bound at the index of the attribute in the set.
+
+use ash::vk;
+use common::{VertexAttribute, VertexAttributes};
+
+pub fn format(attribute: VertexAttribute) -> vk::Format {
+    match attribute.components() {
+        2 => vk::Format::R32G32_SFLOAT,
+        3 => vk::Format::R32G32B32_SFLOAT,
+        4 => vk::Format::R32G32B32A32_SFLOAT,
+        components => unreachable!("no vertex format with {components} components"),
+    }
+}
+
+/// Size of the attribute of one vertex in bytes
+pub fn size(attribute: VertexAttribute) -> u64 {
+    (attribute.components() * std::mem::size_of::<f32>()) as u64
+}
+
+pub fn bindings(attributes: VertexAttributes) -> Vec<vk::VertexInputBindingDescription> {
+    attributes
+        .iter()
+        .enumerate()
+        .map(|(binding, attribute)| vk::VertexInputBindingDescription {
+            binding: binding as u32,
+            stride: size(attribute) as u32,
+            input_rate: vk::VertexInputRate::VERTEX,
+        })
+        .collect()
+}
+
+pub fn attribute_descriptions(
+    attributes: VertexAttributes,
+) -> Vec<vk::VertexInputAttributeDescription> {
+    attributes
+        .iter()
+        .enumerate()
+        .map(|(binding, attribute)| vk::VertexInputAttributeDescription {
+            location: attribute.location(),
+            binding: binding as u32,
+            format: format(attribute),
+            offset: 0,
+        })
+        .collect()
+}
@@ -0,0 +1,254 @@
+//! Meshes are grouped by their vertex attributes, as each attribute set needs its own vertex
+//! buffer layout and shader variant.
+
+use std::{collections::BTreeMap, ops::Range};
+
+use common::{BufferStats, Mesh, MeshHandle, RangeAllocator, VertexAttributes};
+use wgpu::{
+    BufferUsages, ColorTargetState, ColorWrites, DepthStencilState, Device, FragmentState, MultisampleState,
+    PipelineCompilationOptions, PipelineLayout, PrimitiveState, Queue, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, TextureFormat, VertexState,
+};
+
+use crate::{buffer::GrowableBuffer, vertex};
+
+const SHADER_SOURCE: &str = include_str!("shader.wgsl");
+
+/// Capacities of new batches, before they grow
+const INITIAL_VERTICES: u64 = 1024;
+const INITIAL_INDICES: u64 = 3 * 1024;
+
+/// All meshes with the same vertex attributes. The meshes share one buffer per attribute and an
+/// index buffer, in which the ranges of unloaded meshes are reused.
+pub struct Batch {
+    pub attributes: VertexAttributes,
+    pipeline: RenderPipeline,
+    /// One per attribute, in the order of [`VertexAttributes::iter`]
+    vertex_buffers: Vec<GrowableBuffer>,
+    vertex_allocator: RangeAllocator,
+    index_buffer: GrowableBuffer,
+    index_allocator: RangeAllocator,
+    meshes: BTreeMap<MeshHandle, MeshRanges>,
+}
+
+/// Where a mesh is stored, in vertices and indices
+struct MeshRanges {
+    vertices: Range<u64>,
+    indices: Range<u64>,
+}
+
+impl Batch {
+    pub fn new(
+        device: &Device,
+        pipeline_layout: &PipelineLayout,
+        format: TextureFormat,
+        depth_stencil: Option<DepthStencilState>,
+        sample_count: u32,
+        attributes: VertexAttributes,
+    ) -> Self {
+        let vertex_buffers = attributes
+            .iter()
+            .map(|attribute| {
+                GrowableBuffer::new(
+                    device,
+                    attribute.define(),
+                    BufferUsages::VERTEX,
+                    INITIAL_VERTICES * vertex::format(attribute).size(),
+                )
+            })
+            .collect();
+
+        let index_buffer = GrowableBuffer::new(
+            device,
+            "indices",
+            BufferUsages::INDEX,
+            INITIAL_INDICES * INDEX_SIZE,
+        );
+
+        Self {
+            attributes,
+            pipeline: create_pipeline(
+                device,
+                pipeline_layout,
+                format,
+                depth_stencil,
+                sample_count,
+                attributes,
+            ),
+            vertex_buffers,
+            vertex_allocator: RangeAllocator::new(INITIAL_VERTICES),
+            index_buffer,
+            index_allocator: RangeAllocator::new(INITIAL_INDICES),
+            meshes: BTreeMap::new(),
+        }
+    }
+
+    pub fn contains(&self, handle: MeshHandle) -> bool {
+        self.meshes.contains_key(&handle)
+    }
+
+    /// The mesh must have exactly the attributes of the batch
+    pub fn insert(&mut self, device: &Device, queue: &Queue, handle: MeshHandle, mesh: &Mesh) {
+        assert_eq!(mesh.attributes(), self.attributes);
+        assert_eq!(mesh.indices.len() % 3, 0);
+
+        let vertices = self.allocate_vertices(device, queue, mesh.vertices.len() as u64);
+        for (attribute, buffer) in self.attributes.iter().zip(&self.vertex_buffers) {
+            let data = mesh.attribute_data(attribute).unwrap();
+            assert_eq!(
+                data.len(),
+                mesh.vertices.len() * attribute.components(),
+                "{attribute:?} needs one entry per vertex"
+            );
+
+            let offset = vertices.start * vertex::format(attribute).size();
+            buffer.write(queue, offset, bytemuck::cast_slice(&data));
+        }
+
+        // Indices stay relative to the mesh, the draw call offsets them
+        let indices = self.allocate_indices(device, queue, mesh.indices.len() as u64);
+        self.index_buffer.write(
+            queue,
+            indices.start * INDEX_SIZE,
+            bytemuck::cast_slice(&mesh.indices),
+        );
+
+        self.meshes.insert(handle, MeshRanges { vertices, indices });
+    }
+
+    /// Frees the ranges of the mesh for later meshes, panics if it is not in this batch
+    pub fn remove(&mut self, handle: MeshHandle) {
+        let ranges = self
+            .meshes
+            .remove(&handle)
+            .expect("mesh is not in this batch");
+        self.vertex_allocator.free(ranges.vertices);
+        self.index_allocator.free(ranges.indices);
+    }
+
+    pub fn buffer_stats(&self) -> [BufferStats; 2] {
+        [
+            BufferStats::of(
+                format!("{} vertices", self.attributes),
+                &self.vertex_allocator,
+            ),
+            BufferStats::of(
+                format!("{} indices", self.attributes),
+                &self.index_allocator,
+            ),
+        ]
+    }
+
+    /// The camera has to be bound already
+    pub fn draw(&self, rpass: &mut wgpu::RenderPass) {
+        if self.meshes.is_empty() {
+            return;
+        }
+
+        rpass.set_pipeline(&self.pipeline);
+        for (slot, buffer) in self.vertex_buffers.iter().enumerate() {
+            rpass.set_vertex_buffer(slot as u32, buffer.buffer().slice(..));
+        }
+        rpass.set_index_buffer(
+            self.index_buffer.buffer().slice(..),
+            wgpu::IndexFormat::Uint32,
+        );
+
+        for ranges in self.meshes.values() {
+            if ranges.indices.is_empty() {
+                continue;
+            }
+
+            let indices = ranges.indices.start as u32..ranges.indices.end as u32;
+            rpass.draw_indexed(indices, ranges.vertices.start as i32, 0..1);
+        }
+    }
+
+    fn allocate_vertices(&mut self, device: &Device, queue: &Queue, len: u64) -> Range<u64> {
+        if let Some(range) = self.vertex_allocator.allocate(len) {
+            return range;
+        }
+
+        let capacity = grown_capacity(&self.vertex_allocator, len);
+        for (attribute, buffer) in self.attributes.iter().zip(&mut self.vertex_buffers) {
+            buffer.grow(device, queue, capacity * vertex::format(attribute).size());
+        }
+        self.vertex_allocator.grow(capacity);
+
+        self.vertex_allocator.allocate(len).unwrap()
+    }
+
+    fn allocate_indices(&mut self, device: &Device, queue: &Queue, len: u64) -> Range<u64> {
+        if let Some(range) = self.index_allocator.allocate(len) {
+            return range;
+        }
+
+        let capacity = grown_capacity(&self.index_allocator, len);
+        self.index_buffer.grow(device, queue, capacity * INDEX_SIZE);
+        self.index_allocator.grow(capacity);
+
+        self.index_allocator.allocate(len).unwrap()
+    }
+}
+
+const INDEX_SIZE: u64 = std::mem::size_of::<u32>() as u64;
+
+/// At least doubles the capacity, so that growing is rare. The added space alone fits `len`, so
+/// the allocation succeeds regardless of fragmentation.
+fn grown_capacity(allocator: &RangeAllocator, len: u64) -> u64 {
+    let capacity = allocator.capacity();
+    (capacity * 2).max(capacity + len)
+}
+
+fn create_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    format: TextureFormat,
+    depth_stencil: Option<DepthStencilState>,
+    sample_count: u32,
+    attributes: VertexAttributes,
+) -> RenderPipeline {
+    let shader_module = device.create_shader_module(ShaderModuleDescriptor {
+        label: Some(&format!("shader {attributes:?}")),
+        source: ShaderSource::Wgsl(common::shader_variant(SHADER_SOURCE, attributes).into()),
+    });
+
+    let buffer_attributes = vertex::buffer_attributes(attributes);
+
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        layout: Some(layout),
+        vertex: VertexState {
+            module: &shader_module,
+            entry_point: "vertex_main",
+            buffers: &vertex::buffer_layouts(&buffer_attributes),
+            compilation_options: PipelineCompilationOptions::default(),
+        },
+        primitive: PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            unclipped_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        },
+        fragment: Some(FragmentState {
+            module: &shader_module,
+            entry_point: "fragment_main",
+            targets: &[Some(ColorTargetState {
+                format,
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            })],
+            compilation_options: PipelineCompilationOptions::default(),
+        }),
+        label: None,
+        depth_stencil,
+        multisample: MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+        cache: None,
+    })
+}
@@ -0,0 +1,60 @@
+//! Buffers that can grow while keeping their contents.
+
+use wgpu::{Buffer, BufferDescriptor, BufferUsages, Device, Queue};
+
+/// Grows on request, copying the existing contents into the larger buffer on the GPU so they
+/// never have to be kept on the CPU. Which ranges are in use is up to the owner.
+pub struct GrowableBuffer {
+    buffer: Buffer,
+    label: &'static str,
+    usage: BufferUsages,
+}
+
+impl GrowableBuffer {
+    pub fn new(device: &Device, label: &'static str, usage: BufferUsages, size: u64) -> Self {
+        // Growing copies from the old buffer into the new one
+        let usage = usage | BufferUsages::COPY_SRC | BufferUsages::COPY_DST;
+
+        Self {
+            buffer: create_buffer(device, label, usage, size),
+            label,
+            usage,
+        }
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    /// `offset` and the length of `data` must be multiples of 4
+    pub fn write(&self, queue: &Queue, offset: u64, data: &[u8]) {
+        queue.write_buffer(&self.buffer, offset, data);
+    }
+
+    /// Replaces the buffer with one of `size` bytes, which must not be smaller
+    pub fn grow(&mut self, device: &Device, queue: &Queue, size: u64) {
+        let current = self.buffer.size();
+        assert!(size >= current, "buffers can't shrink");
+
+        let grown = create_buffer(device, self.label, self.usage, size);
+
+        // Pending writes to the old buffer are executed at the start of this submission, so
+        // they are part of the copy. The old buffer is freed once frames in flight are done.
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("grow buffer"),
+        });
+        encoder.copy_buffer_to_buffer(&self.buffer, 0, &grown, 0, current);
+        queue.submit(Some(encoder.finish()));
+
+        self.buffer = grown;
+    }
+}
+
+fn create_buffer(device: &Device, label: &str, usage: BufferUsages, size: u64) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some(label),
+        size,
+        usage,
+        mapped_at_creation: false,
+    })
+}
@@ -1,5 +1,4 @@
 use bytemuck::{Pod, Zeroable};
-use cgmath::{Matrix4, Perspective, Point3, Vector3};
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt}, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Buffer, BufferUsages, Device, Queue, ShaderStages
 };
@@ -12,13 +11,15 @@ struct CameraRaw {
 
 pub struct Camera {
     buffer: Buffer,
+    reverse_z: bool,
     bind_group_layout: BindGroupLayout,
     bind_group: BindGroup,
 }
 
 impl Camera {
-    pub fn new(device: &Device, initial_position: (f32, f32, f32), pitch: f32, yaw: f32, aspect_ratio: f32) -> Self {
-        let camera_raw = make_raw_camera(initial_position.into(), pitch_yaw_to_dir(pitch, yaw), aspect_ratio);
+    /// With `reverse_z`, the near plane is mapped to depth 1 and the far plane to 0
+    pub fn new(device: &Device, initial_position: (f32, f32, f32), pitch: f32, yaw: f32, aspect_ratio: f32, reverse_z: bool) -> Self {
+        let camera_raw = CameraRaw::new(initial_position, pitch, yaw, aspect_ratio, reverse_z);
 
         let buffer: Buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: None,
@@ -51,13 +52,14 @@ impl Camera {
 
         Self {
             buffer,
+            reverse_z,
             bind_group_layout,
             bind_group,
         }
     }
 
     pub fn update(&mut self, queue: &Queue, position: (f32, f32, f32), pitch: f32, yaw: f32, aspect_ratio: f32) {
-        let camera_raw = make_raw_camera(position.into(), pitch_yaw_to_dir(pitch, yaw), aspect_ratio);
+        let camera_raw = CameraRaw::new(position, pitch, yaw, aspect_ratio, self.reverse_z);
 
         queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[camera_raw]));
     }
@@ -75,26 +77,11 @@ impl Camera {
     }
 }
 
-
-fn pitch_yaw_to_dir(pitch: f32, yaw: f32) -> Vector3<f32> {
-    Vector3::new(yaw.cos() * pitch.cos(), pitch.sin(), pitch.cos() * yaw.sin())
-}
-
-fn make_raw_camera(eye: Point3<f32>, dir: Vector3<f32>, aspect_ratio: f32) -> CameraRaw {
-    let view = Matrix4::look_to_rh(eye, dir, Vector3::unit_y());
-    let proj = cgmath::perspective(cgmath::Deg(45.0), aspect_ratio, 0.1, 100.0);
-
-    let view_proj = OPENGL_TO_WGPU_MATRIX * proj * view;
-
-    CameraRaw {
-        view_proj: view_proj.into(),
+impl CameraRaw {
+    fn new(xyz: (f32, f32, f32), pitch: f32, yaw: f32, aspect_ratio: f32, reverse_z: bool) -> Self {
+        let camera = common::Camera { xyz, pitch, yaw };
+        Self {
+            view_proj: camera.view_proj(aspect_ratio, reverse_z),
+        }
     }
 }
-
-#[rustfmt::skip]
-pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
-    1.0, 0.0, 0.0, 0.0,
-    0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 0.5, 0.5,
-    0.0, 0.0, 0.0, 1.0,
-);
@@ -0,0 +1,75 @@
+//! Reads textures back into CPU memory.
+
+use common::Image;
+use wgpu::{
+    BufferDescriptor, BufferUsages, Device, Extent3d, ImageCopyBuffer, ImageCopyTexture,
+    ImageDataLayout, Maintain, MapMode, Origin3d, Queue, Texture, TextureAspect, TextureFormat,
+    COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+
+/// Copies the texture into a buffer and waits until it can be read.
+/// Only 8-bit RGBA and BGRA formats are supported, which covers the formats used by the targets.
+pub fn read_texture(device: &Device, queue: &Queue, texture: &Texture) -> Image {
+    let swap_red_blue = match texture.format() {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => false,
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => true,
+        format => panic!("capturing {format:?} textures is not supported"),
+    };
+
+    let (width, height) = (texture.width(), texture.height());
+
+    // Rows of texture to buffer copies have to be aligned, the padding is removed afterwards
+    let bytes_per_row =
+        (width * Image::BYTES_PER_PIXEL).next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("capture readback"),
+        size: (bytes_per_row * height) as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder =
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    encoder.copy_texture_to_buffer(
+        ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(MapMode::Read, move |result| sender.send(result).unwrap());
+    device.poll(Maintain::Wait).panic_on_timeout();
+    receiver
+        .recv()
+        .unwrap()
+        .expect("failed to map the capture buffer");
+
+    let mut image =
+        Image::from_padded_rows(width, height, bytes_per_row, &slice.get_mapped_range());
+    buffer.unmap();
+
+    if swap_red_blue {
+        image.swap_red_blue();
+    }
+
+    image
+}
@@ -0,0 +1,109 @@
+//! The depth buffer, set up according to [`DepthSettings`].
+
+use common::{DepthCompare, DepthFormat, DepthSettings};
+use wgpu::{
+    CompareFunction, DepthStencilState, Device, Extent3d, RenderPassDepthStencilAttachment,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView,
+    TextureViewDescriptor,
+};
+
+pub struct DepthBuffer {
+    settings: DepthSettings,
+    /// Same as the color target's
+    sample_count: u32,
+    view: TextureView,
+}
+
+impl DepthBuffer {
+    pub fn new(
+        device: &Device,
+        settings: DepthSettings,
+        sample_count: u32,
+        size: (u32, u32),
+    ) -> Self {
+        Self {
+            settings,
+            sample_count,
+            view: create_view(device, format(settings.format), sample_count, size),
+        }
+    }
+
+    /// Must be called whenever the render target is resized, the sizes have to match
+    pub fn resize(&mut self, device: &Device, size: (u32, u32)) {
+        self.view = create_view(
+            device,
+            format(self.settings.format),
+            self.sample_count,
+            size,
+        );
+    }
+
+    /// The depth state of pipelines that render into this depth buffer
+    pub fn state(&self) -> DepthStencilState {
+        DepthStencilState {
+            format: format(self.settings.format),
+            depth_write_enabled: true,
+            depth_compare: compare(self.settings.compare),
+            stencil: Default::default(),
+            bias: Default::default(),
+        }
+    }
+
+    /// Clears the depth buffer and discards it at the end of the pass, as it is not needed
+    /// after the frame
+    pub fn attachment(&self) -> RenderPassDepthStencilAttachment<'_> {
+        RenderPassDepthStencilAttachment {
+            view: &self.view,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(self.settings.clear_value()),
+                store: wgpu::StoreOp::Discard,
+            }),
+            stencil_ops: None,
+        }
+    }
+}
+
+fn create_view(
+    device: &Device,
+    format: TextureFormat,
+    sample_count: u32,
+    size: (u32, u32),
+) -> TextureView {
+    device
+        .create_texture(&TextureDescriptor {
+            label: Some("depth buffer"),
+            size: Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        })
+        .create_view(&TextureViewDescriptor::default())
+}
+
+pub fn format(format: DepthFormat) -> TextureFormat {
+    match format {
+        DepthFormat::Depth16Unorm => TextureFormat::Depth16Unorm,
+        DepthFormat::Depth24Plus => TextureFormat::Depth24Plus,
+        DepthFormat::Depth32Float => TextureFormat::Depth32Float,
+    }
+}
+
+fn compare(compare: DepthCompare) -> CompareFunction {
+    match compare {
+        DepthCompare::Never => CompareFunction::Never,
+        DepthCompare::Less => CompareFunction::Less,
+        DepthCompare::Equal => CompareFunction::Equal,
+        DepthCompare::LessEqual => CompareFunction::LessEqual,
+        DepthCompare::Greater => CompareFunction::Greater,
+        DepthCompare::NotEqual => CompareFunction::NotEqual,
+        DepthCompare::GreaterEqual => CompareFunction::GreaterEqual,
+        DepthCompare::Always => CompareFunction::Always,
+    }
+}
@@ -0,0 +1,163 @@
+//! Measures how long the GPU spends on render passes using timestamp queries.
+//!
+//! Timestamps of a frame are resolved into a buffer which is then mapped asynchronously.
+//! Multiple readback slots are used so that the CPU never has to wait for the GPU.
+
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    time::Duration,
+};
+
+use common::{GpuFrameTiming, GpuPassTiming};
+use wgpu::{
+    Buffer, BufferAsyncError, BufferDescriptor, BufferUsages, CommandEncoder, Device, Maintain,
+    MapMode, QuerySet, QuerySetDescriptor, QueryType, Queue, RenderPassTimestampWrites, QUERY_SIZE,
+};
+
+/// Number of frames whose timestamps can be in flight at the same time
+const SLOTS: usize = 4;
+/// Beginning and end of the main render pass
+const QUERIES_PER_SLOT: u32 = 2;
+
+const SLOT_SIZE: u64 = (QUERIES_PER_SLOT * QUERY_SIZE) as u64;
+
+struct Slot {
+    resolve_buffer: Buffer,
+    readback_buffer: Buffer,
+    /// The frame whose timestamps are currently stored in this slot
+    frame: Option<u64>,
+}
+
+pub struct GpuTimer {
+    query_set: QuerySet,
+    slots: Vec<Slot>,
+    /// Slot used by the frame that is currently being encoded
+    current_slot: Option<usize>,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    map_sender: Sender<(usize, Result<(), BufferAsyncError>)>,
+    map_receiver: Receiver<(usize, Result<(), BufferAsyncError>)>,
+}
+
+impl GpuTimer {
+    /// The device must have been created with [`wgpu::Features::TIMESTAMP_QUERY`].
+    pub fn new(device: &Device, queue: &Queue) -> Self {
+        let query_set = device.create_query_set(&QuerySetDescriptor {
+            label: Some("gpu timer"),
+            ty: QueryType::Timestamp,
+            count: SLOTS as u32 * QUERIES_PER_SLOT,
+        });
+
+        let slots = (0..SLOTS)
+            .map(|_| Slot {
+                resolve_buffer: device.create_buffer(&BufferDescriptor {
+                    label: Some("gpu timer resolve"),
+                    size: SLOT_SIZE,
+                    usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                }),
+                readback_buffer: device.create_buffer(&BufferDescriptor {
+                    label: Some("gpu timer readback"),
+                    size: SLOT_SIZE,
+                    usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                frame: None,
+            })
+            .collect();
+
+        let (map_sender, map_receiver) = mpsc::channel();
+
+        Self {
+            query_set,
+            slots,
+            current_slot: None,
+            period: queue.get_timestamp_period(),
+            map_sender,
+            map_receiver,
+        }
+    }
+
+    /// Reserves a slot for `frame`. Returns `None` if all slots are still in use, in which case
+    /// this frame will not be timed.
+    pub fn begin_frame(&mut self, frame: u64) -> Option<RenderPassTimestampWrites<'_>> {
+        let index = self.slots.iter().position(|slot| slot.frame.is_none())?;
+        self.slots[index].frame = Some(frame);
+        self.current_slot = Some(index);
+
+        let first_query = index as u32 * QUERIES_PER_SLOT;
+        Some(RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(first_query),
+            end_of_pass_write_index: Some(first_query + 1),
+        })
+    }
+
+    /// Copies the timestamps of the current frame into its readback buffer
+    pub fn resolve(&self, encoder: &mut CommandEncoder) {
+        let Some(index) = self.current_slot else {
+            return;
+        };
+        let slot = &self.slots[index];
+
+        let first_query = index as u32 * QUERIES_PER_SLOT;
+        encoder.resolve_query_set(
+            &self.query_set,
+            first_query..first_query + QUERIES_PER_SLOT,
+            &slot.resolve_buffer,
+            0,
+        );
+        encoder.copy_buffer_to_buffer(&slot.resolve_buffer, 0, &slot.readback_buffer, 0, SLOT_SIZE);
+    }
+
+    /// Must be called after the command buffer containing [`Self::resolve`] was submitted
+    pub fn end_frame(&mut self) {
+        let Some(index) = self.current_slot.take() else {
+            return;
+        };
+
+        let sender = self.map_sender.clone();
+        self.slots[index]
+            .readback_buffer
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                // The receiver only goes away together with the buffers
+                let _ = sender.send((index, result));
+            });
+    }
+
+    /// Returns the timings of all frames whose timestamps have been read back since the last call
+    pub fn collect(&mut self, device: &Device) -> Vec<GpuFrameTiming> {
+        device.poll(Maintain::Poll);
+
+        let mut timings = Vec::new();
+        while let Ok((index, result)) = self.map_receiver.try_recv() {
+            let slot = &mut self.slots[index];
+            let frame = slot
+                .frame
+                .take()
+                .expect("mapped slot must belong to a frame");
+
+            if result.is_err() {
+                continue;
+            }
+
+            let timestamps: [u64; QUERIES_PER_SLOT as usize] = {
+                let data = slot.readback_buffer.slice(..).get_mapped_range();
+                bytemuck::pod_read_unaligned(&data)
+            };
+            slot.readback_buffer.unmap();
+
+            let ticks = timestamps[1].saturating_sub(timestamps[0]);
+            timings.push(GpuFrameTiming {
+                frame,
+                passes: vec![GpuPassTiming {
+                    name: "main",
+                    duration: Duration::from_nanos((ticks as f64 * self.period as f64) as u64),
+                }],
+            });
+        }
+
+        timings
+    }
+}
@@ -1,54 +1,71 @@
-use std::{num::NonZeroU32, sync::Arc};
+use std::num::NonZeroU32;
 
+use batch::Batch;
 use camera::Camera;
-use common::{HasWindowAndDisplayHandle, Mesh};
-use vertex::Vertex;
+use common::{
+    BufferStats, GpuFrameTiming, HasWindowAndDisplayHandle, Image, Mesh, MeshHandle, RendererInfo,
+    RendererSettings, SampleCount, VertexAttributes,
+};
+use depth::DepthBuffer;
+use gpu_timer::GpuTimer;
+use msaa::MsaaTarget;
+use target::RenderTarget;
 use wgpu::{
-    include_wgsl,
-    util::{BufferInitDescriptor, DeviceExt},
-    Adapter, Backends, BindGroup, BindGroupLayout, Buffer, BufferAddress, BufferDescriptor,
-    BufferUsages, ColorTargetState, ColorWrites, Device, DeviceDescriptor, Features, FragmentState,
-    Instance, InstanceDescriptor, InstanceFlags, MultisampleState, PipelineCompilationOptions,
-    PipelineLayoutDescriptor, PrimitiveState, Queue, RenderPipeline, RenderPipelineDescriptor,
-    RequestAdapterOptions, Surface, SurfaceConfiguration, VertexState,
+    Adapter, Backends, Device, DeviceDescriptor, Features, Instance, InstanceDescriptor,
+    InstanceFlags, PipelineLayout, PipelineLayoutDescriptor, Queue, RequestAdapterOptions,
 };
 
 pub mod vertex;
+mod batch;
+mod buffer;
 mod camera;
-
-const MAX_VERTICES: usize = 100;
-const MAX_INDICES: usize = 100;
+mod capture;
+mod depth;
+mod gpu_timer;
+mod msaa;
+mod target;
+
+const CLEAR_COLOR: wgpu::Color = wgpu::Color {
+    r: 0.4,
+    g: 0.9,
+    b: 1.0,
+    a: 1.0,
+};
 
 struct WgpuRenderer {
     adapter: Adapter,
     device: Device,
     queue: Queue,
-    surface: Surface<'static>,
-    surface_config: SurfaceConfiguration,
-    render_pipeline: RenderPipeline,
-    vertex_buffer: Buffer,
-    num_vertices: usize,
-    index_buffer: Buffer,
-    num_indices: usize,
+    target: RenderTarget,
+    /// `None` if depth testing is disabled
+    depth: Option<DepthBuffer>,
+    /// Negotiated from the requested sample count, see [`msaa::sample_count`]
+    sample_count: SampleCount,
+    /// `None` without MSAA, in which case the render target is rendered into directly
+    msaa: Option<MsaaTarget>,
+    pipeline_layout: PipelineLayout,
+    /// One per vertex attribute set of the loaded meshes, in the order they were first loaded
+    batches: Vec<Batch>,
+    /// The id of the next loaded mesh
+    next_mesh: u64,
     camera: Camera,
+    /// `None` if the adapter does not support timestamp queries
+    gpu_timer: Option<GpuTimer>,
+    /// Number of frames rendered so far
+    frame: u64,
+    /// Set by [`common::Renderer::request_capture`] until the next frame was rendered
+    capture_requested: bool,
+    /// Whether a frame was captured since the target was resized and can be read back
+    frame_captured: bool,
 }
 
 impl common::Renderer for WgpuRenderer {
     fn new(
         window: impl HasWindowAndDisplayHandle + Send + Sync + 'static,
         initial_window_size: (u32, u32),
+        settings: &RendererSettings,
     ) -> Self {
-        const DEVICE_FEATURES: Features = Features::empty();
-
-        let instance = Instance::new(InstanceDescriptor {
-            backends: Backends::PRIMARY,
-            flags: if cfg!(debug_assertions) {
-                InstanceFlags::debugging()
-            } else {
-                InstanceFlags::empty()
-            },
-            ..Default::default()
-        });
+        let instance = create_instance();
 
         let surface = instance.create_surface(window).unwrap();
 
@@ -59,205 +76,347 @@ impl common::Renderer for WgpuRenderer {
         }))
         .unwrap();
 
-        let (device, queue) = pollster::block_on(adapter.request_device(
-            &DeviceDescriptor {
-                required_features: DEVICE_FEATURES,
-                ..Default::default()
-            },
-            None,
-        ))
-        .unwrap();
+        let (device, queue) = request_device(&adapter);
+        let target = RenderTarget::surface(
+            surface,
+            &adapter,
+            &device,
+            initial_window_size,
+            settings.present_mode,
+            settings.frame_latency(),
+        );
 
-        let surface_caps = surface.get_capabilities(&adapter);
-        let surface_format = surface_caps
-            .formats
-            .iter()
-            .find(|f| f.is_srgb())
-            .copied()
-            .unwrap_or(surface_caps.formats[0]);
-        let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width: initial_window_size.0,
-            height: initial_window_size.1,
-            present_mode: surface_caps.present_modes[0],
-            alpha_mode: surface_caps.alpha_modes[0],
-            view_formats: vec![],
-            desired_maximum_frame_latency: 2,
-        };
-        surface.configure(&device, &surface_config);
+        Self::with_target(adapter, device, queue, target, settings)
+    }
 
-        let vertex_buffer = device.create_buffer(&BufferDescriptor {
-            label: None,
-            size: (MAX_VERTICES * std::mem::size_of::<Vertex>()) as u64,
-            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+    fn new_headless(size: (u32, u32), settings: &RendererSettings) -> Self {
+        let instance = create_instance();
 
-        let index_buffer = device.create_buffer(&BufferDescriptor {
-            label: None,
-            size: (MAX_INDICES * std::mem::size_of::<u32>()) as u64,
-            usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+        let adapter = pollster::block_on(instance.request_adapter(&RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: None,
+            ..Default::default()
+        }))
+        .expect("no suitable adapter found");
 
-        let aspect_ratio = surface_config.width as f32 / surface_config.height as f32;
-        let camera = Camera::new(&device, (0.0, 0.0, 0.0), 0.0, 1.0, aspect_ratio);
+        let (device, queue) = request_device(&adapter);
+        let target = RenderTarget::offscreen(&device, size);
 
-        let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &[camera.bind_group_layout()],
-            push_constant_ranges: &[],
-        });
+        Self::with_target(adapter, device, queue, target, settings)
+    }
 
-        let vertex_shader = include_wgsl!("shader.wgsl");
-        let shader_module = device.create_shader_module(vertex_shader);
+    fn render(&mut self, camera: common::Camera) {
+        {
+            let _phase = common::phase("camera_upload");
+            self.camera.update_with_camera(&self.queue, camera, self.target.aspect_ratio());
+        }
 
-        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            layout: Some(&render_pipeline_layout),
-            vertex: VertexState {
-                module: &shader_module,
-                entry_point: "vertex_main",
-                buffers: &[Vertex::layout()],
-                compilation_options: PipelineCompilationOptions::default(),
-            },
-            primitive: PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            fragment: Some(FragmentState {
-                module: &shader_module,
-                entry_point: "fragment_main",
-                targets: &[Some(ColorTargetState {
-                    format: surface_config.format,
-                    blend: None,
-                    write_mask: ColorWrites::ALL,
-                })],
-                compilation_options: PipelineCompilationOptions::default(),
-            }),
-            label: None,
-            depth_stencil: None,
-            multisample: MultisampleState::default(),
-            multiview: None,
-            cache: None,
-        });
+        let frame = {
+            let _phase = common::phase("acquire");
+            self.target.acquire()
+        };
 
-        Self {
-            adapter,
-            device,
-            queue,
-            surface,
-            surface_config,
-            render_pipeline,
-            vertex_buffer,
-            num_vertices: 0,
-            index_buffer,
-            num_indices: 0,
-            camera,
-        }
-    }
+        let command_buffer = {
+            let _phase = common::phase("encode");
+
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+            let timestamp_writes = self
+                .gpu_timer
+                .as_mut()
+                .and_then(|timer| timer.begin_frame(self.frame));
+            {
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[Some(color_attachment(self.msaa.as_ref(), &frame.view))],
+                    depth_stencil_attachment: self.depth.as_ref().map(DepthBuffer::attachment),
+                    timestamp_writes,
+                    occlusion_query_set: None,
+                });
+                self.draw_scene(&mut rpass);
+            }
 
-    fn render(&mut self, camera: common::Camera) {
-        self.camera.update_with_camera(&self.queue, camera, self.surface_config.width as f32 / self.surface_config.height as f32);
+            if let Some(timer) = &self.gpu_timer {
+                timer.resolve(&mut encoder);
+            }
+
+            // After the timed render pass, so the copy doesn't count towards the frame time
+            if self.capture_requested {
+                self.target.keep_frame(&mut encoder, &frame);
+            }
 
-        let frame = self.surface.get_current_texture().unwrap();
+            encoder.finish()
+        };
 
-        let view = frame
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        {
+            let _phase = common::phase("submit");
+            self.queue.submit(Some(command_buffer));
+        }
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.end_frame();
+        }
 
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
         {
-            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: None,
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {r: 0.4, g: 0.9, b: 1.0, a: 1.0}),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-            if self.num_indices > 0 && self.num_vertices > 0 {
-                rpass.set_pipeline(&self.render_pipeline);
-
-                rpass.set_vertex_buffer(
-                    0,
-                    self.vertex_buffer
-                        .slice(..),
-                );
-                rpass.set_index_buffer(
-                    self.index_buffer.slice(..),
-                    wgpu::IndexFormat::Uint32,
-                );
-
-                rpass.set_bind_group(0, self.camera.bind_group(), &[]);
-
-                rpass.draw_indexed(0..self.num_indices as u32, 0, 0..1);
-            }
+            let _phase = common::phase("present");
+            frame.present();
         }
+        self.frame += 1;
 
-        self.queue.submit(Some(encoder.finish()));
-        frame.present();
+        if self.capture_requested {
+            self.target.end_capture(&self.device);
+            self.capture_requested = false;
+            self.frame_captured = true;
+        }
     }
 
     fn resize(&mut self, size: (NonZeroU32, NonZeroU32), _scale_factor: f64) {
-        // Reconfigure the surface with the new size
-        self.surface_config.width = size.0.get();
-        self.surface_config.height = size.1.get();
-        self.surface.configure(&self.device, &self.surface_config);
+        let size = (size.0.get(), size.1.get());
+        self.target.resize(&self.device, size);
+        self.frame_captured = false;
+        if let Some(depth) = &mut self.depth {
+            depth.resize(&self.device, size);
+        }
+        if let Some(msaa) = &mut self.msaa {
+            msaa.resize(&self.device, size);
+        }
+    }
+
+    fn load_mesh(&mut self, mesh: Mesh) -> MeshHandle {
+        let handle = MeshHandle(self.next_mesh);
+        self.next_mesh += 1;
+
+        self.insert_mesh(handle, &mesh);
+        handle
+    }
+
+    fn unload_mesh(&mut self, handle: MeshHandle) {
+        self.batch_of(handle).remove(handle);
+    }
+
+    /// Space that was freed by the old mesh is reused if the new one has the same attributes
+    fn update_mesh(&mut self, handle: MeshHandle, mesh: Mesh) {
+        self.unload_mesh(handle);
+        self.insert_mesh(handle, &mesh);
+    }
+
+    fn info(&self) -> RendererInfo {
+        let info = self.adapter.get_info();
+
+        RendererInfo {
+            backend: "wgpu".to_owned(),
+            adapter: format!("{} ({:?}, {:?})", info.name, info.backend, info.device_type),
+            driver: format!("{} {}", info.driver, info.driver_info),
+            msaa: self.sample_count,
+            present_mode: self.target.present_mode(),
+            max_frame_latency: self.target.max_frame_latency(),
+            vertex_attributes: VertexAttributes::ALL,
+        }
     }
 
-    fn load_mesh(&mut self, mesh: Mesh) {
-        let Mesh {
-            vertices,
-            mut indices,
-        } = mesh;
+    fn gpu_timings(&mut self) -> Vec<GpuFrameTiming> {
+        match &mut self.gpu_timer {
+            Some(timer) => timer.collect(&self.device),
+            None => Vec::new(),
+        }
+    }
+
+    fn finish_gpu_timings(&mut self) {
+        // Also runs the callbacks of the readback buffers, which were mapped after submitting
+        self.device.poll(wgpu::Maintain::Wait);
+    }
 
-        let vertices: Vec<Vertex> = vertices.into_iter().map(Into::into).collect();
+    fn buffer_stats(&self) -> Vec<BufferStats> {
+        self.batches.iter().flat_map(Batch::buffer_stats).collect()
+    }
 
-        if self.num_vertices + vertices.len() > MAX_VERTICES {
-            panic!("Reached maximum amount of vertices")
+    /// Surface textures can't be read anymore once they were presented, so the surface is
+    /// configured for copying until the next frame was copied into a texture
+    fn request_capture(&mut self) {
+        if !self.capture_requested {
+            self.target.begin_capture(&self.device);
+            self.capture_requested = true;
         }
+    }
 
-        if self.num_indices + indices.len() > MAX_INDICES {
-            panic!("Reached maximum amount of indices")
+    /// Offscreen textures are read back directly, surface textures from their copy
+    fn capture(&mut self) -> Option<Image> {
+        if !std::mem::take(&mut self.frame_captured) {
+            return None;
         }
+        let texture = self.target.captured_frame()?;
+
+        Some(capture::read_texture(&self.device, &self.queue, texture))
+    }
+}
 
-        assert_eq!(indices.len() % 3, 0);
+impl WgpuRenderer {
+    fn draw_scene(&self, rpass: &mut wgpu::RenderPass) {
+        rpass.set_bind_group(0, self.camera.bind_group(), &[]);
 
-        // Offset indices
-        for index in &mut indices {
-            *index = *index + self.num_vertices as u32;
+        for batch in &self.batches {
+            batch.draw(rpass);
         }
+    }
 
-        self.queue.write_buffer(
-            &self.vertex_buffer,
-            (self.num_vertices * std::mem::size_of::<Vertex>()) as u64,
-            bytemuck::cast_slice(&vertices),
-        );
-        self.queue.write_buffer(
-            &self.index_buffer,
-            (self.num_indices * std::mem::size_of::<u32>()) as u64,
-            bytemuck::cast_slice(&indices),
-        );
+    fn insert_mesh(&mut self, handle: MeshHandle, mesh: &Mesh) {
+        let attributes = mesh.attributes();
 
-        self.num_vertices += vertices.len();
-        self.num_indices += indices.len();
+        let batch = match self
+            .batches
+            .iter()
+            .position(|batch| batch.attributes == attributes)
+        {
+            Some(index) => &mut self.batches[index],
+            None => {
+                self.batches.push(Batch::new(
+                    &self.device,
+                    &self.pipeline_layout,
+                    self.target.format(),
+                    self.depth.as_ref().map(DepthBuffer::state),
+                    self.sample_count.get(),
+                    attributes,
+                ));
+                self.batches.last_mut().unwrap()
+            }
+        };
+
+        batch.insert(&self.device, &self.queue, handle, mesh);
+    }
+
+    fn batch_of(&mut self, handle: MeshHandle) -> &mut Batch {
+        self.batches
+            .iter_mut()
+            .find(|batch| batch.contains(handle))
+            .unwrap_or_else(|| panic!("{handle:?} is not loaded"))
     }
+
+    fn with_target(
+        adapter: Adapter,
+        device: Device,
+        queue: Queue,
+        target: RenderTarget,
+        settings: &RendererSettings,
+    ) -> Self {
+        let gpu_timer = if device.features().contains(Features::TIMESTAMP_QUERY) {
+            Some(GpuTimer::new(&device, &queue))
+        } else {
+            println!("Timestamp queries are not supported, GPU timings will not be available");
+            None
+        };
+
+        let formats: Vec<_> = [target.format()]
+            .into_iter()
+            .chain(settings.depth.map(|depth| depth::format(depth.format)))
+            .collect();
+        let sample_count = msaa::sample_count(&adapter, &device, &formats, settings.msaa);
+        let msaa = (sample_count != SampleCount::ONE).then(|| {
+            MsaaTarget::new(&device, target.format(), sample_count.get(), target.size())
+        });
+
+        let depth = settings
+            .depth
+            .map(|depth| DepthBuffer::new(&device, depth, sample_count.get(), target.size()));
+
+        let reverse_z = settings.depth.is_some_and(|depth| depth.reverse_z);
+        let camera = Camera::new(&device, (0.0, 0.0, 0.0), 0.0, 1.0, target.aspect_ratio(), reverse_z);
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[camera.bind_group_layout()],
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            adapter,
+            device,
+            queue,
+            target,
+            depth,
+            sample_count,
+            msaa,
+            pipeline_layout,
+            batches: Vec::new(),
+            next_mesh: 0,
+            camera,
+            gpu_timer,
+            frame: 0,
+            capture_requested: false,
+            frame_captured: false,
+        }
+    }
+}
+
+/// Clears the color target and leaves the frame in `view`, resolving it first with MSAA
+fn color_attachment<'a>(
+    msaa: Option<&'a MsaaTarget>,
+    view: &'a wgpu::TextureView,
+) -> wgpu::RenderPassColorAttachment<'a> {
+    let load = wgpu::LoadOp::Clear(CLEAR_COLOR);
+    match msaa {
+        Some(msaa) => msaa.attachment(view, load),
+        None => wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        },
+    }
+}
+
+fn create_instance() -> Instance {
+    Instance::new(InstanceDescriptor {
+        backends: wgpu::util::backend_bits_from_env().unwrap_or(Backends::PRIMARY),
+        flags: if cfg!(debug_assertions) {
+            InstanceFlags::debugging()
+        } else {
+            InstanceFlags::empty()
+        },
+        ..Default::default()
+    })
+}
+
+fn request_device(adapter: &Adapter) -> (Device, Queue) {
+    const DEVICE_FEATURES: Features = Features::empty();
+    // Adapter specific format features allow all sample counts the adapter supports
+    const OPTIONAL_FEATURES: Features =
+        Features::TIMESTAMP_QUERY.union(Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+
+    pollster::block_on(adapter.request_device(
+        &DeviceDescriptor {
+            required_features: DEVICE_FEATURES | (adapter.features() & OPTIONAL_FEATURES),
+            ..Default::default()
+        },
+        None,
+    ))
+    .unwrap()
 }
 
 fn main() {
-    common::run_app::<WgpuRenderer>();
+    common::run::<WgpuRenderer>(common::Cli::from_env());
+}
+
+#[cfg(test)]
+mod tests {
+    use common::golden::{self, GoldenScene, Tolerance};
+    use wgpu::RequestAdapterOptions;
+
+    use super::{create_instance, WgpuRenderer};
+
+    /// Also finds software adapters like llvmpipe, see [`common::golden`]
+    fn adapter_available() -> bool {
+        pollster::block_on(create_instance().request_adapter(&RequestAdapterOptions::default()))
+            .is_some()
+    }
+
+    #[test]
+    fn triangles_match_golden_image() {
+        if !adapter_available() {
+            eprintln!("No adapter found, skipping the golden image test");
+            return;
+        }
+        golden::assert_matches_golden::<WgpuRenderer>(&GoldenScene::triangles(), &Tolerance::default());
+    }
 }
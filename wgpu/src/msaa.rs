@@ -0,0 +1,99 @@
+//! The multisampled color target that frames are rendered into with MSAA. It is resolved into the
+//! render target at the end of every render pass.
+
+use common::SampleCount;
+use wgpu::{
+    Adapter, Device, Extent3d, Features, Operations, RenderPassColorAttachment, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureUsages, TextureView, TextureViewDescriptor,
+};
+
+pub struct MsaaTarget {
+    format: TextureFormat,
+    sample_count: u32,
+    view: TextureView,
+}
+
+impl MsaaTarget {
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        size: (u32, u32),
+    ) -> Self {
+        Self {
+            format,
+            sample_count,
+            view: create_view(device, format, sample_count, size),
+        }
+    }
+
+    /// Must be called whenever the render target is resized, the sizes have to match
+    pub fn resize(&mut self, device: &Device, size: (u32, u32)) {
+        self.view = create_view(device, self.format, self.sample_count, size);
+    }
+
+    /// Renders into the multisampled texture and resolves it into `resolve_target`. The samples
+    /// themselves are discarded, as they are not needed after the frame.
+    pub fn attachment<'a>(
+        &'a self,
+        resolve_target: &'a TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> RenderPassColorAttachment<'a> {
+        RenderPassColorAttachment {
+            view: &self.view,
+            resolve_target: Some(resolve_target),
+            ops: Operations {
+                load,
+                store: wgpu::StoreOp::Discard,
+            },
+        }
+    }
+}
+
+/// Negotiates the sample count for rendering into `formats`. Without
+/// [`Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES`], wgpu only allows the sample counts that
+/// WebGPU guarantees, which are 1 and 4.
+pub fn sample_count(
+    adapter: &Adapter,
+    device: &Device,
+    formats: &[TextureFormat],
+    requested: SampleCount,
+) -> SampleCount {
+    requested.negotiate(|sample_count| {
+        formats.iter().all(|&format| {
+            let features = if device
+                .features()
+                .contains(Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+            {
+                adapter.get_texture_format_features(format)
+            } else {
+                format.guaranteed_format_features(device.features())
+            };
+            features.flags.sample_count_supported(sample_count.get())
+        })
+    })
+}
+
+fn create_view(
+    device: &Device,
+    format: TextureFormat,
+    sample_count: u32,
+    size: (u32, u32),
+) -> TextureView {
+    device
+        .create_texture(&TextureDescriptor {
+            label: Some("multisampled color target"),
+            size: Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        })
+        .create_view(&TextureViewDescriptor::default())
+}
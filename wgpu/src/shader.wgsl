@@ -1,3 +1,4 @@
+// Variants are generated with `common::shader_variant`, for the vertex attributes a mesh has
 
 struct Camera {
     view_proj: mat4x4<f32>,
@@ -5,26 +6,86 @@ struct Camera {
 @group(0) @binding(0)
 var<uniform> camera: Camera;
 
+// Locations are the same as `common::VertexAttribute::location`
 struct VertexInput {
-    @location(0) position: vec4<f32>,
+    @location(0) position: vec3<f32>,
+#ifdef NORMAL
+    @location(1) normal: vec3<f32>,
+#endif
+#ifdef TANGENT
+    @location(2) tangent: vec4<f32>,
+#endif
+#ifdef UV0
+    @location(3) uv0: vec2<f32>,
+#endif
+#ifdef COLOR
+    @location(4) color: vec4<f32>,
+#endif
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
-    @location(0) world_position: vec3<f32>
+    @location(0) world_position: vec3<f32>,
+#ifdef NORMAL
+    @location(1) normal: vec3<f32>,
+#endif
+#ifdef TANGENT
+    @location(2) tangent: vec4<f32>,
+#endif
+#ifdef UV0
+    @location(3) uv0: vec2<f32>,
+#endif
+#ifdef COLOR
+    @location(4) color: vec4<f32>,
+#endif
 }
 
 @vertex
 fn vertex_main(in: VertexInput) -> VertexOutput {
     var out: VertexOutput;
 
-    out.clip_position = camera.view_proj * vec4(in.position.xyz, 1.0);
-    out.world_position = in.position.xyz;
+    out.clip_position = camera.view_proj * vec4(in.position, 1.0);
+    out.world_position = in.position;
+#ifdef NORMAL
+    out.normal = in.normal;
+#endif
+#ifdef TANGENT
+    out.tangent = in.tangent;
+#endif
+#ifdef UV0
+    out.uv0 = in.uv0;
+#endif
+#ifdef COLOR
+    out.color = in.color;
+#endif
 
     return out;
 }
 
 @fragment
 fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(abs(in.world_position.xyz), 1.0);
-}
\ No newline at end of file
+    var color = abs(in.world_position);
+#ifdef COLOR
+    color *= in.color.rgb;
+#endif
+#ifdef UV0
+    // A procedural checkerboard stands in for a texture
+    let cell = floor(in.uv0 * 8.0);
+    if (cell.x + cell.y) % 2.0 != 0.0 {
+        color *= 0.5;
+    }
+#endif
+#ifdef NORMAL
+    var normal = normalize(in.normal);
+#ifdef TANGENT
+    // Tilts the normal back and forth along the tangent, like a normal map of ridges would
+    let tangent = normalize(in.tangent.xyz);
+    normal = normalize(normal + tangent * 0.5 * sin(dot(in.world_position, tangent) * 20.0));
+#endif
+    let light_direction = normalize(vec3(1.0, 3.0, 2.0));
+    let ambient = 0.2;
+    color *= ambient + (1.0 - ambient) * max(dot(normal, light_direction), 0.0);
+#endif
+
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,286 @@
+//! Where frames are rendered to: either a window surface or an offscreen texture.
+
+use common::PresentMode;
+use wgpu::{
+    Adapter, CommandEncoder, Device, Extent3d, Surface, SurfaceConfiguration, SurfaceTexture,
+    Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView,
+    TextureViewDescriptor,
+};
+
+/// The sRGB counterpart of what surfaces are usually configured with
+pub const OFFSCREEN_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+// There is only one target per renderer, so its size doesn't matter
+#[allow(clippy::large_enum_variant)]
+pub enum RenderTarget {
+    Surface {
+        surface: Surface<'static>,
+        config: SurfaceConfiguration,
+        /// Negotiated from the requested present mode
+        present_mode: PresentMode,
+        /// Whether the surface textures can be copied from, which they are only configured for
+        /// while a frame is captured
+        copyable: bool,
+        /// A copy of the captured frame, as surface textures can't be read anymore once they
+        /// were presented
+        last_frame: Option<Texture>,
+    },
+    Offscreen {
+        texture: Texture,
+    },
+}
+
+/// The texture a single frame is rendered into
+pub struct TargetFrame {
+    surface_texture: Option<SurfaceTexture>,
+    pub view: TextureView,
+}
+
+impl RenderTarget {
+    pub fn surface(
+        surface: Surface<'static>,
+        adapter: &Adapter,
+        device: &Device,
+        size: (u32, u32),
+        present_mode: PresentMode,
+        frame_latency: u32,
+    ) -> Self {
+        let surface_caps = surface.get_capabilities(adapter);
+        let present_mode = present_mode.negotiate(|mode| {
+            surface_caps
+                .present_modes
+                .contains(&wgpu_present_mode(mode))
+        });
+        let surface_format = surface_caps
+            .formats
+            .iter()
+            .find(|f| f.is_srgb())
+            .copied()
+            .unwrap_or(surface_caps.formats[0]);
+        let config = wgpu::SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            format: surface_format,
+            width: size.0,
+            height: size.1,
+            present_mode: wgpu_present_mode(present_mode),
+            alpha_mode: surface_caps.alpha_modes[0],
+            view_formats: vec![],
+            desired_maximum_frame_latency: frame_latency,
+        };
+        surface.configure(device, &config);
+
+        Self::Surface {
+            surface,
+            config,
+            present_mode,
+            copyable: surface_caps.usages.contains(TextureUsages::COPY_SRC),
+            last_frame: None,
+        }
+    }
+
+    pub fn offscreen(device: &Device, size: (u32, u32)) -> Self {
+        Self::Offscreen {
+            texture: create_offscreen_texture(device, OFFSCREEN_FORMAT, size),
+        }
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        match self {
+            Self::Surface { config, .. } => config.format,
+            Self::Offscreen { texture } => texture.format(),
+        }
+    }
+
+    /// `None` for offscreen targets, which are never presented
+    pub fn present_mode(&self) -> Option<PresentMode> {
+        match self {
+            Self::Surface { present_mode, .. } => Some(*present_mode),
+            Self::Offscreen { .. } => None,
+        }
+    }
+
+    /// `None` for offscreen targets, where the number of queued up frames is not limited
+    pub fn max_frame_latency(&self) -> Option<u32> {
+        match self {
+            Self::Surface { config, .. } => Some(config.desired_maximum_frame_latency),
+            Self::Offscreen { .. } => None,
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        match self {
+            Self::Surface { config, .. } => (config.width, config.height),
+            Self::Offscreen { texture } => (texture.width(), texture.height()),
+        }
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        let (width, height) = self.size();
+        width as f32 / height as f32
+    }
+
+    pub fn resize(&mut self, device: &Device, size: (u32, u32)) {
+        match self {
+            Self::Surface {
+                surface,
+                config,
+                last_frame,
+                ..
+            } => {
+                // Reconfigure the surface with the new size
+                config.width = size.0;
+                config.height = size.1;
+                surface.configure(device, config);
+                // The captured frame has the old size
+                *last_frame = None;
+            }
+            Self::Offscreen { texture } => {
+                *texture = create_offscreen_texture(device, OFFSCREEN_FORMAT, size)
+            }
+        }
+    }
+
+    pub fn acquire(&self) -> TargetFrame {
+        match self {
+            Self::Surface { surface, .. } => {
+                let surface_texture = surface.get_current_texture().unwrap();
+                let view = surface_texture
+                    .texture
+                    .create_view(&TextureViewDescriptor::default());
+
+                TargetFrame {
+                    surface_texture: Some(surface_texture),
+                    view,
+                }
+            }
+            Self::Offscreen { texture } => TargetFrame {
+                surface_texture: None,
+                view: texture.create_view(&TextureViewDescriptor::default()),
+            },
+        }
+    }
+
+    /// Prepares a surface for copying the next frame with [`RenderTarget::keep_frame`], which is
+    /// not possible if the surface doesn't support it. Offscreen textures are read back directly.
+    pub fn begin_capture(&mut self, device: &Device) {
+        if let Self::Surface {
+            surface,
+            config,
+            copyable: true,
+            last_frame,
+            ..
+        } = self
+        {
+            config.usage |= TextureUsages::COPY_SRC;
+            surface.configure(device, config);
+            *last_frame = Some(create_last_frame_texture(
+                device,
+                config.format,
+                (config.width, config.height),
+            ));
+        }
+    }
+
+    /// Records a copy of the frame if it belongs to a surface, so that it can still be read back
+    /// after it was presented
+    pub fn keep_frame(&self, encoder: &mut CommandEncoder, frame: &TargetFrame) {
+        if let (
+            Self::Surface {
+                last_frame: Some(last_frame),
+                ..
+            },
+            Some(surface_texture),
+        ) = (self, &frame.surface_texture)
+        {
+            encoder.copy_texture_to_texture(
+                surface_texture.texture.as_image_copy(),
+                last_frame.as_image_copy(),
+                last_frame.size(),
+            );
+        }
+    }
+
+    /// Configures a surface without copying again, so the following frames are not slowed down
+    pub fn end_capture(&mut self, device: &Device) {
+        if let Self::Surface {
+            surface, config, ..
+        } = self
+        {
+            if config.usage.contains(TextureUsages::COPY_SRC) {
+                config.usage = TextureUsages::RENDER_ATTACHMENT;
+                surface.configure(device, config);
+            }
+        }
+    }
+
+    /// The texture holding the captured frame, `None` if the surface textures can't be copied
+    pub fn captured_frame(&self) -> Option<&Texture> {
+        match self {
+            Self::Surface { last_frame, .. } => last_frame.as_ref(),
+            Self::Offscreen { texture } => Some(texture),
+        }
+    }
+}
+
+impl TargetFrame {
+    /// Presents the frame if it belongs to a surface
+    pub fn present(self) {
+        if let Some(surface_texture) = self.surface_texture {
+            surface_texture.present();
+        }
+    }
+}
+
+fn wgpu_present_mode(mode: PresentMode) -> wgpu::PresentMode {
+    match mode {
+        PresentMode::Fifo => wgpu::PresentMode::Fifo,
+        PresentMode::FifoRelaxed => wgpu::PresentMode::FifoRelaxed,
+        PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+        PresentMode::Immediate => wgpu::PresentMode::Immediate,
+    }
+}
+
+/// Creates a texture that can be rendered to and read back
+fn create_offscreen_texture(device: &Device, format: TextureFormat, size: (u32, u32)) -> Texture {
+    create_texture(
+        device,
+        "offscreen target",
+        format,
+        size,
+        TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+    )
+}
+
+/// Creates a texture that surface textures can be copied into and that can be read back
+fn create_last_frame_texture(device: &Device, format: TextureFormat, size: (u32, u32)) -> Texture {
+    create_texture(
+        device,
+        "last frame",
+        format,
+        size,
+        TextureUsages::COPY_DST | TextureUsages::COPY_SRC,
+    )
+}
+
+fn create_texture(
+    device: &Device,
+    label: &str,
+    format: TextureFormat,
+    size: (u32, u32),
+    usage: TextureUsages,
+) -> Texture {
+    device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size: Extent3d {
+            width: size.0,
+            height: size.1,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format,
+        usage,
+        view_formats: &[],
+    })
+}
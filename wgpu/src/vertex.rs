@@ -1,35 +1,41 @@
-use bytemuck::{Pod, Zeroable};
-use wgpu::{VertexAttribute, VertexBufferLayout};
+//! Vertex buffer layouts for the attribute sets of meshes. Every attribute is stored in its own
+//! buffer, so the layout of an attribute set is one buffer per attribute.
 
-#[repr(C)]
-#[derive(Copy, Clone, Pod, Zeroable)]
-pub struct Vertex {
-    pub position: [f32; 4],
-}
+use common::{VertexAttribute, VertexAttributes};
+use wgpu::{VertexBufferLayout, VertexFormat};
 
-impl Vertex {
-    pub fn new(position: impl Into<[f32; 3]>) -> Self {
-        let position = position.into();
-        Self {
-            position: [position[0], position[1], position[2], 0.0],
-        }
+pub fn format(attribute: VertexAttribute) -> VertexFormat {
+    match attribute.components() {
+        2 => VertexFormat::Float32x2,
+        3 => VertexFormat::Float32x3,
+        4 => VertexFormat::Float32x4,
+        components => unreachable!("no vertex format with {components} components"),
     }
+}
 
-    pub fn layout() -> VertexBufferLayout<'static> {
-        const ATTRIBUTES: &'static [VertexAttribute] = &wgpu::vertex_attr_array![0 => Float32x4];
+/// The attribute of each buffer, which [`buffer_layouts`] refers to
+pub fn buffer_attributes(attributes: VertexAttributes) -> Vec<[wgpu::VertexAttribute; 1]> {
+    attributes
+        .iter()
+        .map(|attribute| {
+            [wgpu::VertexAttribute {
+                format: format(attribute),
+                offset: 0,
+                shader_location: attribute.location(),
+            }]
+        })
+        .collect()
+}
 
-        wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Self>() as u64,
+pub fn buffer_layouts(
+    buffer_attributes: &[[wgpu::VertexAttribute; 1]],
+) -> Vec<VertexBufferLayout<'_>> {
+    buffer_attributes
+        .iter()
+        .map(|attributes| VertexBufferLayout {
+            array_stride: attributes[0].format.size(),
             step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: ATTRIBUTES,
-        }
-    }
+            attributes,
+        })
+        .collect()
 }
-
-impl From<common::Vertex> for Vertex {
-    fn from(value: common::Vertex) -> Self {
-        Self {
-            position: [value.xyz[0], value.xyz[1], value.xyz[2], 0.0],
-        }
-    }
-}
\ No newline at end of file